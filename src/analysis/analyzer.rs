@@ -1,16 +1,62 @@
+use super::history::SpectrumHistory;
+use super::onset::OnsetDetector;
+use super::structure::SectionInfo;
+use super::track::LookAhead;
 use rustfft::{FftPlanner, num_complex::Complex32};
+use serde::{Deserialize, Serialize};
 
+/// Tunable analysis parameters; the `[analyzer]` section of a preset.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct AnalyzerParams {
+    pub alpha_bands: f32,     // band smoothing; lower = more cinematic
+    pub alpha_bass_slow: f32, // drives colour and glow
+    pub alpha_bass_fast: f32, // drives the pulse
+    pub f_min: f32,
+    pub f_max: f32, // clamped to Nyquist
+    pub onset_sensitivity: f32,
+    pub onset_min_gap: usize, // frames
+    pub history: usize,       // spectra kept for the spectrogram
+}
+
+impl Default for AnalyzerParams {
+    fn default() -> Self {
+        Self {
+            alpha_bands: 0.12,
+            alpha_bass_slow: 0.08,
+            alpha_bass_fast: 0.30,
+            f_min: 20.0,
+            f_max: 18_000.0,
+            onset_sensitivity: 1.6,
+            onset_min_gap: 6,
+            history: 256,
+        }
+    }
+}
+
+#[derive(Clone, Default)]
 pub struct AnalysisFrame {
     pub bands: Vec<f32>,
     pub bass_fast: f32,
     pub bass_smooth: f32,
+
+    /// true on the frame a transient (kick, snare, pluck…) was detected
+    pub onset: bool,
+    /// how strongly the onset stood out above the adaptive threshold
+    pub onset_strength: f32,
+    /// spectral centroid as a position along the bands: 0 = lowest, 1 = highest
+    pub centroid: f32,
+    /// spectral flux: how much the spectrum rose since the previous frame
+    pub flux: f32,
+    /// what's coming, for file playback analyzed ahead; `None` when live
+    pub lookahead: Option<LookAhead>,
+    /// the part of the song this frame is in, once known
+    pub section: Option<SectionInfo>,
 }
 
 pub struct Analyzer {
     pub fft_size: usize,
     pub bars: usize,
-    pub f_min: f32,
-    pub f_max: f32,
 
     hann: Vec<f32>,
     fft_in: Vec<Complex32>,
@@ -21,15 +67,16 @@ pub struct Analyzer {
     bass_fast: f32,
     bass_smooth: f32,
 
-    alpha_bands: f32,
-    alpha_bass_slow: f32,
-    alpha_bass_fast: f32,
+    params: AnalyzerParams,
+
+    history: SpectrumHistory,
+    onsets: OnsetDetector,
 
     fft: std::sync::Arc<dyn rustfft::Fft<f32>>,
 }
 
 impl Analyzer {
-    pub fn new(sample_rate: u32, fft_size: usize, bars: usize) -> Self {
+    pub fn new(fft_size: usize, bars: usize) -> Self {
         let mut planner = FftPlanner::<f32>::new();
         let fft = planner.plan_fft_forward(fft_size);
         let hann = (0..fft_size)
@@ -40,13 +87,11 @@ impl Analyzer {
             })
             .collect::<Vec<_>>();
         let half = fft_size / 2;
-        let f_max = (sample_rate as f32 * 0.5).min(18_000.0);
+        let params = AnalyzerParams::default();
 
         Self {
             fft_size,
             bars,
-            f_min: 20.0,
-            f_max,
 
             hann,
             fft_in: vec![Complex32::new(0.0, 0.0); fft_size],
@@ -57,20 +102,50 @@ impl Analyzer {
             bass_fast: 0.0,
             bass_smooth: 0.0,
 
-            alpha_bands: 0.12,     //cinematic smooth
-            alpha_bass_slow: 0.08, //color/glow
-            alpha_bass_fast: 0.30, //pulse
+            history: SpectrumHistory::new(params.history, bars),
+            onsets: OnsetDetector::new(bars),
+            params,
 
             fft,
         }
     }
+
+    /// Applies new parameters without resetting the smoothing state. The
+    /// spectrum history starts over only if its length changed.
+    pub fn set_params(&mut self, params: &AnalyzerParams) {
+        if params.history.max(1) != self.history.capacity() {
+            self.history = SpectrumHistory::new(params.history, self.bars);
+        }
+        self.onsets.sensitivity = params.onset_sensitivity;
+        self.onsets.min_gap = params.onset_min_gap;
+        self.params = params.clone();
+    }
+
+    /// The parameters currently in effect.
+    pub fn params(&self) -> &AnalyzerParams {
+        &self.params
+    }
+
+    /// Raw (unsmoothed) dB spectra of the most recent frames.
+    pub fn history(&self) -> &SpectrumHistory {
+        &self.history
+    }
+
+    /// Keeps the spectrum history going from recorded bands when replaying
+    /// instead of analyzing. They were smoothed, so the spectrogram comes out
+    /// softer than live.
+    pub fn replay(&mut self, bands: &[f32]) {
+        // bands are the square root of the mean magnitude
+        let bands_db: Vec<f32> = bands.iter().map(|&b| 40.0 * b.max(1e-9).log10()).collect();
+        self.history.push(&bands_db);
+    }
+
     pub fn analyze(&mut self, window: &[f32], sample_rate: u32) -> AnalysisFrame {
         debug_assert_eq!(window.len(), self.fft_size);
 
         //window + complex input
-        for i in 0..self.fft_size {
-            let x = window[i] * self.hann[i];
-            self.fft_in[i] = Complex32::new(x, 0.0);
+        for (i, (&s, &w)) in window.iter().zip(&self.hann).enumerate() {
+            self.fft_in[i] = Complex32::new(s * w, 0.0);
             self.fft_out[i] = self.fft_in[i];
         }
 
@@ -90,18 +165,22 @@ impl Analyzer {
         let bass_raw = self.bass_energy_from_bins(sample_rate, 20.0, 120.0);
 
         //fast + smooth bass
-        self.bass_fast += self.alpha_bass_fast * (bass_raw - self.bass_fast);
-        self.bass_smooth += self.alpha_bass_slow * (bass_raw - self.bass_smooth);
+        let p = &self.params;
+        self.bass_fast += p.alpha_bass_fast * (bass_raw - self.bass_fast);
+        self.bass_smooth += p.alpha_bass_slow * (bass_raw - self.bass_smooth);
 
         //log bands
         let mut bands = vec![0.0f32; self.bars];
-        let r = self.f_max / self.f_min;
+        let mut bands_db = vec![0.0f32; self.bars];
+        let f_min = p.f_min.max(1.0);
+        let f_max = p.f_max.min(sample_rate as f32 * 0.5).max(f_min * 1.01);
+        let r = f_max / f_min;
 
         for b in 0..self.bars {
             let t0 = b as f32 / self.bars as f32;
             let t1 = (b + 1) as f32 / self.bars as f32;
-            let f0 = self.f_min * r.powf(t0);
-            let f1 = self.f_min * r.powf(t1);
+            let f0 = f_min * r.powf(t0);
+            let f1 = f_min * r.powf(t1);
 
             let (i0, i1) = self.freq_range_to_bin_range(sample_rate, f0, f1);
 
@@ -114,17 +193,37 @@ impl Analyzer {
             let avg: f32 = if count > 0.0 { sum / count } else { 0.0 };
 
             bands[b] = avg.sqrt();
+            bands_db[b] = 20.0 * avg.max(1e-9).log10();
         }
 
+        //keep the raw spectrum around before it gets smoothed away
+        self.history.push(&bands_db);
+
+        //transients and brightness come from the raw bands so smoothing can't blur them
+        let (onset, onset_strength) = self.onsets.process(&bands);
+        let total: f32 = bands.iter().sum();
+        let centroid = if total > 1e-6 {
+            let weighted: f32 = bands.iter().enumerate().map(|(b, &v)| b as f32 * v).sum();
+            weighted / total / (self.bars.max(2) - 1) as f32
+        } else {
+            0.0
+        };
+
         //smooth bands
-        for b in 0..self.bars {
-            self.smoothed_bands[b] += self.alpha_bands * (bands[b] - self.smoothed_bands[b]);
+        for (smoothed, &band) in self.smoothed_bands.iter_mut().zip(&bands) {
+            *smoothed += self.params.alpha_bands * (band - *smoothed);
         }
 
         AnalysisFrame {
             bands: self.smoothed_bands.clone(),
             bass_fast: self.bass_fast,
             bass_smooth: self.bass_smooth,
+            onset,
+            onset_strength,
+            flux: self.onsets.flux,
+            centroid,
+            lookahead: None,
+            section: None,
         }
     }
     fn freq_range_to_bin_range(&self, sample_rate: u32, f0: f32, f1: f32) -> (usize, usize) {
@@ -0,0 +1,58 @@
+/// Fixed-size ring buffer of the most recent spectra, one row per analysis frame.
+/// Values are stored in dB so quiet detail survives next to loud peaks.
+pub struct SpectrumHistory {
+    bins: usize,
+    capacity: usize,
+    rows: Vec<f32>, // capacity * bins, row-major
+    head: usize,    // row the next push goes into
+    len: usize,
+}
+
+impl SpectrumHistory {
+    pub fn new(capacity: usize, bins: usize) -> Self {
+        let capacity = capacity.max(1);
+        Self {
+            bins,
+            capacity,
+            rows: vec![f32::NEG_INFINITY; capacity * bins],
+            head: 0,
+            len: 0,
+        }
+    }
+
+    /// Appends a spectrum, overwriting the oldest row once full.
+    /// Shorter inputs are padded with silence, longer ones truncated.
+    pub fn push(&mut self, spectrum_db: &[f32]) {
+        let row = &mut self.rows[self.head * self.bins..(self.head + 1) * self.bins];
+        for (i, dst) in row.iter_mut().enumerate() {
+            *dst = spectrum_db.get(i).copied().unwrap_or(f32::NEG_INFINITY);
+        }
+        self.head = (self.head + 1) % self.capacity;
+        self.len = (self.len + 1).min(self.capacity);
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    pub fn bins(&self) -> usize {
+        self.bins
+    }
+
+    /// Returns the row `age` frames ago (0 = newest).
+    pub fn row(&self, age: usize) -> Option<&[f32]> {
+        if age >= self.len {
+            return None;
+        }
+        let idx = (self.head + self.capacity - 1 - age) % self.capacity;
+        Some(&self.rows[idx * self.bins..(idx + 1) * self.bins])
+    }
+}
@@ -0,0 +1,55 @@
+/// Quietest level the meters show, in dBFS.
+pub const FLOOR_DB: f32 = -60.0;
+
+/// RMS and sample peak of the latest window in dBFS, plus a peak-hold that
+/// waits a moment and then falls, like a hardware meter.
+pub struct LevelMeter {
+    pub rms_db: f32,
+    pub peak_db: f32,
+    pub hold_db: f32,
+
+    hold_left: f32, // seconds before the held peak starts falling
+}
+
+impl LevelMeter {
+    const HOLD_SECS: f32 = 1.0;
+    const FALL_DB_PER_SEC: f32 = 20.0;
+
+    pub fn new() -> Self {
+        Self {
+            rms_db: FLOOR_DB,
+            peak_db: FLOOR_DB,
+            hold_db: FLOOR_DB,
+            hold_left: 0.0,
+        }
+    }
+
+    pub fn update(&mut self, samples: &[f32], dt: f32) {
+        let (mut sum_sq, mut peak) = (0.0f32, 0.0f32);
+        for &s in samples {
+            sum_sq += s * s;
+            peak = peak.max(s.abs());
+        }
+        let rms = (sum_sq / samples.len().max(1) as f32).sqrt();
+        self.set(to_db(rms), to_db(peak), dt);
+    }
+
+    /// Takes levels measured elsewhere, e.g. from a recording.
+    pub fn set(&mut self, rms_db: f32, peak_db: f32, dt: f32) {
+        self.rms_db = rms_db;
+        self.peak_db = peak_db;
+
+        if self.peak_db >= self.hold_db {
+            self.hold_db = self.peak_db;
+            self.hold_left = Self::HOLD_SECS;
+        } else if self.hold_left > 0.0 {
+            self.hold_left -= dt;
+        } else {
+            self.hold_db = (self.hold_db - Self::FALL_DB_PER_SEC * dt).max(self.peak_db);
+        }
+    }
+}
+
+fn to_db(amplitude: f32) -> f32 {
+    (20.0 * amplitude.max(1e-6).log10()).max(FLOOR_DB)
+}
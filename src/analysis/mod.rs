@@ -1,3 +1,20 @@
 pub mod analyzer;
+pub mod history;
+pub mod level;
+pub mod onset;
+pub mod source;
+pub mod stereo;
+pub mod structure;
+pub mod tempo;
+pub mod track;
+pub mod trigger;
 
-pub use analyzer::Analyzer;
+pub use analyzer::{AnalysisFrame, Analyzer, AnalyzerParams};
+pub use history::SpectrumHistory;
+pub use level::LevelMeter;
+pub use source::Source;
+pub use stereo::StereoMeter;
+pub use structure::{SectionInfo, SectionKind, Segmenter};
+pub use tempo::{TapTempo, TempoEstimator};
+pub use track::{LookAhead, TrackAnalysis};
+pub use trigger::{Trigger, TriggerMode};
@@ -0,0 +1,66 @@
+use std::collections::VecDeque;
+
+/// Spectral-flux onset detector with an adaptive threshold.
+///
+/// Flux is the summed positive change of the raw band magnitudes between
+/// frames; an onset fires when it rises above the recent average by
+/// `sensitivity`, and no sooner than `min_gap` frames after the last one.
+pub struct OnsetDetector {
+    pub sensitivity: f32,
+    pub min_gap: usize,
+    /// flux of the latest frame, the novelty curve tempo tracking follows
+    pub flux: f32,
+
+    prev: Vec<f32>,
+    flux_history: VecDeque<f32>,
+    history_len: usize,
+    since_last: usize,
+}
+
+impl OnsetDetector {
+    pub fn new(bars: usize) -> Self {
+        Self {
+            sensitivity: 1.6,
+            min_gap: 6,
+            flux: 0.0,
+            prev: vec![0.0; bars],
+            flux_history: VecDeque::new(),
+            history_len: 43, // ~0.7 s at 60 analysis frames per second
+            since_last: usize::MAX,
+        }
+    }
+
+    /// Feeds one frame of raw band magnitudes; returns `(onset, strength)`
+    /// where strength is how far the flux exceeded the threshold (0 if not).
+    pub fn process(&mut self, bands: &[f32]) -> (bool, f32) {
+        let flux: f32 = bands
+            .iter()
+            .zip(&self.prev)
+            .map(|(&b, &p)| (b - p).max(0.0))
+            .sum::<f32>()
+            / bands.len().max(1) as f32;
+        self.flux = flux;
+        self.prev.clear();
+        self.prev.extend_from_slice(bands);
+
+        let mean = if self.flux_history.is_empty() {
+            0.0
+        } else {
+            self.flux_history.iter().sum::<f32>() / self.flux_history.len() as f32
+        };
+        self.flux_history.push_back(flux);
+        if self.flux_history.len() > self.history_len {
+            self.flux_history.pop_front();
+        }
+
+        // the small floor stops near-silence from triggering on noise
+        let threshold = mean * self.sensitivity + 0.002;
+        self.since_last = self.since_last.saturating_add(1);
+        if flux > threshold && self.since_last >= self.min_gap {
+            self.since_last = 0;
+            (true, (flux - threshold) / threshold)
+        } else {
+            (false, 0.0)
+        }
+    }
+}
@@ -0,0 +1,147 @@
+use super::level::FLOOR_DB;
+use super::{AnalysisFrame, LevelMeter};
+use anyhow::{Context, bail, ensure};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+/// What drives a level, written as a string in preset and mapping files,
+/// e.g. `"bands:0-12"` or `"bass_fast"`.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub enum Source {
+    /// `bands` or `bands:<from>-<to>` (band indices, end exclusive): mean level
+    Bands(Option<(usize, usize)>),
+    BassFast,
+    BassSmooth,
+    /// RMS level, -60 dB to 0 dB
+    Level,
+    /// 1 while an onset is active (the caller decides for how long)
+    Onset,
+    Centroid,
+    /// 0 to 1 over the build-up before a drop; file playback only
+    Tension,
+    /// A number, for channels that should just sit at a value
+    Fixed(f32),
+}
+
+impl TryFrom<String> for Source {
+    type Error = anyhow::Error;
+
+    fn try_from(s: String) -> anyhow::Result<Self> {
+        Ok(match s.as_str() {
+            "bands" => Self::Bands(None),
+            "bass_fast" => Self::BassFast,
+            "bass_smooth" => Self::BassSmooth,
+            "level" => Self::Level,
+            "onset" => Self::Onset,
+            "centroid" => Self::Centroid,
+            "tension" => Self::Tension,
+            _ => {
+                if let Some(range) = s.strip_prefix("bands:") {
+                    let (a, b) = range
+                        .split_once('-')
+                        .and_then(|(a, b)| Some((a.parse().ok()?, b.parse().ok()?)))
+                        .with_context(|| format!("'{s}' should look like bands:0-12"))?;
+                    ensure!(a < b, "'{s}' is an empty band range");
+                    Self::Bands(Some((a, b)))
+                } else if let Ok(v) = s.parse::<f32>() {
+                    Self::Fixed(v)
+                } else {
+                    bail!(
+                        "Unknown source '{s}', expected bands, bands:<from>-<to>, bass_fast, \
+                         bass_smooth, level, onset, centroid, tension or a number"
+                    )
+                }
+            }
+        })
+    }
+}
+
+impl Source {
+    /// Level for element `k` of `n` sharing this source, in 0..1 before gain.
+    pub fn value(
+        &self,
+        frame: &AnalysisFrame,
+        levels: &LevelMeter,
+        onset: bool,
+        k: usize,
+        n: usize,
+    ) -> f32 {
+        match self {
+            Self::Bands(range) => {
+                let len = frame.bands.len();
+                let (a, b) = range.unwrap_or((0, len));
+                let (a, b) = (a.min(len), b.min(len));
+                let lo = a + (b - a) * k / n;
+                let hi = (a + (b - a) * (k + 1) / n).max(lo + 1).min(len);
+                if lo >= hi {
+                    return 0.0;
+                }
+                frame.bands[lo..hi].iter().sum::<f32>() / (hi - lo) as f32
+            }
+            Self::BassFast => frame.bass_fast,
+            Self::BassSmooth => frame.bass_smooth,
+            Self::Level => (levels.rms_db - FLOOR_DB) / -FLOOR_DB,
+            Self::Onset => onset as u8 as f32,
+            Self::Centroid => frame.centroid,
+            Self::Tension => frame.lookahead.map_or(0.0, |ahead| ahead.tension),
+            Self::Fixed(v) => *v,
+        }
+    }
+}
+
+impl fmt::Display for Source {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Bands(None) => write!(f, "bands"),
+            Self::Bands(Some((a, b))) => write!(f, "bands:{a}-{b}"),
+            Self::BassFast => write!(f, "bass_fast"),
+            Self::BassSmooth => write!(f, "bass_smooth"),
+            Self::Level => write!(f, "level"),
+            Self::Onset => write!(f, "onset"),
+            Self::Centroid => write!(f, "centroid"),
+            Self::Tension => write!(f, "tension"),
+            Self::Fixed(v) => write!(f, "{v}"),
+        }
+    }
+}
+
+impl From<Source> for String {
+    fn from(source: Source) -> Self {
+        source.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn names_round_trip() {
+        for name in ["bands", "bands:3-9", "bass_fast", "onset", "tension", "0.5"] {
+            let source = Source::try_from(name.to_string()).unwrap();
+            assert_eq!(source.to_string(), name);
+        }
+        assert!(Source::try_from("bands:9-3".to_string()).is_err());
+        assert!(Source::try_from("treble".to_string()).is_err());
+    }
+
+    #[test]
+    fn band_ranges_split_between_elements() {
+        let frame = AnalysisFrame {
+            bands: vec![0.0, 0.2, 0.4, 0.6, 1.0, 1.0],
+            ..Default::default()
+        };
+        let levels = LevelMeter::new();
+        let source = Source::Bands(Some((1, 5)));
+        let split: Vec<f32> = (0..2)
+            .map(|k| source.value(&frame, &levels, false, k, 2))
+            .collect();
+        assert_eq!(split, [0.3, 0.8]);
+        // ranges past the end are cut to the bands there are
+        assert_eq!(
+            Source::Bands(Some((4, 50))).value(&frame, &levels, false, 0, 1),
+            1.0
+        );
+    }
+}
@@ -0,0 +1,53 @@
+/// Phase correlation and balance of a block of stereo frames, smoothed over time.
+pub struct StereoMeter {
+    /// +1 = mono-compatible, 0 = uncorrelated/wide, -1 = out of phase
+    pub correlation: f32,
+    /// -1 = hard left, 0 = centred, +1 = hard right (by RMS)
+    pub balance: f32,
+    pub rms_left: f32,
+    pub rms_right: f32,
+
+    alpha: f32,
+}
+
+impl StereoMeter {
+    pub fn new() -> Self {
+        Self {
+            correlation: 0.0,
+            balance: 0.0,
+            rms_left: 0.0,
+            rms_right: 0.0,
+            alpha: 0.15,
+        }
+    }
+
+    pub fn update(&mut self, frames: &[[f32; 2]]) {
+        if frames.is_empty() {
+            return;
+        }
+        let (mut ll, mut rr, mut lr) = (0.0f32, 0.0f32, 0.0f32);
+        for &[l, r] in frames {
+            ll += l * l;
+            rr += r * r;
+            lr += l * r;
+        }
+        let n = frames.len() as f32;
+        let rms_l = (ll / n).sqrt();
+        let rms_r = (rr / n).sqrt();
+
+        // silence says nothing about phase, so let the readings drift back to neutral
+        let energy = (ll * rr).sqrt();
+        let corr = if energy > 1e-9 { lr / energy } else { 0.0 };
+        let sum = rms_l + rms_r;
+        let bal = if sum > 1e-6 {
+            (rms_r - rms_l) / sum
+        } else {
+            0.0
+        };
+
+        self.correlation += self.alpha * (corr - self.correlation);
+        self.balance += self.alpha * (bal - self.balance);
+        self.rms_left += self.alpha * (rms_l - self.rms_left);
+        self.rms_right += self.alpha * (rms_r - self.rms_right);
+    }
+}
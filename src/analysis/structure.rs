@@ -0,0 +1,476 @@
+use super::track::{DROP_DB, percentile};
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+
+/// Seconds of audio per block; sections start and end on this grid.
+pub const BLOCK_SECS: f32 = 1.0;
+/// Bands are pooled into this many groups to describe a block.
+pub const GROUPS: usize = 12;
+/// Blocks compared either side of a possible boundary over a whole file.
+const HALF: usize = 8;
+/// The same live, where a boundary is only known this many blocks after it.
+const LIVE_HALF: usize = 4;
+/// Shortest section, in blocks.
+const MIN_BLOCKS: usize = 8;
+/// Novelty below this is never a boundary, however flat the rest of the
+/// curve is, so steady music stays one section.
+const MIN_NOVELTY: f32 = 0.5;
+/// Energy (0 to 1 across the track's range) from which a section is a drop.
+const HIGH: f32 = 0.6;
+/// Rise in energy from the first third of a section to its last that makes
+/// it a build.
+const RISE: f32 = 0.15;
+/// How far past `HIGH` or `RISE` live energy has to go to relabel a section.
+const MARGIN: f32 = 0.1;
+/// Blocks a new live label has to hold before it replaces the old one.
+const SETTLE: usize = 8;
+/// Blocks of live history kept for the novelty threshold and energy range.
+const LIVE_BLOCKS: usize = 240;
+
+/// What a stretch of a track is doing, judged by its energy and where it sits.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SectionKind {
+    Intro,
+    /// energy rising
+    Build,
+    Drop,
+    Breakdown,
+    Outro,
+}
+
+impl SectionKind {
+    pub const ALL: [Self; 5] = [
+        Self::Intro,
+        Self::Build,
+        Self::Drop,
+        Self::Breakdown,
+        Self::Outro,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Intro => "intro",
+            Self::Build => "build",
+            Self::Drop => "drop",
+            Self::Breakdown => "breakdown",
+            Self::Outro => "outro",
+        }
+    }
+}
+
+/// A section of a track; it lasts until the next one starts.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Section {
+    /// seconds
+    pub start: f32,
+    pub kind: SectionKind,
+}
+
+/// The section an analysis frame falls in.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct SectionInfo {
+    pub kind: SectionKind,
+    /// sections since the start of the track, or of listening when live
+    pub index: usize,
+    /// seconds since the section began
+    pub elapsed: f32,
+}
+
+/// A block's description: band groups, log-compressed so quiet and loud
+/// passages are compared on their spectral shape as well as their level.
+pub fn pool(bands: &[f32]) -> [f32; GROUPS] {
+    let n = bands.len();
+    std::array::from_fn(|g| {
+        let lo = g * n / GROUPS;
+        let hi = ((g + 1) * n / GROUPS).max(lo + 1).min(n);
+        if lo >= hi {
+            return 0.0;
+        }
+        let mean = bands[lo..hi].iter().sum::<f32>() / (hi - lo) as f32;
+        (1.0 + 100.0 * mean).ln()
+    })
+}
+
+fn distance(a: &[f32; GROUPS], b: &[f32; GROUPS]) -> f32 {
+    a.iter()
+        .zip(b)
+        .map(|(x, y)| (x - y).powi(2))
+        .sum::<f32>()
+        .sqrt()
+}
+
+/// How much the `half` blocks from `i` on differ from the `half` before,
+/// beyond how much each side varies within itself: the self-similarity
+/// matrix seen through a checkerboard kernel centred on `i`.
+fn novelty(blocks: &[[f32; GROUPS]], i: usize, half: usize) -> f32 {
+    let (before, after) = (&blocks[i - half..i], &blocks[i..i + half]);
+    let mean = |a: &[[f32; GROUPS]], b: &[[f32; GROUPS]]| {
+        a.iter()
+            .flat_map(|x| b.iter().map(move |y| distance(x, y)))
+            .sum::<f32>()
+            / (a.len() * b.len()) as f32
+    };
+    mean(before, after) - 0.5 * (mean(before, before) + mean(after, after))
+}
+
+/// What a section is from its blocks' energy (0 to 1 across the track's
+/// range). Loud is a drop wherever it is; otherwise the first section is the
+/// intro, the last the outro, a rising one a build and the rest breakdowns.
+fn label(energy: &[f32], first: bool, last: bool) -> SectionKind {
+    let (mean, rise) = shape(energy);
+    match mean {
+        m if m >= HIGH => SectionKind::Drop,
+        _ if first => SectionKind::Intro,
+        _ if last => SectionKind::Outro,
+        _ if rise >= RISE => SectionKind::Build,
+        _ => SectionKind::Breakdown,
+    }
+}
+
+/// `label` for a live section already labelled `current`: the thresholds
+/// lean towards `current` by `MARGIN`, so energy hovering around one doesn't
+/// flip the label back and forth.
+fn relabel(energy: &[f32], first: bool, current: SectionKind) -> SectionKind {
+    let (mean, rise) = shape(energy);
+    match current {
+        _ if mean >= HIGH + MARGIN => SectionKind::Drop,
+        _ if first => SectionKind::Intro,
+        SectionKind::Build if rise > RISE - MARGIN => SectionKind::Build,
+        SectionKind::Breakdown if rise < RISE + MARGIN => SectionKind::Breakdown,
+        _ if rise >= RISE => SectionKind::Build,
+        _ => SectionKind::Breakdown,
+    }
+}
+
+/// Mean energy, and its rise from the first third to the last.
+fn shape(energy: &[f32]) -> (f32, f32) {
+    let mean = |e: &[f32]| e.iter().sum::<f32>() / e.len().max(1) as f32;
+    let third = (energy.len() / 3).max(1).min(energy.len());
+    let rise = mean(&energy[energy.len().saturating_sub(third)..]) - mean(&energy[..third]);
+    (mean(energy), rise)
+}
+
+/// Splits a whole track into labelled sections. `blocks` and `energy` (0 to
+/// 1 across the track's range) describe each `BLOCK_SECS`; `drops` always
+/// start a section of their own, and other boundaries land on the nearest
+/// beat within half a second.
+pub fn segment(
+    blocks: &[[f32; GROUPS]],
+    energy: &[f32],
+    drops: &[f32],
+    beats: &[f32],
+) -> Vec<Section> {
+    let n = blocks.len();
+    let curve: Vec<f32> = (0..n)
+        .map(|i| {
+            if i >= HALF && i + HALF <= n {
+                novelty(blocks, i, HALF)
+            } else {
+                0.0
+            }
+        })
+        .collect();
+    let valid: Vec<f32> = (HALF..(n + 1).saturating_sub(HALF))
+        .map(|i| curve[i])
+        .collect();
+    let bar = threshold(&valid);
+
+    // peaks of the novelty curve, strongest first, kept apart by MIN_BLOCKS
+    let mut peaks: Vec<usize> = (HALF..(n + 1).saturating_sub(HALF))
+        .filter(|&i| {
+            let near = &curve[i.saturating_sub(HALF)..(i + HALF + 1).min(n)];
+            curve[i] >= bar && near.iter().all(|&v| v <= curve[i])
+        })
+        .collect();
+    peaks.sort_by(|&a, &b| curve[b].total_cmp(&curve[a]));
+    let mut starts: Vec<f32> = drops.to_vec();
+    let apart = MIN_BLOCKS as f32 * BLOCK_SECS;
+    for i in peaks {
+        let t = i as f32 * BLOCK_SECS;
+        let t = beats
+            .iter()
+            .copied()
+            .filter(|b| (b - t).abs() <= 0.5)
+            .min_by(|a, b| (a - t).abs().total_cmp(&(b - t).abs()))
+            .unwrap_or(t);
+        let end = n as f32 * BLOCK_SECS;
+        if t >= apart && end - t >= apart && starts.iter().all(|s| (s - t).abs() >= apart) {
+            starts.push(t);
+        }
+    }
+    starts.retain(|&s| s > 0.0);
+    starts.push(0.0);
+    starts.sort_by(f32::total_cmp);
+
+    let block = |t: f32| ((t / BLOCK_SECS).round() as usize).min(n);
+    let mut sections: Vec<Section> = Vec::new();
+    for (k, &start) in starts.iter().enumerate() {
+        let end = starts.get(k + 1).map_or(n, |&t| block(t));
+        let kind = if drops.contains(&start) {
+            SectionKind::Drop
+        } else {
+            let span = &energy[block(start).min(end)..end];
+            label(span, k == 0, k + 1 == starts.len())
+        };
+        // neighbours of the same kind are one section
+        if sections.last().is_none_or(|s| s.kind != kind) {
+            sections.push(Section { start, kind });
+        }
+    }
+    sections
+}
+
+/// Novelty a boundary has to reach: half a standard deviation above the
+/// curve's mean, and at least `MIN_NOVELTY`.
+fn threshold(curve: &[f32]) -> f32 {
+    if curve.is_empty() {
+        return f32::INFINITY;
+    }
+    let mean = curve.iter().sum::<f32>() / curve.len() as f32;
+    let var = curve.iter().map(|v| (v - mean).powi(2)).sum::<f32>() / curve.len() as f32;
+    (mean + 0.5 * var.sqrt()).max(MIN_NOVELTY)
+}
+
+/// Follows the structure of live input from its recent history. A drop is
+/// recognised as it lands; other boundaries come a few seconds late, once
+/// there is enough after them to compare and their novelty has peaked. The
+/// kind of the current section can change as more of it is heard, once the
+/// new label has clearly held for a few seconds, and there is no outro.
+pub struct Segmenter {
+    // the block being filled
+    sum: [f32; GROUPS],
+    loudness: f32,
+    frames: usize,
+    clock: f32,
+
+    blocks: VecDeque<[f32; GROUPS]>,
+    energy_db: VecDeque<f32>,
+    curve: VecDeque<f32>,
+    /// blocks completed since listening began
+    done: usize,
+    /// block the current section began at, counted like `done`
+    start: usize,
+    index: usize,
+    kind: SectionKind,
+    /// a different label the current section has had for this many blocks
+    pending: Option<(SectionKind, usize)>,
+}
+
+impl Segmenter {
+    pub fn new() -> Self {
+        Self {
+            sum: [0.0; GROUPS],
+            loudness: 0.0,
+            frames: 0,
+            clock: 0.0,
+            blocks: VecDeque::new(),
+            energy_db: VecDeque::new(),
+            curve: VecDeque::new(),
+            done: 0,
+            start: 0,
+            index: 0,
+            kind: SectionKind::Intro,
+            pending: None,
+        }
+    }
+
+    /// Feeds one analysis frame, `dt` seconds after the previous one.
+    pub fn push(&mut self, bands: &[f32], rms_db: f32, dt: f32) -> SectionInfo {
+        for (sum, v) in self.sum.iter_mut().zip(pool(bands)) {
+            *sum += v;
+        }
+        self.loudness += rms_db;
+        self.frames += 1;
+        // a stalled frame shouldn't count as seconds of music
+        self.clock += dt.clamp(0.0, 0.25);
+        if self.clock >= BLOCK_SECS {
+            self.clock -= BLOCK_SECS;
+            let n = self.frames as f32;
+            let block = self.sum.map(|s| s / n);
+            let energy_db = self.loudness / n;
+            (self.sum, self.loudness, self.frames) = ([0.0; GROUPS], 0.0, 0);
+            self.block(block, energy_db);
+        }
+        SectionInfo {
+            kind: self.kind,
+            index: self.index,
+            elapsed: (self.done - self.start) as f32 * BLOCK_SECS + self.clock,
+        }
+    }
+
+    fn block(&mut self, block: [f32; GROUPS], energy_db: f32) {
+        self.blocks.push_back(block);
+        self.energy_db.push_back(energy_db);
+        self.done += 1;
+        if self.blocks.len() > LIVE_BLOCKS {
+            self.blocks.pop_front();
+            self.energy_db.pop_front();
+        }
+        let n = self.blocks.len();
+        let first = self.done - n; // block number of `blocks[0]`
+        let history: Vec<f32> = self.energy_db.iter().copied().collect();
+        let (quiet, loud) = (percentile(&history, 0.1), percentile(&history, 0.95));
+        let level = |db: f32| ((db - quiet) / (loud - quiet).max(1.0)).clamp(0.0, 1.0);
+
+        // a drop: this block well above the four before, and loud for the music so far
+        let dropped = n > 4 && {
+            let before = history[n - 5..n - 1].iter().sum::<f32>() / 4.0;
+            energy_db - before >= DROP_DB && level(energy_db) >= HIGH
+        };
+        if dropped && self.kind != SectionKind::Drop {
+            self.begin(self.done - 1, SectionKind::Drop);
+            return;
+        }
+
+        if n > 2 * LIVE_HALF {
+            let blocks: Vec<[f32; GROUPS]> = self.blocks.iter().copied().collect();
+            let at = n - LIVE_HALF;
+            self.curve.push_back(novelty(&blocks, at, LIVE_HALF));
+            if self.curve.len() > LIVE_BLOCKS {
+                self.curve.pop_front();
+            }
+            // the value before last is a boundary once it's known to stand
+            // above its neighbours on both sides, and above the curve so far
+            let curve: Vec<f32> = self.curve.iter().copied().collect();
+            let m = curve.len();
+            if m > 2 * LIVE_HALF {
+                let (value, before) = (curve[m - 2], &curve[..m - 2]);
+                let peak = value >= curve[m - 1]
+                    && before.iter().rev().take(LIVE_HALF).all(|&v| v <= value);
+                let from = first + at - 1;
+                if peak && value >= threshold(before) && from >= self.start + MIN_BLOCKS {
+                    let span: Vec<f32> = history[at - 1..].iter().map(|&db| level(db)).collect();
+                    self.begin(from, label(&span, false, false));
+                    return;
+                }
+            }
+        }
+
+        // a drop stays one until the next boundary; anything else follows
+        // its energy, once a new label has settled
+        if self.kind != SectionKind::Drop {
+            let from = self.start.max(first) - first;
+            let span: Vec<f32> = history[from..].iter().map(|&db| level(db)).collect();
+            let kind = relabel(&span, self.index == 0, self.kind);
+            self.pending = match self.pending {
+                _ if kind == self.kind => None,
+                Some((pending, blocks)) if pending == kind => Some((kind, blocks + 1)),
+                _ => Some((kind, 1)),
+            };
+            if let Some((kind, SETTLE..)) = self.pending {
+                self.kind = kind;
+                self.pending = None;
+            }
+        }
+    }
+
+    fn begin(&mut self, block: usize, kind: SectionKind) {
+        self.start = block;
+        self.index += 1;
+        self.kind = kind;
+        self.pending = None;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Blocks of one timbre at one energy, `secs` long.
+    fn part(tone: f32, energy: f32, secs: usize) -> Vec<([f32; GROUPS], f32)> {
+        (0..secs)
+            .map(|i| {
+                // a little movement so no two blocks are identical
+                let wobble = 0.05 * (i as f32 * 1.7).sin();
+                (
+                    std::array::from_fn(|g| tone * (g as f32 + 1.0).sqrt() + wobble),
+                    energy,
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn sections_follow_timbre_and_energy() {
+        let track: Vec<_> = [
+            part(0.5, 0.2, 24),
+            part(1.0, 0.45, 16),
+            part(2.0, 0.9, 32),
+            part(0.7, 0.25, 24),
+        ]
+        .concat();
+        let (blocks, mut energy): (Vec<_>, Vec<_>) = track.into_iter().unzip();
+        // the build rises towards the drop
+        for (k, e) in energy[24..40].iter_mut().enumerate() {
+            *e = 0.3 + 0.02 * k as f32;
+        }
+        let sections = segment(&blocks, &energy, &[40.0], &[]);
+        let kinds: Vec<SectionKind> = sections.iter().map(|s| s.kind).collect();
+        assert_eq!(
+            kinds,
+            [
+                SectionKind::Intro,
+                SectionKind::Build,
+                SectionKind::Drop,
+                SectionKind::Outro
+            ],
+            "{sections:?}"
+        );
+        assert!((sections[1].start - 24.0).abs() <= 1.0, "{sections:?}");
+        assert_eq!(sections[2].start, 40.0);
+        assert!((sections[3].start - 72.0).abs() <= 1.0, "{sections:?}");
+    }
+
+    #[test]
+    fn live_drops_land_at_once_and_breakdowns_follow() {
+        let mut seg = Segmenter::new();
+        let dt = 1.0 / 50.0;
+        let feed = |seg: &mut Segmenter, level: f32, db: f32, secs: f32| {
+            let bands = vec![level; 120];
+            let mut info = None;
+            for _ in 0..(secs / dt).round() as usize {
+                info = Some(seg.push(&bands, db, dt));
+            }
+            info.unwrap()
+        };
+        assert_eq!(feed(&mut seg, 0.05, -30.0, 30.0).kind, SectionKind::Intro);
+        let drop = feed(&mut seg, 0.6, -8.0, 1.5);
+        assert_eq!((drop.kind, drop.index), (SectionKind::Drop, 1));
+        assert!(drop.elapsed < 1.5, "{drop:?}");
+        assert_eq!(feed(&mut seg, 0.6, -8.0, 20.0).index, 1);
+        let after = feed(&mut seg, 0.03, -32.0, 12.0);
+        assert_eq!((after.kind, after.index), (SectionKind::Breakdown, 2));
+    }
+
+    #[test]
+    fn live_labels_hold_steady_through_noise() {
+        // ±5 dB, a new value every four seconds or so: the section's rise
+        // keeps wandering across `RISE`
+        let noise = |t: f32| {
+            let x = (t.floor() * 12.9898).sin() * 43_758.547;
+            2.0 * (x - x.floor()) - 1.0
+        };
+        let dt = 1.0 / 50.0;
+        for seed in 0..5 {
+            let mut seg = Segmenter::new();
+            let mut seen = vec![];
+            for k in 0..(170.0 / dt) as usize {
+                let t = k as f32 * dt;
+                let (level, db) = match t {
+                    t if t < 30.0 => (0.05, -30.0),
+                    t if t < 50.0 => (0.6, -8.0),
+                    t => (0.2, -22.0 + 5.0 * noise(t * 0.25 + seed as f32 * 7.0)),
+                };
+                let info = seg.push(&vec![level; 120], db, dt);
+                if seen.last() != Some(&(info.index, info.kind)) {
+                    seen.push((info.index, info.kind));
+                }
+            }
+            // one section after the drop, relabelled at most once
+            let after: Vec<_> = seen.iter().filter(|(i, _)| *i == 2).collect();
+            assert!(matches!(after.len(), 1 | 2), "seed {seed}: {seen:?}");
+            assert_eq!(seen.last().unwrap().0, 2, "seed {seed}: {seen:?}");
+        }
+    }
+}
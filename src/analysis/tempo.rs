@@ -0,0 +1,239 @@
+use std::collections::VecDeque;
+
+/// Envelope samples per second; flux arrives at the frame rate and is resampled
+/// onto this fixed grid so the estimate doesn't depend on how fast we draw.
+pub const RATE: f32 = 50.0;
+const WINDOW_SECS: f32 = 8.0;
+/// Less than this much envelope gives no estimate at all.
+const MIN_SECS: f32 = 4.0;
+const MIN_BPM: f32 = 60.0;
+const MAX_BPM: f32 = 200.0;
+/// Seconds between re-estimates; the autocorrelation isn't free.
+const INTERVAL: f32 = 0.5;
+
+/// Tempo from the autocorrelation of the spectral-flux curve over the last
+/// few seconds.
+///
+/// Periodic onsets make the flux correlate with itself at the beat period;
+/// a broad preference for ~120 BPM settles the usual half/double-tempo
+/// ambiguity. Weak or irregular correlation gives no estimate rather than a
+/// wandering one.
+pub struct TempoEstimator {
+    envelope: VecDeque<f32>,
+    clock: f32, // time into the current grid cell
+    peak: f32,  // strongest flux seen in the current cell
+    since_estimate: f32,
+    bpm: Option<f32>,
+}
+
+impl TempoEstimator {
+    pub fn new() -> Self {
+        Self {
+            envelope: VecDeque::new(),
+            clock: 0.0,
+            peak: 0.0,
+            since_estimate: 0.0,
+            bpm: None,
+        }
+    }
+
+    /// Beats per minute, or `None` while there's no clear pulse.
+    pub fn bpm(&self) -> Option<f32> {
+        self.bpm
+    }
+
+    /// Feeds the flux of one analysis frame, `dt` seconds after the previous one.
+    pub fn push(&mut self, flux: f32, dt: f32) {
+        let cell = 1.0 / RATE;
+        self.peak = self.peak.max(flux);
+        // a stalled frame shouldn't flood the envelope with copies of one value
+        self.clock += dt.clamp(0.0, 0.25);
+        while self.clock >= cell {
+            self.clock -= cell;
+            self.envelope.push_back(self.peak);
+            self.peak = 0.0;
+        }
+        let len = (WINDOW_SECS * RATE) as usize;
+        while self.envelope.len() > len {
+            self.envelope.pop_front();
+        }
+
+        self.since_estimate += dt;
+        if self.since_estimate >= INTERVAL {
+            self.since_estimate = 0.0;
+            self.estimate();
+        }
+    }
+
+    fn estimate(&mut self) {
+        let envelope: Vec<f32> = self.envelope.iter().copied().collect();
+        let Some(bpm) = estimate(&envelope) else {
+            self.bpm = None;
+            return;
+        };
+        // settle instead of flickering when the new value agrees with the last
+        self.bpm = Some(match self.bpm {
+            Some(prev) if (bpm / prev - 1.0).abs() < 0.04 => prev * 0.7 + bpm * 0.3,
+            _ => bpm,
+        });
+    }
+}
+
+/// Tempo of a flux envelope sampled `RATE` times a second, as
+/// `TempoEstimator` finds it over its window; also used over whole files.
+pub fn estimate(envelope: &[f32]) -> Option<f32> {
+    let n = envelope.len();
+    if n < (MIN_SECS * RATE) as usize {
+        return None;
+    }
+    let mean = envelope.iter().sum::<f32>() / n as f32;
+    let x: Vec<f32> = envelope.iter().map(|v| v - mean).collect();
+    let energy = x.iter().map(|v| v * v).sum::<f32>() / n as f32;
+    if energy <= 1e-9 {
+        return None;
+    }
+
+    let corr = |lag: usize| {
+        x.iter().zip(&x[lag..]).map(|(a, b)| a * b).sum::<f32>() / (n - lag) as f32 / energy
+    };
+    let lag_min = (60.0 * RATE / MAX_BPM).floor() as usize;
+    let lag_max = (60.0 * RATE / MIN_BPM).ceil() as usize;
+    let r: Vec<f32> = (lag_min - 1..=lag_max + 1).map(corr).collect();
+
+    // index into `r` of the best lag, weighted towards 120 BPM by octave distance
+    let prior = |lag: usize| {
+        let octaves = (60.0 * RATE / lag as f32 / 120.0).log2();
+        (-0.5 * octaves * octaves).exp()
+    };
+    let best = (1..r.len() - 1)
+        .max_by(|&a, &b| {
+            let wa = r[a] * prior(lag_min - 1 + a);
+            let wb = r[b] * prior(lag_min - 1 + b);
+            wa.total_cmp(&wb)
+        })
+        .unwrap();
+    if r[best] < 0.2 {
+        return None;
+    }
+
+    // parabolic fit through the neighbours for a lag between grid cells
+    let (a, b, c) = (r[best - 1], r[best], r[best + 1]);
+    let denom = a - 2.0 * b + c;
+    let shift = if denom.abs() > 1e-9 {
+        (0.5 * (a - c) / denom).clamp(-0.5, 0.5)
+    } else {
+        0.0
+    };
+    let lag = (lag_min - 1 + best) as f32 + shift;
+    Some(60.0 * RATE / lag)
+}
+
+/// Tempo tapped in by hand: the mean interval of the last few taps. A pause
+/// of more than two seconds starts a fresh count.
+pub struct TapTempo {
+    taps: VecDeque<f32>, // seconds
+    bpm: Option<f32>,
+}
+
+impl TapTempo {
+    const MAX_GAP: f32 = 2.0;
+    const MAX_TAPS: usize = 8;
+
+    pub fn new() -> Self {
+        Self {
+            taps: VecDeque::new(),
+            bpm: None,
+        }
+    }
+
+    pub fn bpm(&self) -> Option<f32> {
+        self.bpm
+    }
+
+    /// Registers a tap at `now` seconds and returns the tempo so far.
+    pub fn tap(&mut self, now: f32) -> Option<f32> {
+        if self
+            .taps
+            .back()
+            .is_some_and(|&last| now - last > Self::MAX_GAP)
+        {
+            self.taps.clear();
+        }
+        self.taps.push_back(now);
+        if self.taps.len() > Self::MAX_TAPS {
+            self.taps.pop_front();
+        }
+        if self.taps.len() >= 2 {
+            let span = self.taps.back().unwrap() - self.taps.front().unwrap();
+            let interval = span / (self.taps.len() - 1) as f32;
+            if interval > 0.0 {
+                self.bpm = Some(60.0 / interval);
+            }
+        }
+        self.bpm
+    }
+
+    pub fn clear(&mut self) {
+        self.taps.clear();
+        self.bpm = None;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn taps_average_and_restart_after_a_pause() {
+        let mut tap = TapTempo::new();
+        assert_eq!(tap.tap(10.0), None);
+        tap.tap(10.5);
+        tap.tap(11.02);
+        let bpm = tap.tap(11.5).unwrap();
+        assert!((bpm - 120.0).abs() < 1.0, "{bpm}");
+        // a long pause forgets the old taps but keeps showing the last tempo
+        assert_eq!(tap.tap(20.0), Some(bpm));
+        let bpm = tap.tap(21.0).unwrap();
+        assert!((bpm - 60.0).abs() < 0.1, "{bpm}");
+    }
+
+    /// One flux spike per beat at 60 frames per second, for `secs` seconds.
+    fn pulse(bpm: f32, secs: f32) -> TempoEstimator {
+        let mut tempo = TempoEstimator::new();
+        let dt = 1.0 / 60.0;
+        let period = 60.0 / bpm;
+        let mut next_beat = 0.0;
+        for k in 0..(secs * 60.0) as usize {
+            let t = k as f32 * dt;
+            let flux = if t >= next_beat {
+                next_beat += period;
+                1.0
+            } else {
+                0.02
+            };
+            tempo.push(flux, dt);
+        }
+        tempo
+    }
+
+    #[test]
+    fn finds_a_steady_pulse() {
+        for bpm in [90.0, 120.0, 128.0, 174.0] {
+            let found = pulse(bpm, 12.0).bpm().expect("no tempo found");
+            assert!(
+                (found - bpm).abs() < bpm * 0.03,
+                "{bpm} BPM read as {found}"
+            );
+        }
+    }
+
+    #[test]
+    fn silence_and_short_input_give_nothing() {
+        let mut silent = TempoEstimator::new();
+        for _ in 0..600 {
+            silent.push(0.0, 1.0 / 60.0);
+        }
+        assert_eq!(silent.bpm(), None);
+        assert_eq!(pulse(120.0, 2.0).bpm(), None);
+    }
+}
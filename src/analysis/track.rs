@@ -0,0 +1,439 @@
+use super::Analyzer;
+use super::structure::{self, BLOCK_SECS, GROUPS, Section, SectionInfo};
+use super::tempo::{self, RATE};
+use crate::audio::AudioData;
+use crate::{BARS, FFT_SIZE};
+use anyhow::Context;
+use serde::{Deserialize, Serialize};
+use std::fs::File;
+use std::io::{BufRead, BufReader};
+use std::path::{Path, PathBuf};
+use std::time::Instant;
+
+/// Bumped whenever what's computed changes, so older cache files are redone.
+const VERSION: u32 = 2;
+/// Seconds of build-up `tension` rises over before a drop.
+const BUILD_SECS: f32 = 8.0;
+/// How much louder (dB) the music has to come in than the passage before it
+/// to count as a drop.
+pub(super) const DROP_DB: f32 = 6.0;
+/// Drops closer together than this are one drop: the biggest jump wins.
+const DROP_GAP_SECS: f32 = 16.0;
+
+/// What file playback knows about the moment showing from having heard the
+/// whole track.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct LookAhead {
+    /// 0 on a beat, rising to 1 just before the next; 0 without a tempo
+    pub beat_phase: f32,
+    /// 0 to 1 over the build-up before a drop, back to 0 once it lands
+    pub tension: f32,
+    /// loudness now within the track's own quiet-to-loud range, 0 to 1
+    pub loudness: f32,
+}
+
+/// A whole file analyzed before playback, one hop per tempo envelope cell
+/// (`RATE` a second). Kept on disk by file contents, so a track is only
+/// analyzed the first time it's played.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct TrackAnalysis {
+    version: u32,
+    /// largest absolute sample of the mono mix
+    pub peak: f32,
+    /// RMS of the analysis window at each hop, dBFS
+    pub loudness: Vec<f32>,
+    /// spectral flux at each hop
+    pub flux: Vec<f32>,
+    /// tempo over the whole track, if it holds a steady one
+    pub bpm: Option<f32>,
+    /// seconds
+    pub beats: Vec<f32>,
+    /// seconds at which the music comes in loud after a quieter passage
+    pub drops: Vec<f32>,
+    /// intro, builds, drops, breakdowns and outro, in order from the start
+    pub sections: Vec<Section>,
+    /// quiet and loud ends of the track (10th and 95th percentile loudness), dBFS
+    pub range_db: (f32, f32),
+}
+
+impl TrackAnalysis {
+    pub fn compute(audio: &AudioData) -> Self {
+        let mut analyzer = Analyzer::new(FFT_SIZE, BARS);
+        let hops = (audio.duration_sec * RATE).ceil() as usize;
+        let mut window = Vec::with_capacity(FFT_SIZE);
+        let mut loudness = Vec::with_capacity(hops);
+        let mut flux = Vec::with_capacity(hops);
+        let per_block = (BLOCK_SECS * RATE) as usize;
+        let mut blocks = Vec::with_capacity(hops / per_block + 1);
+        let mut sum = [0.0; GROUPS];
+        for k in 0..hops {
+            audio.window_at_time(k as f32 / RATE, FFT_SIZE, &mut window);
+            let mean_square = window.iter().map(|s| s * s).sum::<f32>() / window.len() as f32;
+            loudness.push(10.0 * mean_square.max(1e-12).log10());
+            let frame = analyzer.analyze(&window, audio.sample_rate);
+            flux.push(frame.flux);
+            for (s, v) in sum.iter_mut().zip(structure::pool(&frame.bands)) {
+                *s += v;
+            }
+            if (k + 1) % per_block == 0 || k + 1 == hops {
+                let count = (k % per_block + 1) as f32;
+                blocks.push(sum.map(|s| s / count));
+                sum = [0.0; GROUPS];
+            }
+        }
+        let peak = audio
+            .samples_mono
+            .iter()
+            .fold(0.0f32, |m, s| m.max(s.abs()));
+
+        let bpm = tempo::estimate(&flux);
+        let beats = bpm.map_or_else(Vec::new, |bpm| beat_grid(&flux, 60.0 * RATE / bpm));
+        let smooth = moving_mean(&loudness, RATE as usize);
+        let drops = find_drops(&smooth, &beats);
+        let (quiet, loud) = (percentile(&loudness, 0.1), percentile(&loudness, 0.95));
+        let energy: Vec<f32> = loudness
+            .chunks(per_block)
+            .map(|c| {
+                let db = c.iter().sum::<f32>() / c.len() as f32;
+                ((db - quiet) / (loud - quiet).max(1.0)).clamp(0.0, 1.0)
+            })
+            .collect();
+        let sections = structure::segment(&blocks, &energy, &drops, &beats);
+        Self {
+            version: VERSION,
+            peak,
+            range_db: (quiet, loud),
+            loudness,
+            flux,
+            bpm,
+            beats,
+            drops,
+            sections,
+        }
+    }
+
+    /// The analysis of `path`, already loaded as `audio`: from the cache if
+    /// these exact contents were analyzed before, otherwise computed and
+    /// cached. A cache that can't be read or written only costs the time to
+    /// analyze again.
+    pub fn load(path: &Path, audio: &AudioData) -> anyhow::Result<Self> {
+        Self::load_in(cache_dir().as_deref(), path, audio)
+    }
+
+    fn load_in(dir: Option<&Path>, path: &Path, audio: &AudioData) -> anyhow::Result<Self> {
+        let hash = File::open(path)
+            .and_then(|f| fnv1a(BufReader::new(f)))
+            .with_context(|| format!("Failed to read {}", path.display()))?;
+        let file = dir.map(|dir| dir.join(format!("{hash:016x}.json")));
+        if let Some(file) = &file
+            && let Ok(text) = std::fs::read_to_string(file)
+            && let Ok(cached) = serde_json::from_str::<Self>(&text)
+            && cached.version == VERSION
+        {
+            eprintln!("[lookahead] cached analysis {}", file.display());
+            return Ok(cached);
+        }
+
+        let started = Instant::now();
+        let analysis = Self::compute(audio);
+        eprintln!(
+            "[lookahead] analyzed {:.0}s of audio in {:.1}s: {} beats, {} drops, {} sections",
+            audio.duration_sec,
+            started.elapsed().as_secs_f32(),
+            analysis.beats.len(),
+            analysis.drops.len(),
+            analysis.sections.len()
+        );
+        if let Some(file) = &file
+            && let Err(e) = analysis.save(file)
+        {
+            eprintln!("[lookahead] not cached: {e:#}");
+        }
+        Ok(analysis)
+    }
+
+    fn save(&self, file: &Path) -> anyhow::Result<()> {
+        if let Some(dir) = file.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        std::fs::write(file, serde_json::to_vec(self)?)
+            .with_context(|| format!("Failed to write {}", file.display()))
+    }
+
+    pub fn duration(&self) -> f32 {
+        self.loudness.len() as f32 / RATE
+    }
+
+    /// Gain that brings the track's peak to full scale, so quiet masters
+    /// drive the visuals as hard as loud ones. At most 20×, like the gain key.
+    pub fn gain(&self) -> f32 {
+        if self.peak > 1e-4 {
+            (1.0 / self.peak).min(20.0)
+        } else {
+            1.0
+        }
+    }
+
+    /// Where `t` seconds into playback stands; past the end wraps around,
+    /// as playback loops.
+    pub fn at(&self, t: f32) -> LookAhead {
+        let t = t.rem_euclid(self.duration().max(1e-3));
+        let beat_phase = match self.beats.partition_point(|&b| b <= t) {
+            0 => 0.0,
+            i if i == self.beats.len() => 0.0,
+            i => (t - self.beats[i - 1]) / (self.beats[i] - self.beats[i - 1]),
+        };
+        let tension = self
+            .drops
+            .iter()
+            .find(|&&d| d > t)
+            .map_or(0.0, |&d| (1.0 - (d - t) / BUILD_SECS).max(0.0));
+        let hop = ((t * RATE) as usize).min(self.loudness.len().saturating_sub(1));
+        let (quiet, loud) = self.range_db;
+        let loudness = self.loudness.get(hop).map_or(0.0, |db| {
+            ((db - quiet) / (loud - quiet).max(1.0)).clamp(0.0, 1.0)
+        });
+        LookAhead {
+            beat_phase,
+            tension,
+            loudness,
+        }
+    }
+
+    /// The section `t` seconds into playback falls in, wrapping like `at`.
+    pub fn section_at(&self, t: f32) -> Option<SectionInfo> {
+        let t = t.rem_euclid(self.duration().max(1e-3));
+        let index = self
+            .sections
+            .partition_point(|s| s.start <= t)
+            .checked_sub(1)?;
+        let section = self.sections[index];
+        Some(SectionInfo {
+            kind: section.kind,
+            index,
+            elapsed: t - section.start,
+        })
+    }
+}
+
+/// `$XDG_CACHE_HOME/audio_visualizer/analysis`, or under `~/.cache`.
+pub fn cache_dir() -> Option<PathBuf> {
+    let cache = std::env::var_os("XDG_CACHE_HOME")
+        .map(PathBuf::from)
+        .or_else(|| std::env::var_os("HOME").map(|h| PathBuf::from(h).join(".cache")))?;
+    Some(cache.join("audio_visualizer").join("analysis"))
+}
+
+/// 64-bit FNV-1a: stable across builds and platforms, unlike std's hasher.
+/// Reads a buffer at a time, so a long set isn't held in memory twice.
+fn fnv1a(mut reader: impl BufRead) -> std::io::Result<u64> {
+    let mut h: u64 = 0xcbf2_9ce4_8422_2325;
+    loop {
+        let buf = reader.fill_buf()?;
+        if buf.is_empty() {
+            return Ok(h);
+        }
+        for &b in buf {
+            h = (h ^ b as u64).wrapping_mul(0x0000_0100_0000_01b3);
+        }
+        let n = buf.len();
+        reader.consume(n);
+    }
+}
+
+/// Beats of a steady tempo, `period` hops apart. The first is at the
+/// offset that lines up with the most flux; each one after is expected a
+/// period on and pulled halfway onto the strongest flux within two hops, so
+/// the grid follows small tempo drift without jumping at every ghost note.
+fn beat_grid(flux: &[f32], period: f32) -> Vec<f32> {
+    let score = |offset: usize| {
+        (0usize..)
+            .map(|k| (offset as f32 + k as f32 * period).round() as usize)
+            .take_while(|&h| h < flux.len())
+            .map(|h| flux[h])
+            .sum::<f32>()
+    };
+    let Some(offset) = (0..period.ceil() as usize).max_by(|&a, &b| score(a).total_cmp(&score(b)))
+    else {
+        return Vec::new();
+    };
+    let mut beats = Vec::new();
+    let mut at = offset as f32;
+    while (at.round() as usize) < flux.len() {
+        let h = at.round() as usize;
+        let near = h.saturating_sub(2)..(h + 3).min(flux.len());
+        let peak = near
+            .max_by(|&a, &b| flux[a].total_cmp(&flux[b]))
+            .unwrap_or(h);
+        at += (peak as f32 - at) * 0.5;
+        beats.push(at / RATE);
+        at += period;
+    }
+    beats
+}
+
+/// Hops where the smoothed loudness of the next two seconds is `DROP_DB`
+/// above the four before and at least the track's median, keeping the
+/// biggest jump within any `DROP_GAP_SECS`. Each lands on the nearest beat
+/// within half a second, when there are beats.
+fn find_drops(smooth: &[f32], beats: &[f32]) -> Vec<f32> {
+    let (before, after) = ((4.0 * RATE) as usize, (2.0 * RATE) as usize);
+    let median = percentile(smooth, 0.5);
+    let mean = |s: &[f32]| s.iter().sum::<f32>() / s.len() as f32;
+    let mut jumps: Vec<(usize, f32)> = (before..smooth.len().saturating_sub(after))
+        .filter_map(|i| {
+            let level = mean(&smooth[i..i + after]);
+            let jump = level - mean(&smooth[i - before..i]);
+            (jump >= DROP_DB && level >= median).then_some((i, jump))
+        })
+        .collect();
+    jumps.sort_by(|a, b| b.1.total_cmp(&a.1));
+
+    let gap = (DROP_GAP_SECS * RATE) as usize;
+    let mut kept: Vec<usize> = Vec::new();
+    for (i, _) in jumps {
+        if kept.iter().all(|&k| k.abs_diff(i) >= gap) {
+            kept.push(i);
+        }
+    }
+    kept.sort_unstable();
+    kept.into_iter()
+        .map(|i| {
+            let t = i as f32 / RATE;
+            beats
+                .iter()
+                .copied()
+                .filter(|b| (b - t).abs() <= 0.5)
+                .min_by(|a, b| (a - t).abs().total_cmp(&(b - t).abs()))
+                .unwrap_or(t)
+        })
+        .collect()
+}
+
+/// Mean over a window of `width` values centred on each one.
+fn moving_mean(values: &[f32], width: usize) -> Vec<f32> {
+    let mut sums = Vec::with_capacity(values.len() + 1);
+    sums.push(0.0f64);
+    for &v in values {
+        sums.push(sums.last().unwrap() + v as f64);
+    }
+    (0..values.len())
+        .map(|i| {
+            let lo = i.saturating_sub(width / 2);
+            let hi = (i + width / 2 + 1).min(values.len());
+            ((sums[hi] - sums[lo]) / (hi - lo) as f64) as f32
+        })
+        .collect()
+}
+
+pub(super) fn percentile(values: &[f32], q: f32) -> f32 {
+    if values.is_empty() {
+        return 0.0;
+    }
+    let mut sorted = values.to_vec();
+    sorted.sort_by(f32::total_cmp);
+    sorted[((sorted.len() - 1) as f32 * q).round() as usize]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::analysis::structure::SectionKind;
+    use crate::testutil::TempDir;
+
+    /// Kicks at 128 BPM: quiet for the first 20 seconds, then at full level
+    /// with a bass note under them.
+    fn track() -> AudioData {
+        let rate = 44_100;
+        let secs = 36.0;
+        let beat = (60.0 / 128.0 * rate as f32) as usize;
+        let mono: Vec<f32> = (0..(secs * rate as f32) as usize)
+            .map(|i| {
+                let t = i as f32 / rate as f32;
+                let since = (i % beat) as f32 / rate as f32;
+                let kick = (-since * 30.0).exp() * (std::f32::consts::TAU * 60.0 * since).sin();
+                if t < 20.0 {
+                    0.05 * kick
+                } else {
+                    0.5 * kick + 0.3 * (std::f32::consts::TAU * 45.0 * t).sin()
+                }
+            })
+            .collect();
+        AudioData {
+            sample_rate: rate,
+            samples_stereo: mono.iter().map(|&s| [s, s]).collect(),
+            duration_sec: secs,
+            samples_mono: mono,
+        }
+    }
+
+    #[test]
+    fn finds_beats_and_the_drop() {
+        let analysis = TrackAnalysis::compute(&track());
+        let bpm = analysis.bpm.expect("no tempo");
+        assert!((bpm - 128.0).abs() < 2.0, "{bpm}");
+        let spacing = analysis.beats.windows(2).map(|w| w[1] - w[0]);
+        assert!(spacing.clone().all(|s| (s - 60.0 / 128.0).abs() < 0.05));
+        assert_eq!(analysis.drops.len(), 1, "{:?}", analysis.drops);
+        assert!(
+            (analysis.drops[0] - 20.0).abs() < 0.6,
+            "{:?}",
+            analysis.drops
+        );
+        assert!((analysis.gain() - 1.0 / analysis.peak).abs() < 1e-6);
+
+        // tension builds towards the drop and lets go on it
+        let drop = analysis.drops[0];
+        assert_eq!(analysis.at(drop - BUILD_SECS - 1.0).tension, 0.0);
+        assert!(analysis.at(drop - 4.0).tension > 0.4);
+        assert!(analysis.at(drop - 0.1).tension > 0.95);
+        assert_eq!(analysis.at(drop + 0.1).tension, 0.0);
+        assert!(analysis.at(drop + 4.0).loudness > analysis.at(drop - 4.0).loudness);
+        let kinds: Vec<_> = analysis.sections.iter().map(|s| s.kind).collect();
+        assert_eq!(kinds, [SectionKind::Intro, SectionKind::Drop]);
+        assert_eq!(analysis.sections[1].start, drop);
+        let section = analysis.section_at(drop + 2.0).unwrap();
+        assert_eq!((section.kind, section.index), (SectionKind::Drop, 1));
+        assert!((section.elapsed - 2.0).abs() < 1e-4);
+        let phase = analysis.at(analysis.beats[10] + 0.1).beat_phase;
+        assert!(phase > 0.1 && phase < 0.3, "{phase}");
+    }
+
+    #[test]
+    fn cache_keys_stay_fnv1a() {
+        // keys from before the hash was streamed still find their files
+        assert_eq!(fnv1a(&b""[..]).unwrap(), 0xcbf2_9ce4_8422_2325);
+        assert_eq!(fnv1a(&b"a"[..]).unwrap(), 0xaf63_dc4c_8601_ec8c);
+        let long = vec![7u8; 20_000];
+        let small = BufReader::with_capacity(64, &long[..]);
+        assert_eq!(fnv1a(small).unwrap(), fnv1a(&long[..]).unwrap());
+    }
+
+    #[test]
+    fn analysis_is_cached_by_contents() {
+        let dir = TempDir::new("lookahead");
+        let wav = dir.join("a.wav");
+        std::fs::write(&wav, b"stand-in contents").unwrap();
+        let audio = track();
+        let cache = dir.join("cache");
+
+        let first = TrackAnalysis::load_in(Some(&cache), &wav, &audio).unwrap();
+        let files: Vec<_> = std::fs::read_dir(&cache).unwrap().collect();
+        assert_eq!(files.len(), 1);
+        // a cached analysis is used as is, even for different samples
+        let second = TrackAnalysis::load_in(
+            Some(&cache),
+            &wav,
+            &AudioData {
+                duration_sec: 1.0,
+                ..audio.clone()
+            },
+        )
+        .unwrap();
+        assert_eq!(second.beats, first.beats);
+        // other contents are another entry
+        std::fs::write(&wav, b"other contents").unwrap();
+        TrackAnalysis::load_in(Some(&cache), &wav, &audio).unwrap();
+        assert_eq!(std::fs::read_dir(&cache).unwrap().count(), 2);
+    }
+}
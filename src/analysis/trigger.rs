@@ -0,0 +1,112 @@
+use serde::{Deserialize, Serialize};
+
+/// How a trace picks its start point inside the analysis window.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, clap::ValueEnum, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TriggerMode {
+    /// No triggering — always show the newest samples
+    Free,
+    /// Start on a rising zero crossing
+    Rising,
+    /// Start where the signal rises through `level`
+    Level,
+    /// Start at the offset that best matches the previous trace (stable phase)
+    Autocorrelation,
+}
+
+impl TriggerMode {
+    pub fn next(self) -> Self {
+        match self {
+            Self::Free => Self::Rising,
+            Self::Rising => Self::Level,
+            Self::Level => Self::Autocorrelation,
+            Self::Autocorrelation => Self::Free,
+        }
+    }
+}
+
+/// Finds a stable start offset for a `span`-sample trace so periodic
+/// signals stop jittering from frame to frame.
+pub struct Trigger {
+    pub mode: TriggerMode,
+    pub level: f32,
+    pub hysteresis: f32, // signal must dip this far below the level to re-arm
+
+    reference: Vec<f32>, // last trace returned, for the correlation search
+}
+
+impl Trigger {
+    pub fn new(mode: TriggerMode) -> Self {
+        Self {
+            mode,
+            level: 0.1,
+            hysteresis: 0.02,
+            reference: Vec::new(),
+        }
+    }
+
+    /// Returns the start index of a `span`-sample trace inside `window`.
+    /// Falls back to the newest samples when nothing triggers.
+    pub fn find(&mut self, window: &[f32], span: usize) -> usize {
+        if span == 0 || span >= window.len() {
+            self.reference.clear();
+            return 0;
+        }
+        let latest = window.len() - span;
+        let start = match self.mode {
+            TriggerMode::Free => latest,
+            TriggerMode::Rising => self.edge(window, latest, 0.0).unwrap_or(latest),
+            TriggerMode::Level => self.edge(window, latest, self.level).unwrap_or(latest),
+            TriggerMode::Autocorrelation => self.best_phase(window, span, latest),
+        };
+
+        self.reference.clear();
+        self.reference
+            .extend_from_slice(&window[start..start + span]);
+        start
+    }
+
+    /// Newest rising crossing of `level` at or before `latest`, with hysteresis
+    /// so noise riding on the crossing can't fire it twice.
+    fn edge(&self, window: &[f32], latest: usize, level: f32) -> Option<usize> {
+        let mut armed = false;
+        let mut found = None;
+        for i in 1..=latest {
+            if window[i] < level - self.hysteresis {
+                armed = true;
+            }
+            if armed && window[i - 1] < level && window[i] >= level {
+                found = Some(i);
+                armed = false;
+            }
+        }
+        found
+    }
+
+    /// Searches one span back from the newest start for the offset whose samples
+    /// are closest (least squared difference) to the previous trace.
+    fn best_phase(&self, window: &[f32], span: usize, latest: usize) -> usize {
+        if self.reference.len() != span {
+            return self.edge(window, latest, 0.0).unwrap_or(latest);
+        }
+        // every 4th sample is plenty to rank candidates and keeps this cheap
+        const STEP: usize = 4;
+        let earliest = latest.saturating_sub(span);
+        let mut best = latest;
+        let mut best_err = f32::INFINITY;
+        for start in (earliest..=latest).rev() {
+            let err: f32 = (0..span)
+                .step_by(STEP)
+                .map(|k| {
+                    let d = window[start + k] - self.reference[k];
+                    d * d
+                })
+                .sum();
+            if err < best_err {
+                best_err = err;
+                best = start;
+            }
+        }
+        best
+    }
+}
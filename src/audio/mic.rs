@@ -1,13 +1,19 @@
+use super::stats::{BufferHealth, BufferStats};
 use anyhow::anyhow;
 use cpal::traits::{DeviceTrait, HostTrait, StreamTrait};
 use ringbuf::{HeapConsumer, HeapProducer, HeapRb};
 use std::collections::VecDeque;
+use std::sync::Arc;
 
 pub struct MicCapture {
     _stream: cpal::Stream, // must stay alive or audio stops
     consumer: HeapConsumer<f32>,
+    stereo_consumer: HeapConsumer<[f32; 2]>,
     window: VecDeque<f32>, // sliding window of the latest `fft_size` samples
+    stereo_window: VecDeque<[f32; 2]>,
     pub sample_rate: u32,
+    stats: Arc<BufferStats>,
+    backlog: usize, // samples that were waiting at the last read
 }
 
 impl MicCapture {
@@ -26,21 +32,33 @@ impl MicCapture {
         // ring buffer: 8x fft_size so the callback never stalls
         let rb = HeapRb::<f32>::new(fft_size * 8);
         let (producer, consumer) = rb.split();
+        let (stereo_producer, stereo_consumer) = HeapRb::<[f32; 2]>::new(fft_size * 8).split();
+        let stats = Arc::new(BufferStats::default());
+        let producers = Producers {
+            mono: producer,
+            stereo: stereo_producer,
+            stats: stats.clone(),
+        };
 
-        let stream = build_stream(&device, &config, format, channels, producer)?;
+        let stream = build_stream(&device, &config, format, channels, producers)?;
         stream.play()?;
 
         Ok(Self {
             _stream: stream,
             consumer,
+            stereo_consumer,
             window: VecDeque::from(vec![0.0f32; fft_size]),
+            stereo_window: VecDeque::from(vec![[0.0f32; 2]; fft_size]),
             sample_rate,
+            stats,
+            backlog: 0,
         })
     }
 
     /// Drains new samples from the ring buffer into the sliding window,
     /// then copies the latest `size` samples into `out`.
     pub fn read_window(&mut self, out: &mut Vec<f32>, size: usize) {
+        self.backlog = self.consumer.len();
         while let Some(s) = self.consumer.pop() {
             self.window.push_back(s);
             if self.window.len() > size {
@@ -54,6 +72,47 @@ impl MicCapture {
             out.push(0.0);
         }
     }
+
+    pub fn health(&self) -> BufferHealth {
+        BufferHealth::new(&self.stats, self.backlog, self.consumer.capacity())
+    }
+
+    /// Like `read_window`, but keeps the first two input channels apart.
+    /// Mono devices report the same sample on both sides.
+    pub fn read_stereo_window(&mut self, out: &mut Vec<[f32; 2]>, size: usize) {
+        while let Some(f) = self.stereo_consumer.pop() {
+            self.stereo_window.push_back(f);
+            if self.stereo_window.len() > size {
+                self.stereo_window.pop_front();
+            }
+        }
+        out.clear();
+        out.extend(self.stereo_window.iter().copied());
+        while out.len() < size {
+            out.push([0.0; 2]);
+        }
+    }
+}
+
+/// Both ends the capture callback writes into.
+struct Producers {
+    mono: HeapProducer<f32>,
+    stereo: HeapProducer<[f32; 2]>,
+    stats: Arc<BufferStats>,
+}
+
+impl Producers {
+    fn push(&mut self, chunk: &[f32]) {
+        let mono = chunk.iter().sum::<f32>() / chunk.len() as f32;
+        let left = chunk[0];
+        let right = chunk.get(1).copied().unwrap_or(left);
+        // a full buffer means the UI fell behind; count it rather than block
+        let mono_ok = self.mono.push(mono).is_ok();
+        let stereo_ok = self.stereo.push([left, right]).is_ok();
+        if !(mono_ok && stereo_ok) {
+            self.stats.count_drop();
+        }
+    }
 }
 
 fn mic_err(e: cpal::StreamError) {
@@ -61,41 +120,41 @@ fn mic_err(e: cpal::StreamError) {
 }
 
 /// Builds an input stream for the given sample format.
-/// `producer` is moved into exactly one callback closure.
+/// `producers` is moved into exactly one callback closure.
 fn build_stream(
     device: &cpal::Device,
     config: &cpal::StreamConfig,
     format: cpal::SampleFormat,
     channels: usize,
-    mut producer: HeapProducer<f32>,
+    mut producers: Producers,
 ) -> anyhow::Result<cpal::Stream> {
     Ok(match format {
         cpal::SampleFormat::F32 => device.build_input_stream(
             config,
             move |data: &[f32], _| {
                 for chunk in data.chunks(channels) {
-                    let mono = chunk.iter().sum::<f32>() / channels as f32;
-                    let _ = producer.push(mono);
-                }
-            },
-            mic_err,
-            None,
-        )?,
-        cpal::SampleFormat::I16 => device.build_input_stream(
-            config,
-            move |data: &[i16], _| {
-                for chunk in data.chunks(channels) {
-                    let mono = chunk
-                        .iter()
-                        .map(|&s| s as f32 / i16::MAX as f32)
-                        .sum::<f32>()
-                        / channels as f32;
-                    let _ = producer.push(mono);
+                    producers.push(chunk);
                 }
             },
             mic_err,
             None,
         )?,
+        cpal::SampleFormat::I16 => {
+            // allocated here once, not on the audio thread at every callback
+            let mut scaled = Vec::with_capacity(channels);
+            device.build_input_stream(
+                config,
+                move |data: &[i16], _| {
+                    for chunk in data.chunks(channels) {
+                        scaled.clear();
+                        scaled.extend(chunk.iter().map(|&s| s as f32 / i16::MAX as f32));
+                        producers.push(&scaled);
+                    }
+                },
+                mic_err,
+                None,
+            )?
+        }
         _ => anyhow::bail!("Unsupported mic sample format: {:?}", format),
     })
 }
@@ -1,9 +1,13 @@
 pub mod mic;
 pub mod player;
+pub mod stats;
 pub mod stream;
+pub mod tags;
 pub mod wav;
 
 pub use mic::MicCapture;
 pub use player::AudioPlayer;
+pub use stats::BufferHealth;
 pub use stream::UrlStream;
+pub use tags::TrackInfo;
 pub use wav::AudioData;
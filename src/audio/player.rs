@@ -1,11 +1,16 @@
+use anyhow::Context;
 use rodio::{Decoder, OutputStream, Sink, Source};
 use std::fs::File;
 use std::io::BufReader;
-use std::time::Instant;
+use std::time::{Duration, Instant};
 
 pub struct AudioPlayer {
     _stream: OutputStream, // must stay alive for audio to keep playing
-    start: Instant,
+    sink: Sink,
+    path: String,
+    // playback position = offset + time since `resumed`, while playing
+    offset: f32,
+    resumed: Option<Instant>,
 }
 
 impl AudioPlayer {
@@ -13,17 +18,66 @@ impl AudioPlayer {
         let (_stream, handle) =
             OutputStream::try_default().expect("Failed to open audio output device");
         let sink = Sink::try_new(&handle).expect("Failed to create audio sink");
-        let file = BufReader::new(File::open(path).expect("Failed to open WAV for playback"));
-        let source = Decoder::new(file).expect("Failed to decode WAV for playback");
-        sink.append(source.repeat_infinite());
-        sink.detach();
+        sink.append(
+            decode(path)
+                .expect("Failed to open WAV for playback")
+                .repeat_infinite(),
+        );
         Self {
             _stream,
-            start: Instant::now(),
+            sink,
+            path: path.to_string(),
+            offset: 0.0,
+            resumed: Some(Instant::now()),
         }
     }
 
+    /// Seconds of audio played since the start, counting loops.
     pub fn elapsed_secs(&self) -> f32 {
-        self.start.elapsed().as_secs_f32()
+        self.offset + self.resumed.map_or(0.0, |t| t.elapsed().as_secs_f32())
     }
+
+    pub fn is_paused(&self) -> bool {
+        self.resumed.is_none()
+    }
+
+    pub fn set_paused(&mut self, paused: bool) {
+        if paused == self.is_paused() {
+            return;
+        }
+        if paused {
+            self.offset = self.elapsed_secs();
+            self.resumed = None;
+            self.sink.pause();
+        } else {
+            self.resumed = Some(Instant::now());
+            self.sink.play();
+        }
+    }
+
+    /// Restarts playback `secs` into the file, keeping the paused state. The
+    /// file is opened again, so this fails if it has gone since startup;
+    /// playback then carries on where it was.
+    pub fn seek(&mut self, secs: f32) -> anyhow::Result<()> {
+        // the rest of the file from `secs`, then the usual endless loop
+        let rest = decode(&self.path)?.skip_duration(Duration::from_secs_f32(secs.max(0.0)));
+        let looped = decode(&self.path)?.repeat_infinite();
+        let paused = self.is_paused();
+        self.sink.stop();
+        self.sink.append(rest);
+        self.sink.append(looped);
+        self.offset = secs;
+        self.resumed = (!paused).then(Instant::now);
+        if paused {
+            self.sink.pause();
+        } else {
+            self.sink.play();
+        }
+        Ok(())
+    }
+}
+
+fn decode(path: &str) -> anyhow::Result<Decoder<BufReader<File>>> {
+    let file = File::open(path).with_context(|| format!("Failed to open {path}"))?;
+    Decoder::new(BufReader::new(file)).with_context(|| format!("Failed to decode {path}"))
 }
@@ -0,0 +1,49 @@
+use serde::Serialize;
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+
+/// Counters the audio threads bump and the UI thread reads. Everything is a
+/// relaxed atomic so the realtime callbacks never block on it.
+#[derive(Default)]
+pub struct BufferStats {
+    /// frames lost because a ring buffer was full when the producer pushed
+    pub dropped: AtomicU64,
+    /// times playback ran out of decoded audio
+    pub underruns: AtomicU64,
+    /// frames of silence played in place of missing audio
+    pub underrun_frames: AtomicU64,
+    /// frames waiting in the playback ring buffer (URL mode)
+    pub playback_len: AtomicUsize,
+}
+
+impl BufferStats {
+    pub fn count_drop(&self) {
+        self.dropped.fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+/// How a source's buffers are doing, sampled once per frame.
+#[derive(Clone, Copy, Debug, Default, Serialize)]
+pub struct BufferHealth {
+    /// samples that were waiting in the viz ring buffer at the last read
+    pub backlog: usize,
+    /// `backlog` as a share of the buffer's capacity
+    pub fill: f32,
+    /// fill of the playback ring buffer, for sources that have one
+    pub playback_fill: Option<f32>,
+    pub dropped: u64,
+    pub underruns: u64,
+    pub underrun_frames: u64,
+}
+
+impl BufferHealth {
+    pub fn new(stats: &BufferStats, backlog: usize, capacity: usize) -> Self {
+        Self {
+            backlog,
+            fill: backlog as f32 / capacity.max(1) as f32,
+            playback_fill: None,
+            dropped: stats.dropped.load(Ordering::Relaxed),
+            underruns: stats.underruns.load(Ordering::Relaxed),
+            underrun_frames: stats.underrun_frames.load(Ordering::Relaxed),
+        }
+    }
+}
@@ -1,3 +1,5 @@
+use super::stats::{BufferHealth, BufferStats};
+use super::tags::{PRINT_TEMPLATE, TrackInfo};
 use anyhow::Context;
 use ringbuf::{HeapConsumer, HeapProducer, HeapRb};
 use rodio::{OutputStream, Sink, Source};
@@ -5,54 +7,94 @@ use std::collections::VecDeque;
 use std::io::{BufReader, Read};
 use std::path::{Path, PathBuf};
 use std::process::{Command, Stdio};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
 use std::time::Duration;
 use std::thread;
 
 const OUT_SAMPLE_RATE: u32 = 44_100;
+const PLAYBACK_CAPACITY: usize = OUT_SAMPLE_RATE as usize * 2; // frames, ~2 s
 
 // ── Public struct ─────────────────────────────────────────────────────────────
 
 pub struct UrlStream {
     consumer: HeapConsumer<f32>,   // visualization samples (filled by playback, not decode)
+    stereo_consumer: HeapConsumer<[f32; 2]>,
     window: VecDeque<f32>,
+    stereo_window: VecDeque<[f32; 2]>,
     pub sample_rate: u32,
+    track: Arc<Mutex<TrackInfo>>,  // filled in by the pipeline once yt-dlp reports it
+    played: Arc<AtomicU64>,        // stereo frames actually handed to the output
+    stats: Arc<BufferStats>,
+    backlog: usize,                // samples that were waiting at the last read
     _reader: thread::JoinHandle<()>,
     _audio_stream: OutputStream,   // dropping this stops audio
 }
 
 impl UrlStream {
     pub fn start(url: &str, fft_size: usize) -> anyhow::Result<Self> {
-        // audio ring buffer: ffmpeg decode → RingSource → rodio
-        let (audio_prod, audio_cons) = HeapRb::<f32>::new(OUT_SAMPLE_RATE as usize * 2).split();
+        // audio ring buffer of stereo frames: ffmpeg decode → RingSource → rodio
+        let (audio_prod, audio_cons) = HeapRb::<[f32; 2]>::new(PLAYBACK_CAPACITY).split();
 
-        // viz ring buffer: filled by RingSource *at playback time* so viz = what's playing
+        // viz ring buffers: filled by RingSource *at playback time* so viz = what's playing
         let (viz_prod, viz_cons) = HeapRb::<f32>::new(fft_size * 8).split();
+        let (stereo_prod, stereo_cons) = HeapRb::<[f32; 2]>::new(fft_size * 8).split();
 
         let (_audio_stream, handle) =
             OutputStream::try_default().context("Failed to open audio output device")?;
         let sink = Sink::try_new(&handle).context("Failed to create audio sink")?;
-        sink.append(RingSource::new(audio_cons, viz_prod));
+        let played = Arc::new(AtomicU64::new(0));
+        let stats = Arc::new(BufferStats::default());
+        sink.append(RingSource::new(
+            audio_cons, viz_prod, stereo_prod, played.clone(), stats.clone(),
+        ));
         sink.detach();
 
         let url = url.replace('\\', "");
         let url = url.trim().to_string();
 
+        let track = Arc::new(Mutex::new(TrackInfo::default()));
+        let pipeline_track = track.clone();
         let _reader = thread::spawn(move || {
-            if let Err(e) = run_pipeline(&url, audio_prod) {
+            if let Err(e) = run_pipeline(&url, audio_prod, &pipeline_track) {
                 eprintln!("[url] error: {e}");
             }
         });
 
         Ok(Self {
             consumer: viz_cons,
+            stereo_consumer: stereo_cons,
             window: VecDeque::from(vec![0.0f32; fft_size]),
+            stereo_window: VecDeque::from(vec![[0.0f32; 2]; fft_size]),
             sample_rate: OUT_SAMPLE_RATE,
+            track,
+            played,
+            stats,
+            backlog: 0,
             _reader,
             _audio_stream,
         })
     }
 
+    pub fn track(&self) -> TrackInfo {
+        self.track.lock().unwrap().clone()
+    }
+
+    /// Seconds of audio played so far (silence while buffering doesn't count).
+    pub fn elapsed_secs(&self) -> f32 {
+        self.played.load(Ordering::Relaxed) as f32 / OUT_SAMPLE_RATE as f32
+    }
+
+    pub fn health(&self) -> BufferHealth {
+        let playback = self.stats.playback_len.load(Ordering::Relaxed);
+        BufferHealth {
+            playback_fill: Some(playback as f32 / PLAYBACK_CAPACITY as f32),
+            ..BufferHealth::new(&self.stats, self.backlog, self.consumer.capacity())
+        }
+    }
+
     pub fn read_window(&mut self, out: &mut Vec<f32>, size: usize) {
+        self.backlog = self.consumer.len();
         while let Some(s) = self.consumer.pop() {
             self.window.push_back(s);
             if self.window.len() > size {
@@ -65,6 +107,20 @@ impl UrlStream {
             out.push(0.0);
         }
     }
+
+    pub fn read_stereo_window(&mut self, out: &mut Vec<[f32; 2]>, size: usize) {
+        while let Some(f) = self.stereo_consumer.pop() {
+            self.stereo_window.push_back(f);
+            if self.stereo_window.len() > size {
+                self.stereo_window.pop_front();
+            }
+        }
+        out.clear();
+        out.extend(self.stereo_window.iter().copied());
+        while out.len() < size {
+            out.push([0.0; 2]);
+        }
+    }
 }
 
 // ── Background pipeline ───────────────────────────────────────────────────────
@@ -72,8 +128,13 @@ impl UrlStream {
 /// Downloads the audio, then decodes with ffmpeg at realtime speed.
 /// Samples go into the audio ring buffer only; the viz buffer is filled
 /// by RingSource at the moment rodio actually plays each sample.
-fn run_pipeline(url: &str, mut audio_prod: HeapProducer<f32>) -> anyhow::Result<()> {
-    let downloaded = download(url)?;
+fn run_pipeline(
+    url: &str,
+    mut audio_prod: HeapProducer<[f32; 2]>,
+    track: &Mutex<TrackInfo>,
+) -> anyhow::Result<()> {
+    let (downloaded, info) = download(url)?;
+    *track.lock().unwrap() = info;
     eprintln!("[url] starting playback + visualization…");
 
     let mut ffmpeg = Command::new("ffmpeg")
@@ -82,7 +143,7 @@ fn run_pipeline(url: &str, mut audio_prod: HeapProducer<f32>) -> anyhow::Result<
             "-i", downloaded.to_str().unwrap(),
             "-vn",
             "-f",  "f32le",
-            "-ac", "1",
+            "-ac", "2",
             "-ar", &OUT_SAMPLE_RATE.to_string(),
             "-loglevel", "quiet",
             "pipe:1",
@@ -94,20 +155,16 @@ fn run_pipeline(url: &str, mut audio_prod: HeapProducer<f32>) -> anyhow::Result<
 
     let stdout = ffmpeg.stdout.take().expect("ffmpeg stdout not piped");
     let mut reader = BufReader::new(stdout);
-    let mut bytes = [0u8; 4];
-
-    loop {
-        match reader.read_exact(&mut bytes) {
-            Ok(()) => {
-                let s = f32::from_le_bytes(bytes);
-                // Backpressure: wait until the audio buffer has space rather than
-                // dropping samples (which would cause drift).
-                loop {
-                    if audio_prod.push(s).is_ok() { break; }
-                    thread::sleep(Duration::from_micros(500));
-                }
-            }
-            Err(_) => break,
+    let mut bytes = [0u8; 8]; // one interleaved L/R frame
+
+    while reader.read_exact(&mut bytes).is_ok() {
+        let l = f32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]);
+        let r = f32::from_le_bytes([bytes[4], bytes[5], bytes[6], bytes[7]]);
+        // Backpressure: wait until the audio buffer has space rather than
+        // dropping samples (which would cause drift).
+        loop {
+            if audio_prod.push([l, r]).is_ok() { break; }
+            thread::sleep(Duration::from_micros(500));
         }
     }
 
@@ -117,25 +174,36 @@ fn run_pipeline(url: &str, mut audio_prod: HeapProducer<f32>) -> anyhow::Result<
     Ok(())
 }
 
-fn download(url: &str) -> anyhow::Result<PathBuf> {
+/// Returns the downloaded file and the track metadata yt-dlp printed for it.
+fn download(url: &str) -> anyhow::Result<(PathBuf, TrackInfo)> {
     let tmp_dir = std::env::temp_dir();
     let stem = format!("audio_viz_{}", std::process::id());
     let template = tmp_dir.join(format!("{}.%(ext)s", stem));
 
     eprintln!("[url] downloading…");
-    let status = Command::new("yt-dlp")
+    let output = Command::new("yt-dlp")
         .args([
             "-f", "bestaudio[ext=m4a]/bestaudio[ext=mp4]/bestaudio",
             "--no-playlist",
             "-o", template.to_str().unwrap(),
+            "--print", PRINT_TEMPLATE,
+            "--no-simulate",                          // --print alone skips the download
+            "--progress",                             // …and hides the progress bar
             url,
         ])
+        .stdout(Stdio::piped())
         .stderr(Stdio::inherit())
-        .status()
+        .output()
         .context("Failed to run yt-dlp — install with: brew install yt-dlp")?;
 
-    anyhow::ensure!(status.success(), "yt-dlp exited with an error");
-    find_file(&tmp_dir, &stem)
+    anyhow::ensure!(output.status.success(), "yt-dlp exited with an error");
+    let printed = String::from_utf8_lossy(&output.stdout);
+    let info = printed
+        .lines()
+        .rfind(|l| l.contains('\t'))
+        .map(TrackInfo::from_ytdlp)
+        .unwrap_or_default();
+    Ok((find_file(&tmp_dir, &stem)?, info))
 }
 
 fn find_file(dir: &Path, stem: &str) -> anyhow::Result<PathBuf> {
@@ -148,32 +216,76 @@ fn find_file(dir: &Path, stem: &str) -> anyhow::Result<PathBuf> {
 
 // ── Custom rodio Source ───────────────────────────────────────────────────────
 
-/// Pulls samples from the audio ring buffer for rodio playback.
-/// Every sample that gets played is also forwarded to viz_prod so the
+/// Pulls stereo frames from the audio ring buffer for rodio playback.
+/// Every frame that gets played is also forwarded to the viz buffers so the
 /// visualizer sees exactly what's being heard — guaranteed sync.
 struct RingSource {
-    consumer: HeapConsumer<f32>,
+    consumer: HeapConsumer<[f32; 2]>,
     viz_prod: HeapProducer<f32>,
+    stereo_prod: HeapProducer<[f32; 2]>,
+    pending_right: Option<f32>,  // rodio wants interleaved samples, one at a time
+    played: Arc<AtomicU64>,
+    stats: Arc<BufferStats>,
+    starved: bool,               // out of audio; true until the first frame arrives
 }
 
 impl RingSource {
-    fn new(consumer: HeapConsumer<f32>, viz_prod: HeapProducer<f32>) -> Self {
-        Self { consumer, viz_prod }
+    fn new(
+        consumer: HeapConsumer<[f32; 2]>,
+        viz_prod: HeapProducer<f32>,
+        stereo_prod: HeapProducer<[f32; 2]>,
+        played: Arc<AtomicU64>,
+        stats: Arc<BufferStats>,
+    ) -> Self {
+        Self {
+            consumer, viz_prod, stereo_prod,
+            pending_right: None,
+            played, stats,
+            starved: true,
+        }
     }
 }
 
 impl Iterator for RingSource {
     type Item = f32;
     fn next(&mut self) -> Option<f32> {
-        let s = self.consumer.pop().unwrap_or(0.0);
-        let _ = self.viz_prod.push(s);  // forward to viz at playback time
-        Some(s)
+        if let Some(r) = self.pending_right.take() {
+            return Some(r);
+        }
+        self.stats.playback_len.store(self.consumer.len(), Ordering::Relaxed);
+        let [l, r] = match self.consumer.pop() {
+            Some(frame) => {
+                self.played.fetch_add(1, Ordering::Relaxed);
+                self.starved = false;
+                frame
+            }
+            None => {
+                // underrun: play silence, don't advance. Waiting for the
+                // download before the first frame isn't counted.
+                if self.played.load(Ordering::Relaxed) > 0 {
+                    if !self.starved {
+                        self.stats.underruns.fetch_add(1, Ordering::Relaxed);
+                    }
+                    self.stats.underrun_frames.fetch_add(1, Ordering::Relaxed);
+                }
+                self.starved = true;
+                [0.0; 2]
+            }
+        };
+        // forward to viz at playback time
+        let mono_ok = self.viz_prod.push((l + r) * 0.5).is_ok();
+        let stereo_ok = self.stereo_prod.push([l, r]).is_ok();
+        if !(mono_ok && stereo_ok) {
+            self.stats.count_drop();
+        }
+        self.pending_right = Some(r);
+        Some(l)
     }
 }
 
 impl Source for RingSource {
     fn current_frame_len(&self) -> Option<usize> { None }
-    fn channels(&self) -> u16 { 1 }
+    fn channels(&self) -> u16 { 2 }
     fn sample_rate(&self) -> u32 { OUT_SAMPLE_RATE }
     fn total_duration(&self) -> Option<Duration> { None }
 }
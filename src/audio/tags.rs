@@ -0,0 +1,164 @@
+use std::fs::File;
+use std::io::{BufReader, Read, Seek, SeekFrom};
+use std::path::Path;
+
+/// What's playing, for display. Any field may be unknown.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct TrackInfo {
+    pub title: Option<String>,
+    pub artist: Option<String>,
+    pub duration: Option<f32>, // seconds
+}
+
+impl TrackInfo {
+    /// Title and artist from the file's `LIST/INFO` chunk, falling back to the
+    /// file name when it has no tags.
+    pub fn from_wav(path: &Path, duration: f32) -> Self {
+        let (title, artist) = read_riff_info(path).unwrap_or_else(|e| {
+            eprintln!("[wav] couldn't read tags from {}: {e}", path.display());
+            (None, None)
+        });
+        Self {
+            title: title.or_else(|| path.file_stem().map(|s| s.to_string_lossy().into_owned())),
+            artist,
+            duration: Some(duration),
+        }
+    }
+
+    /// Parses the line printed by yt-dlp for `--print` with `PRINT_TEMPLATE`.
+    pub fn from_ytdlp(line: &str) -> Self {
+        let mut fields = line
+            .split('\t')
+            .map(str::trim)
+            .map(|f| (!f.is_empty() && f != "NA").then(|| f.to_string()));
+        let title = fields.next().flatten();
+        let artist = fields.next().flatten();
+        let duration = fields.next().flatten().and_then(|d| d.parse().ok());
+        Self {
+            title,
+            artist,
+            duration,
+        }
+    }
+}
+
+/// yt-dlp output template for `from_ytdlp`: title, artist (or uploader) and
+/// duration in seconds, tab separated.
+pub const PRINT_TEMPLATE: &str = "%(title)s\t%(artist,creator,uploader)s\t%(duration)s";
+
+/// Walks the RIFF chunks looking for `LIST/INFO` and returns its `INAM` and
+/// `IART` entries. Chunks are skipped by seeking, so large files are cheap.
+fn read_riff_info(path: &Path) -> std::io::Result<(Option<String>, Option<String>)> {
+    let file = File::open(path)?;
+    let len = file.metadata()?.len();
+    let mut file = BufReader::new(file);
+    let mut header = [0u8; 12];
+    file.read_exact(&mut header)?;
+    if &header[0..4] != b"RIFF" || &header[8..12] != b"WAVE" {
+        return Ok((None, None));
+    }
+
+    let mut chunk = [0u8; 8];
+    while file.read_exact(&mut chunk).is_ok() {
+        let size = u32::from_le_bytes([chunk[4], chunk[5], chunk[6], chunk[7]]) as usize;
+        let padded = size + size % 2; // chunks are word aligned
+        if &chunk[0..4] != b"LIST" || size < 4 {
+            file.seek(SeekFrom::Current(padded as i64))?;
+            continue;
+        }
+        // a corrupt header can claim gigabytes; never allocate past the file's end
+        if size as u64 > len.saturating_sub(file.stream_position()?) {
+            return Ok((None, None));
+        }
+        let mut body = vec![0u8; size];
+        file.read_exact(&mut body)?;
+        if &body[0..4] == b"INFO" {
+            return Ok(parse_info(&body[4..]));
+        }
+        file.seek(SeekFrom::Current((padded - size) as i64))?;
+    }
+    Ok((None, None))
+}
+
+fn parse_info(mut body: &[u8]) -> (Option<String>, Option<String>) {
+    let (mut title, mut artist) = (None, None);
+    while body.len() >= 8 {
+        let id = &body[0..4];
+        let size = u32::from_le_bytes([body[4], body[5], body[6], body[7]]) as usize;
+        let end = (8 + size).min(body.len());
+        let text = String::from_utf8_lossy(&body[8..end])
+            .trim_end_matches('\0')
+            .trim()
+            .to_string();
+        if !text.is_empty() {
+            match id {
+                b"INAM" => title = Some(text),
+                b"IART" => artist = Some(text),
+                _ => {}
+            }
+        }
+        body = &body[(end + size % 2).min(body.len())..];
+    }
+    (title, artist)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testutil::TempDir;
+
+    fn chunk(id: &[u8], body: &[u8]) -> Vec<u8> {
+        let mut out = id.to_vec();
+        out.extend((body.len() as u32).to_le_bytes());
+        out.extend(body);
+        if body.len() % 2 == 1 {
+            out.push(0);
+        }
+        out
+    }
+
+    #[test]
+    fn reads_list_info_tags() {
+        let mut info = b"INFO".to_vec();
+        info.extend(chunk(b"IART", b"The Band\0"));
+        info.extend(chunk(b"INAM", b"A Song\0"));
+        let mut body = b"WAVE".to_vec();
+        body.extend(chunk(b"fmt ", &[0; 16]));
+        body.extend(chunk(b"data", &[0; 7]));
+        body.extend(chunk(b"LIST", &info));
+
+        let dir = TempDir::new("tags");
+        let path = dir.join("tagged.wav");
+        std::fs::write(&path, chunk(b"RIFF", &body)).unwrap();
+        let track = TrackInfo::from_wav(&path, 12.0);
+
+        assert_eq!(track.title.as_deref(), Some("A Song"));
+        assert_eq!(track.artist.as_deref(), Some("The Band"));
+        assert_eq!(track.duration, Some(12.0));
+    }
+
+    #[test]
+    fn oversized_list_chunks_are_ignored() {
+        let mut body = b"WAVE".to_vec();
+        body.extend(b"LIST");
+        body.extend(u32::MAX.to_le_bytes());
+        body.extend(b"INFO");
+
+        let dir = TempDir::new("tags-big");
+        let path = dir.join("untagged.wav");
+        std::fs::write(&path, chunk(b"RIFF", &body)).unwrap();
+        let track = TrackInfo::from_wav(&path, 1.0);
+
+        assert_eq!(track.title.as_deref(), Some("untagged"));
+        assert_eq!(track.artist, None);
+    }
+
+    #[test]
+    fn parses_ytdlp_lines() {
+        let track = TrackInfo::from_ytdlp("Live Set\tSome DJ\t3600.5\n");
+        assert_eq!(track.title.as_deref(), Some("Live Set"));
+        assert_eq!(track.artist.as_deref(), Some("Some DJ"));
+        assert_eq!(track.duration, Some(3600.5));
+        assert_eq!(TrackInfo::from_ytdlp("Untitled\tNA\tNA").artist, None);
+    }
+}
@@ -1,10 +1,11 @@
 use hound::{SampleFormat, WavReader};
-use std::{i16, path::Path};
+use std::path::Path;
 
 #[derive(Clone)]
 pub struct AudioData {
     pub sample_rate: u32,
     pub samples_mono: Vec<f32>,
+    pub samples_stereo: Vec<[f32; 2]>, // mono files are duplicated into both channels
     pub duration_sec: f32,
 }
 
@@ -30,6 +31,7 @@ impl AudioData {
         }
 
         let mut mono = Vec::new();
+        let mut stereo = Vec::new();
 
         //read frames
         let mut frame = Vec::with_capacity(channels);
@@ -37,12 +39,13 @@ impl AudioData {
             let s = s? as f32 / i16::MAX as f32;
             frame.push(s);
             if frame.len() == channels {
-                let m = if channels == 1 {
-                    frame[0]
+                let (l, r) = if channels == 1 {
+                    (frame[0], frame[0])
                 } else {
-                    (frame[0] + frame[1]) * 0.5
+                    (frame[0], frame[1])
                 };
-                mono.push(m);
+                mono.push((l + r) * 0.5);
+                stereo.push([l, r]);
                 frame.clear();
             }
         }
@@ -50,6 +53,7 @@ impl AudioData {
         Ok(Self {
             sample_rate,
             samples_mono: mono,
+            samples_stereo: stereo,
             duration_sec,
         })
     }
@@ -72,4 +76,23 @@ impl AudioData {
             out.push(self.samples_mono[idx]);
         }
     }
+
+    //same as window_at_time, but keeps left/right apart
+    pub fn stereo_window_at_time(&self, t_sec: f32, n: usize, out: &mut Vec<[f32; 2]>) {
+        out.clear();
+        out.reserve(n);
+
+        let dur = self.duration_sec.max(0.000_1);
+        let t = t_sec.rem_euclid(dur);
+
+        let center = (t * self.sample_rate as f32) as isize;
+        let half = (n as isize) / 2;
+
+        let len = self.samples_stereo.len() as isize;
+
+        for i in 0..(n as isize) {
+            let idx = (center - half + i).rem_euclid(len) as usize;
+            out.push(self.samples_stereo[idx]);
+        }
+    }
 }
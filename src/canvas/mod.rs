@@ -0,0 +1,80 @@
+pub mod raster;
+pub mod window;
+
+pub use raster::Pixmap;
+pub use window::WindowCanvas;
+
+use nannou::geom::Rect;
+use nannou::glam::Vec2;
+use nannou::text::Font;
+
+/// Straight-alpha sRGB colour, every component in 0..1.
+pub type Rgba = [f32; 4];
+
+pub fn rgba(r: f32, g: f32, b: f32, a: f32) -> Rgba {
+    [r, g, b, a]
+}
+
+/// HSV (all in 0..1, hue wraps) plus alpha, converted the same way nannou's `hsva` is.
+pub fn hsva(h: f32, s: f32, v: f32, a: f32) -> Rgba {
+    let h = h.rem_euclid(1.0) * 6.0;
+    let c = v * s;
+    let x = c * (1.0 - ((h % 2.0) - 1.0).abs());
+    let (r, g, b) = match h as u32 {
+        0 => (c, x, 0.0),
+        1 => (x, c, 0.0),
+        2 => (0.0, c, x),
+        3 => (0.0, x, c),
+        4 => (x, 0.0, c),
+        _ => (c, 0.0, x),
+    };
+    let m = v - c;
+    [r + m, g + m, b + m, a]
+}
+
+/// Which side of the anchor point text extends from.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Align {
+    Left,
+    Center,
+    Right,
+}
+
+/// The handful of 2D primitives the visualizers need, independent of where
+/// the pixels end up. Coordinates follow nannou: origin at the centre, y up,
+/// units in pixels. Everything is alpha-blended "source over".
+pub trait Canvas {
+    /// Drawable area in canvas coordinates.
+    fn bounds(&self) -> Rect;
+
+    /// Multiplies the alpha of everything drawn afterwards; used for crossfades.
+    fn set_opacity(&mut self, opacity: f32);
+
+    /// Blends `c` over the whole surface — alpha 1 clears, lower alphas leave fading trails.
+    fn fill(&mut self, c: Rgba);
+
+    fn rect(&mut self, center: Vec2, size: Vec2, c: Rgba);
+
+    /// Many rects at once; backends may batch them into a single draw call.
+    fn rects(&mut self, rects: &[(Vec2, Vec2, Rgba)]) {
+        for &(center, size, c) in rects {
+            self.rect(center, size, c);
+        }
+    }
+
+    fn line(&mut self, a: Vec2, b: Vec2, weight: f32, c: Rgba);
+
+    fn polyline(&mut self, points: &[Vec2], weight: f32, c: Rgba) {
+        for seg in points.windows(2) {
+            self.line(seg[0], seg[1], weight, c);
+        }
+    }
+
+    fn circle(&mut self, center: Vec2, radius: f32, c: Rgba);
+
+    /// Font for `text` from now on; `None` goes back to the built-in one.
+    fn set_font(&mut self, font: Option<Font>);
+
+    /// Single line of text, vertically centred on `at`.
+    fn text(&mut self, text: &str, at: Vec2, size: u32, align: Align, c: Rgba);
+}
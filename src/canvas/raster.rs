@@ -0,0 +1,186 @@
+use super::{Align, Canvas, Rgba};
+use nannou::geom::Rect;
+use nannou::glam::Vec2;
+use nannou::text::{Font, Scale, font, rt};
+
+/// CPU framebuffer and software rasterizer, for offline export, tests and
+/// headless machines without a GPU.
+///
+/// Colours are blended "source over" in f32 so slow fades don't band or get
+/// stuck a step above black.
+pub struct Pixmap {
+    pub width: u32,
+    pub height: u32,
+    pixels: Vec<[f32; 3]>, // opaque RGB; the image itself has no alpha
+    opacity: f32,
+    font: Option<Font>, // the built-in one is loaded on first use
+}
+
+impl Pixmap {
+    pub fn new(width: u32, height: u32) -> Self {
+        Self {
+            width,
+            height,
+            pixels: vec![[0.0; 3]; (width * height) as usize],
+            opacity: 1.0,
+            font: None,
+        }
+    }
+
+    /// Converts canvas coordinates to continuous pixel coordinates (y down).
+    fn to_px(&self, p: Vec2) -> (f32, f32) {
+        (
+            p.x + self.width as f32 * 0.5,
+            self.height as f32 * 0.5 - p.y,
+        )
+    }
+
+    fn blend(&mut self, px: i64, py: i64, c: Rgba, coverage: f32) {
+        if px < 0 || py < 0 || px >= self.width as i64 || py >= self.height as i64 {
+            return;
+        }
+        let a = (c[3] * self.opacity * coverage).clamp(0.0, 1.0);
+        if a <= 0.0 {
+            return;
+        }
+        let dst = &mut self.pixels[py as usize * self.width as usize + px as usize];
+        for i in 0..3 {
+            dst[i] += (c[i] - dst[i]) * a;
+        }
+    }
+
+    /// Pixel range covering `[lo, hi)` along an axis of `len` pixels.
+    fn span(lo: f32, hi: f32, len: u32) -> std::ops::Range<i64> {
+        lo.floor().max(0.0) as i64..hi.ceil().min(len as f32) as i64
+    }
+
+    /// Packs the image as 8-bit RGBA rows, top to bottom.
+    pub fn to_rgba8(&self) -> Vec<u8> {
+        let mut out = Vec::with_capacity(self.pixels.len() * 4);
+        for p in &self.pixels {
+            for &ch in p {
+                out.push((ch.clamp(0.0, 1.0) * 255.0 + 0.5) as u8);
+            }
+            out.push(255);
+        }
+        out
+    }
+}
+
+impl Canvas for Pixmap {
+    fn bounds(&self) -> Rect {
+        Rect::from_w_h(self.width as f32, self.height as f32)
+    }
+
+    fn set_opacity(&mut self, opacity: f32) {
+        self.opacity = opacity.clamp(0.0, 1.0);
+    }
+
+    fn fill(&mut self, c: Rgba) {
+        let a = (c[3] * self.opacity).clamp(0.0, 1.0);
+        for dst in &mut self.pixels {
+            for i in 0..3 {
+                dst[i] += (c[i] - dst[i]) * a;
+            }
+        }
+    }
+
+    /// Anti-aliased on its edges by exact pixel coverage.
+    fn rect(&mut self, center: Vec2, size: Vec2, c: Rgba) {
+        let (cx, cy) = self.to_px(center);
+        let (x0, x1) = (cx - size.x * 0.5, cx + size.x * 0.5);
+        let (y0, y1) = (cy - size.y * 0.5, cy + size.y * 0.5);
+        for py in Self::span(y0, y1, self.height) {
+            let cov_y = overlap(py as f32, y0, y1);
+            for px in Self::span(x0, x1, self.width) {
+                let cov = cov_y * overlap(px as f32, x0, x1);
+                self.blend(px, py, c, cov);
+            }
+        }
+    }
+
+    /// Round-capped; coverage comes from the distance to the segment.
+    fn line(&mut self, a: Vec2, b: Vec2, weight: f32, c: Rgba) {
+        let (ax, ay) = self.to_px(a);
+        let (bx, by) = self.to_px(b);
+        let r = weight * 0.5;
+        let pad = r + 1.0;
+
+        let xs = Self::span(ax.min(bx) - pad, ax.max(bx) + pad, self.width);
+        let ys = Self::span(ay.min(by) - pad, ay.max(by) + pad, self.height);
+
+        let (dx, dy) = (bx - ax, by - ay);
+        let len_sq = dx * dx + dy * dy;
+        for py in ys {
+            for px in xs.clone() {
+                let (qx, qy) = (px as f32 + 0.5, py as f32 + 0.5);
+                let t = if len_sq > 0.0 {
+                    (((qx - ax) * dx + (qy - ay) * dy) / len_sq).clamp(0.0, 1.0)
+                } else {
+                    0.0
+                };
+                let (ex, ey) = (ax + dx * t - qx, ay + dy * t - qy);
+                let dist = (ex * ex + ey * ey).sqrt();
+                // lines thinner than a pixel fade instead of vanishing
+                let cov = (r + 0.5 - dist).clamp(0.0, 1.0) * weight.min(1.0);
+                self.blend(px, py, c, cov);
+            }
+        }
+    }
+
+    fn circle(&mut self, center: Vec2, radius: f32, c: Rgba) {
+        let (cx, cy) = self.to_px(center);
+        let pad = radius + 1.0;
+        for py in Self::span(cy - pad, cy + pad, self.height) {
+            for px in Self::span(cx - pad, cx + pad, self.width) {
+                let (ex, ey) = (px as f32 + 0.5 - cx, py as f32 + 0.5 - cy);
+                let cov = (radius + 0.5 - (ex * ex + ey * ey).sqrt()).clamp(0.0, 1.0);
+                self.blend(px, py, c, cov);
+            }
+        }
+    }
+
+    fn set_font(&mut self, font: Option<Font>) {
+        self.font = font;
+    }
+
+    fn text(&mut self, text: &str, at: Vec2, size: u32, align: Align, c: Rgba) {
+        let font = self.font.get_or_insert_with(font::default_notosans).clone();
+        let scale = Scale::uniform(size as f32);
+        let v = font.v_metrics(scale);
+
+        // lay out once at the origin to measure, then shift into place
+        let width = font
+            .layout(text, scale, rt::point(0.0, 0.0))
+            .last()
+            .map_or(0.0, |g| {
+                g.position().x + g.unpositioned().h_metrics().advance_width
+            });
+        let (ax, ay) = self.to_px(at);
+        let x = match align {
+            Align::Left => ax,
+            Align::Center => ax - width * 0.5,
+            Align::Right => ax - width,
+        };
+        let baseline = ay + (v.ascent + v.descent) * 0.5;
+
+        for glyph in font.layout(text, scale, rt::point(x, baseline)) {
+            let Some(bb) = glyph.pixel_bounding_box() else {
+                continue;
+            };
+            glyph.draw(|gx, gy, cov| {
+                self.blend(
+                    bb.min.x as i64 + gx as i64,
+                    bb.min.y as i64 + gy as i64,
+                    c,
+                    cov,
+                );
+            });
+        }
+    }
+}
+
+/// How much of pixel `[p, p + 1)` lies inside `[lo, hi)`.
+fn overlap(p: f32, lo: f32, hi: f32) -> f32 {
+    (hi.min(p + 1.0) - lo.max(p)).clamp(0.0, 1.0)
+}
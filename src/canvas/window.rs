@@ -0,0 +1,110 @@
+use super::{Align, Canvas, Rgba};
+use nannou::geom::Tri;
+use nannou::prelude::*;
+use nannou::text::Font;
+
+/// Draws through nannou's `Draw` into the window.
+pub struct WindowCanvas<'a> {
+    draw: &'a Draw,
+    bounds: Rect,
+    opacity: f32,
+    font: Option<Font>,
+}
+
+impl<'a> WindowCanvas<'a> {
+    pub fn new(draw: &'a Draw, bounds: Rect) -> Self {
+        Self {
+            draw,
+            bounds,
+            opacity: 1.0,
+            font: None,
+        }
+    }
+
+    fn color(&self, c: Rgba) -> Srgba {
+        srgba(c[0], c[1], c[2], c[3] * self.opacity)
+    }
+}
+
+impl Canvas for WindowCanvas<'_> {
+    fn bounds(&self) -> Rect {
+        self.bounds
+    }
+
+    fn set_opacity(&mut self, opacity: f32) {
+        self.opacity = opacity.clamp(0.0, 1.0);
+    }
+
+    fn fill(&mut self, c: Rgba) {
+        self.draw
+            .rect()
+            .xy(self.bounds.xy())
+            .wh(self.bounds.wh())
+            .color(self.color(c));
+    }
+
+    fn rect(&mut self, center: Vec2, size: Vec2, c: Rgba) {
+        self.draw.rect().xy(center).wh(size).color(self.color(c));
+    }
+
+    fn rects(&mut self, rects: &[(Vec2, Vec2, Rgba)]) {
+        // one mesh instead of thousands of rect primitives
+        let tris = rects.iter().flat_map(|&(center, size, c)| {
+            let col = self.color(c);
+            let half = size * 0.5;
+            let p = |dx: f32, dy: f32| (pt3(center.x + dx, center.y + dy, 0.0), col);
+            let (p0, p1) = (p(-half.x, -half.y), p(half.x, -half.y));
+            let (p2, p3) = (p(half.x, half.y), p(-half.x, half.y));
+            [Tri([p0, p1, p2]), Tri([p0, p2, p3])]
+        });
+        self.draw.mesh().tris_colored(tris);
+    }
+
+    fn line(&mut self, a: Vec2, b: Vec2, weight: f32, c: Rgba) {
+        self.draw
+            .line()
+            .start(a)
+            .end(b)
+            .weight(weight)
+            .color(self.color(c));
+    }
+
+    fn polyline(&mut self, points: &[Vec2], weight: f32, c: Rgba) {
+        self.draw
+            .polyline()
+            .weight(weight)
+            .points(points.iter().copied())
+            .color(self.color(c));
+    }
+
+    fn circle(&mut self, center: Vec2, radius: f32, c: Rgba) {
+        self.draw
+            .ellipse()
+            .xy(center)
+            .radius(radius)
+            .color(self.color(c));
+    }
+
+    fn set_font(&mut self, font: Option<Font>) {
+        self.font = font;
+    }
+
+    fn text(&mut self, text: &str, at: Vec2, size: u32, align: Align, c: Rgba) {
+        // nannou lays text out inside a box; anchor the box edge on `at`
+        let w = self.bounds.w().max(1.0) * 2.0;
+        let mut t = self
+            .draw
+            .text(text)
+            .font_size(size)
+            .w(w)
+            .color(self.color(c));
+        if let Some(font) = &self.font {
+            t = t.font(font.clone());
+        }
+        match align {
+            Align::Left => t.left_justify().x_y(at.x + w * 0.5, at.y),
+            Align::Center => t.x_y(at.x, at.y),
+            Align::Right => t.right_justify().x_y(at.x - w * 0.5, at.y),
+        };
+    }
+}
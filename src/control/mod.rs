@@ -0,0 +1,286 @@
+mod remote;
+
+pub use remote::{Command, Target, value_arg};
+
+use anyhow::{Context, bail};
+use nannou::prelude::Key;
+use std::collections::{BTreeMap, HashMap};
+use std::path::Path;
+use std::str::FromStr;
+
+/// Something a key can do. Written in keymap files as kebab-case strings,
+/// e.g. `"gain-up"` or `"view-3"`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Action {
+    /// Switch to the n-th visualizer (0-based here, 1-based in files)
+    View(usize),
+    NextView,
+    PrevView,
+    NextPreset,
+    PrevPreset,
+    PresetList,
+    GainUp,
+    GainDown,
+    SmoothingUp,
+    SmoothingDown,
+    HueUp,
+    HueDown,
+    Freeze,
+    Fullscreen,
+    Hud,
+    Diagnostics,
+    TapTempo,
+    /// Pause or resume file playback and replay
+    Pause,
+    /// Jump 5 s back or on in file playback and replay
+    SeekBack,
+    SeekForward,
+    /// Step through values to bind the next MIDI knob moved to
+    MidiLearn,
+    /// Back to the preset's values: gain, hue, rotation, zoom and tapped tempo
+    Reset,
+}
+
+const ACTIONS: &[(&str, Action)] = &[
+    ("next-view", Action::NextView),
+    ("prev-view", Action::PrevView),
+    ("next-preset", Action::NextPreset),
+    ("prev-preset", Action::PrevPreset),
+    ("preset-list", Action::PresetList),
+    ("gain-up", Action::GainUp),
+    ("gain-down", Action::GainDown),
+    ("smoothing-up", Action::SmoothingUp),
+    ("smoothing-down", Action::SmoothingDown),
+    ("hue-up", Action::HueUp),
+    ("hue-down", Action::HueDown),
+    ("freeze", Action::Freeze),
+    ("fullscreen", Action::Fullscreen),
+    ("hud", Action::Hud),
+    ("diagnostics", Action::Diagnostics),
+    ("tap-tempo", Action::TapTempo),
+    ("pause", Action::Pause),
+    ("seek-back", Action::SeekBack),
+    ("seek-forward", Action::SeekForward),
+    ("midi-learn", Action::MidiLearn),
+    ("reset", Action::Reset),
+];
+
+impl FromStr for Action {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> anyhow::Result<Self> {
+        if let Some(n) = s.strip_prefix("view-") {
+            let n: usize = n
+                .parse()
+                .with_context(|| format!("bad view number in '{s}'"))?;
+            anyhow::ensure!(n >= 1, "views are numbered from 1, got '{s}'");
+            return Ok(Self::View(n - 1));
+        }
+        match ACTIONS.iter().find(|(name, _)| *name == s) {
+            Some(&(_, action)) => Ok(action),
+            None => {
+                let names: Vec<&str> = ACTIONS.iter().map(|(name, _)| *name).collect();
+                bail!(
+                    "Unknown action '{s}', expected view-<n>, none or one of: {}",
+                    names.join(", ")
+                )
+            }
+        }
+    }
+}
+
+/// Keys that can be bound, by the name used in keymap files.
+const KEYS: &[(&str, Key)] = &[
+    ("1", Key::Key1),
+    ("2", Key::Key2),
+    ("3", Key::Key3),
+    ("4", Key::Key4),
+    ("5", Key::Key5),
+    ("6", Key::Key6),
+    ("7", Key::Key7),
+    ("8", Key::Key8),
+    ("9", Key::Key9),
+    ("0", Key::Key0),
+    ("a", Key::A),
+    ("b", Key::B),
+    ("c", Key::C),
+    ("d", Key::D),
+    ("e", Key::E),
+    ("f", Key::F),
+    ("g", Key::G),
+    ("h", Key::H),
+    ("i", Key::I),
+    ("j", Key::J),
+    ("k", Key::K),
+    ("l", Key::L),
+    ("m", Key::M),
+    ("n", Key::N),
+    ("o", Key::O),
+    ("p", Key::P),
+    ("q", Key::Q),
+    ("r", Key::R),
+    ("s", Key::S),
+    ("t", Key::T),
+    ("u", Key::U),
+    ("v", Key::V),
+    ("w", Key::W),
+    ("x", Key::X),
+    ("y", Key::Y),
+    ("z", Key::Z),
+    ("f1", Key::F1),
+    ("f2", Key::F2),
+    ("f3", Key::F3),
+    ("f4", Key::F4),
+    ("f5", Key::F5),
+    ("f6", Key::F6),
+    ("f7", Key::F7),
+    ("f8", Key::F8),
+    ("f9", Key::F9),
+    ("f10", Key::F10),
+    ("f11", Key::F11),
+    ("f12", Key::F12),
+    ("space", Key::Space),
+    ("tab", Key::Tab),
+    ("enter", Key::Return),
+    ("backspace", Key::Back),
+    ("up", Key::Up),
+    ("down", Key::Down),
+    ("left", Key::Left),
+    ("right", Key::Right),
+    ("-", Key::Minus),
+    ("=", Key::Equals),
+    ("[", Key::LBracket),
+    ("]", Key::RBracket),
+    (",", Key::Comma),
+    (".", Key::Period),
+    ("/", Key::Slash),
+    (";", Key::Semicolon),
+    ("'", Key::Apostrophe),
+    ("\\", Key::Backslash),
+    ("`", Key::Grave),
+];
+
+fn parse_key(name: &str) -> anyhow::Result<Key> {
+    let lower = name.to_ascii_lowercase();
+    match KEYS.iter().find(|(n, _)| *n == lower) {
+        Some(&(_, key)) => Ok(key),
+        None => bail!(
+            "Unknown key '{name}' (letters, digits, f1-f12, space, tab, enter, backspace, arrows and - = [ ] , . / ; ' \\ ` can be bound)"
+        ),
+    }
+}
+
+/// The bindings used when no keymap file is given, and the base a file
+/// adds to. Keys left unbound (T, the arrows) go to the active visualizer.
+const DEFAULT: &[(&str, &str)] = &[
+    ("1", "view-1"),
+    ("2", "view-2"),
+    ("3", "view-3"),
+    ("4", "view-4"),
+    ("5", "view-5"),
+    ("6", "view-6"),
+    ("7", "view-7"),
+    ("8", "view-8"),
+    ("9", "view-9"),
+    ("tab", "next-view"),
+    ("[", "prev-preset"),
+    ("]", "next-preset"),
+    ("p", "preset-list"),
+    ("=", "gain-up"),
+    ("-", "gain-down"),
+    (";", "smoothing-down"),
+    ("'", "smoothing-up"),
+    (",", "hue-down"),
+    (".", "hue-up"),
+    ("space", "freeze"),
+    ("f", "fullscreen"),
+    ("h", "hud"),
+    ("d", "diagnostics"),
+    ("b", "tap-tempo"),
+    ("k", "pause"),
+    ("j", "seek-back"),
+    ("l", "seek-forward"),
+    ("m", "midi-learn"),
+    ("r", "reset"),
+];
+
+/// Which key does what.
+pub struct Keymap {
+    bindings: HashMap<Key, Action>,
+}
+
+impl Default for Keymap {
+    fn default() -> Self {
+        let mut map = Self {
+            bindings: HashMap::new(),
+        };
+        for &(key, action) in DEFAULT {
+            map.bind(key, action).expect("default keymap is valid");
+        }
+        map
+    }
+}
+
+impl Keymap {
+    /// The defaults with a TOML file's `key = "action"` lines on top.
+    /// `"none"` unbinds a key so it reaches the visualizers again.
+    pub fn load(path: &Path) -> anyhow::Result<Self> {
+        let text = std::fs::read_to_string(path)
+            .with_context(|| format!("Failed to read {}", path.display()))?;
+        Self::parse(&text).with_context(|| format!("Invalid keymap {}", path.display()))
+    }
+
+    fn parse(text: &str) -> anyhow::Result<Self> {
+        let entries: BTreeMap<String, String> = toml::from_str(text)?;
+        let mut map = Self::default();
+        for (key, action) in &entries {
+            map.bind(key, action)?;
+        }
+        Ok(map)
+    }
+
+    fn bind(&mut self, key: &str, action: &str) -> anyhow::Result<()> {
+        let key = parse_key(key)?;
+        if action == "none" {
+            self.bindings.remove(&key);
+        } else {
+            self.bindings.insert(key, action.parse()?);
+        }
+        Ok(())
+    }
+
+    pub fn get(&self, key: Key) -> Option<Action> {
+        self.bindings.get(&key).copied()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn defaults_cover_the_classic_keys() {
+        let map = Keymap::default();
+        assert_eq!(map.get(Key::Key1), Some(Action::View(0)));
+        assert_eq!(map.get(Key::RBracket), Some(Action::NextPreset));
+        assert_eq!(map.get(Key::F), Some(Action::Fullscreen));
+        // left for the oscilloscope
+        assert_eq!(map.get(Key::T), None);
+    }
+
+    #[test]
+    fn files_override_and_unbind() {
+        let map = Keymap::parse("space = \"tap-tempo\"\nF = \"none\"\nx = \"view-2\"\n").unwrap();
+        assert_eq!(map.get(Key::Space), Some(Action::TapTempo));
+        assert_eq!(map.get(Key::F), None);
+        assert_eq!(map.get(Key::X), Some(Action::View(1)));
+        assert_eq!(map.get(Key::H), Some(Action::Hud));
+    }
+
+    #[test]
+    fn bad_names_are_errors() {
+        assert!(Keymap::parse("space = \"jump\"\n").is_err());
+        assert!(Keymap::parse("hyper = \"freeze\"\n").is_err());
+        assert!(Keymap::parse("x = \"view-0\"\n").is_err());
+    }
+}
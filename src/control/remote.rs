@@ -0,0 +1,169 @@
+use super::Action;
+use crate::osc::{Arg, Message};
+use anyhow::{Context, bail};
+use serde_json::Value;
+
+/// A visualizer or preset, by 1-based position or by name.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Target {
+    Index(usize), // 0-based once parsed
+    Name(String),
+}
+
+/// What an incoming OSC message asks for. Addresses are relative to the
+/// OSC prefix (`/audio` by default):
+///
+/// - `/view i|s`, `/preset i|s`: switch by 1-based number or name
+/// - `/action s`: anything a key can do, by its keymap name
+/// - `/param/<section>/<key> v`: one preset value, e.g. `/param/radial/fade_alpha 0.1`
+/// - `/midi/learn s`: bind the next MIDI knob moved to a preset value, e.g. `radial/wave_gain`
+/// - `/gain f`
+/// - `/transport/pause [i]` (no argument toggles), `/transport/play`, `/transport/seek f`
+/// - `/query [s]`: reply with current values, optionally only under an address
+#[derive(Clone, Debug, PartialEq)]
+pub enum Command {
+    View(Target),
+    Preset(Target),
+    Action(Action),
+    Param(String, Value),
+    Learn(String),
+    Gain(f32),
+    Pause(Option<bool>),
+    Seek(f32),
+    Query(Option<String>),
+}
+
+impl Command {
+    /// `None` for messages outside `prefix`, which are someone else's business.
+    pub fn parse(msg: &Message, prefix: &str) -> Option<anyhow::Result<Self>> {
+        let addr = msg.addr.strip_prefix(prefix)?;
+        addr.starts_with('/')
+            .then(|| Self::parse_addr(addr, &msg.args).with_context(|| msg.addr.clone()))
+    }
+
+    fn parse_addr(addr: &str, args: &[Arg]) -> anyhow::Result<Self> {
+        // NaN would slip through every clamp downstream
+        let number = || {
+            args.first()
+                .and_then(Arg::as_f32)
+                .filter(|v| v.is_finite())
+                .context("expects a number")
+        };
+        let target = || match args.first() {
+            Some(Arg::Str(name)) => Ok(Target::Name(name.clone())),
+            Some(arg) => match arg.as_f32() {
+                Some(n) if n >= 1.0 => Ok(Target::Index(n.round() as usize - 1)),
+                _ => bail!("numbers start at 1"),
+            },
+            None => bail!("expects a number or a name"),
+        };
+        if let Some(path) = addr.strip_prefix("/param/") {
+            let value = match args.first() {
+                Some(Arg::Int(v)) => (*v).into(),
+                Some(Arg::Float(v)) => (*v).into(),
+                Some(Arg::Str(s)) => s.as_str().into(),
+                None => bail!("expects a value"),
+            };
+            return Ok(Self::Param(path.to_string(), value));
+        }
+        Ok(match addr {
+            "/view" => Self::View(target()?),
+            "/preset" => Self::Preset(target()?),
+            "/action" => match args.first() {
+                Some(Arg::Str(name)) => Self::Action(name.parse()?),
+                _ => bail!("expects an action name"),
+            },
+            "/midi/learn" => match args.first() {
+                Some(Arg::Str(path)) => Self::Learn(path.trim_matches('/').to_string()),
+                _ => bail!("expects a preset value path"),
+            },
+            "/gain" => Self::Gain(number()?),
+            "/transport/pause" => Self::Pause(args.first().and_then(Arg::as_f32).map(|v| v != 0.0)),
+            "/transport/play" => Self::Pause(Some(false)),
+            "/transport/seek" => Self::Seek(number()?),
+            "/query" => Self::Query(match args.first() {
+                Some(Arg::Str(s)) => Some(s.clone()),
+                _ => None,
+            }),
+            _ => bail!("unknown address"),
+        })
+    }
+}
+
+/// A preset value as an OSC argument, for query replies.
+pub fn value_arg(value: &Value) -> Option<Arg> {
+    match value {
+        Value::Bool(b) => Some(Arg::Int(*b as i32)),
+        Value::Number(n) if n.is_f64() => Some(Arg::Float(n.as_f64()? as f32)),
+        Value::Number(n) => Some(Arg::Int(n.as_i64()? as i32)),
+        Value::String(s) => Some(Arg::Str(s.clone())),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(addr: &str, args: Vec<Arg>) -> Option<anyhow::Result<Command>> {
+        Command::parse(&Message::new(addr, args), "/audio")
+    }
+
+    #[test]
+    fn maps_addresses_to_commands() {
+        let ok = |addr: &str, args: Vec<Arg>| parse(addr, args).unwrap().unwrap();
+        assert_eq!(
+            ok("/audio/view", vec![Arg::Float(2.0)]),
+            Command::View(Target::Index(1))
+        );
+        assert_eq!(
+            ok("/audio/preset", vec![Arg::Str("neon".into())]),
+            Command::Preset(Target::Name("neon".into()))
+        );
+        assert_eq!(
+            ok("/audio/action", vec![Arg::Str("tap-tempo".into())]),
+            Command::Action(Action::TapTempo)
+        );
+        assert_eq!(
+            ok("/audio/param/radial/fade_alpha", vec![Arg::Float(0.5)]),
+            Command::Param("radial/fade_alpha".into(), 0.5.into())
+        );
+        assert_eq!(
+            ok(
+                "/audio/midi/learn",
+                vec![Arg::Str("radial/wave_gain".into())]
+            ),
+            Command::Learn("radial/wave_gain".into())
+        );
+        assert_eq!(ok("/audio/transport/pause", vec![]), Command::Pause(None));
+        assert_eq!(
+            ok("/audio/transport/pause", vec![Arg::Int(1)]),
+            Command::Pause(Some(true))
+        );
+        assert_eq!(ok("/audio/query", vec![]), Command::Query(None));
+    }
+
+    #[test]
+    fn ignores_other_prefixes_and_rejects_nonsense() {
+        assert!(parse("/other/view", vec![Arg::Int(1)]).is_none());
+        assert!(parse("/audiox/view", vec![Arg::Int(1)]).is_none());
+        assert!(parse("/audio/view", vec![Arg::Int(0)]).unwrap().is_err());
+        assert!(parse("/audio/seek", vec![Arg::Int(1)]).unwrap().is_err());
+        assert!(
+            parse("/audio/action", vec![Arg::Str("jump".into())])
+                .unwrap()
+                .is_err()
+        );
+        assert!(parse("/audio/transport/seek", vec![]).unwrap().is_err());
+        assert!(
+            parse("/audio/gain", vec![Arg::Float(f32::NAN)])
+                .unwrap()
+                .is_err()
+        );
+        assert!(
+            parse("/audio/gain", vec![Arg::Float(f32::INFINITY)])
+                .unwrap()
+                .is_err()
+        );
+    }
+}
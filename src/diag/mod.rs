@@ -0,0 +1,199 @@
+use crate::audio::BufferHealth;
+use crate::canvas::{Align, Canvas, rgba};
+use anyhow::Context;
+use nannou::glam::vec2;
+use serde::Serialize;
+use std::cell::Cell;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+
+/// Average and worst case of one timing over the last reporting period.
+#[derive(Clone, Copy, Debug, Default, Serialize)]
+pub struct Timing {
+    pub avg_ms: f32,
+    pub max_ms: f32,
+}
+
+#[derive(Default)]
+struct Accumulator {
+    sum: f32,
+    max: f32,
+    count: u32,
+}
+
+impl Accumulator {
+    fn add(&mut self, secs: f32) {
+        self.sum += secs;
+        self.max = self.max.max(secs);
+        self.count += 1;
+    }
+
+    /// The period's timing in milliseconds, starting a new period.
+    fn take(&mut self) -> Timing {
+        let t = Timing {
+            avg_ms: self.sum / self.count.max(1) as f32 * 1000.0,
+            max_ms: self.max * 1000.0,
+        };
+        *self = Self::default();
+        t
+    }
+}
+
+/// One reporting period, as shown on screen and written to the log.
+#[derive(Clone, Copy, Debug, Default, Serialize)]
+pub struct Report {
+    /// seconds since startup
+    pub t: f32,
+    pub fps: f32,
+    pub frame: Timing,
+    pub analysis: Timing,
+    pub render: Timing,
+    /// estimated time from a sample being heard (or captured) to it being on screen
+    pub latency_ms: f32,
+    /// `None` for sources without ring buffers (WAV playback)
+    pub buffers: Option<BufferHealth>,
+}
+
+/// Frame, analysis and render timings plus buffer health, summarized once a
+/// second for the overlay and, optionally, a JSON-lines log.
+pub struct Diagnostics {
+    pub visible: bool,
+    report: Report,
+    frame: Accumulator,
+    analysis: Accumulator,
+    render: Accumulator,
+    /// written by `view`, which only gets `&Model`
+    pub last_render: Cell<f32>,
+    elapsed: f32,
+    period: f32,
+    log: Option<Box<dyn Write>>,
+}
+
+impl Diagnostics {
+    const PERIOD: f32 = 1.0;
+
+    pub fn new() -> Self {
+        Self {
+            visible: false,
+            report: Report::default(),
+            frame: Accumulator::default(),
+            analysis: Accumulator::default(),
+            render: Accumulator::default(),
+            last_render: Cell::new(0.0),
+            elapsed: 0.0,
+            period: 0.0,
+            log: None,
+        }
+    }
+
+    /// Writes one JSON object per period to `path`, or to stderr for `-`.
+    pub fn log_to(&mut self, path: &Path) -> anyhow::Result<()> {
+        self.log = Some(if path == Path::new("-") {
+            Box::new(std::io::stderr())
+        } else {
+            let file = File::create(path)
+                .with_context(|| format!("Failed to create {}", path.display()))?;
+            Box::new(BufWriter::new(file))
+        });
+        Ok(())
+    }
+
+    /// Records one frame. `window_lag` is how far the centre of the analysis
+    /// window trails the newest audio, in seconds.
+    pub fn frame(&mut self, dt: f32, analysis: f32, window_lag: f32, health: Option<BufferHealth>) {
+        self.frame.add(dt);
+        self.analysis.add(analysis);
+        self.render.add(self.last_render.get());
+        self.elapsed += dt;
+        self.period += dt;
+        if self.period < Self::PERIOD {
+            return;
+        }
+
+        let frames = self.frame.count;
+        let frame = self.frame.take();
+        // the frame drawn now reaches the screen about one frame later;
+        // output and capture device buffers aren't visible from here
+        let latency_ms = window_lag * 1000.0 + frame.avg_ms;
+        self.report = Report {
+            t: self.elapsed,
+            fps: frames as f32 / self.period,
+            frame,
+            analysis: self.analysis.take(),
+            render: self.render.take(),
+            latency_ms,
+            buffers: health,
+        };
+        self.period = 0.0;
+
+        if let Some(log) = &mut self.log {
+            let line = serde_json::to_string(&self.report).expect("report serializes");
+            if let Err(e) = writeln!(log, "{line}").and_then(|_| log.flush()) {
+                eprintln!("[diag] log write failed, logging stopped: {e}");
+                self.log = None;
+            }
+        }
+    }
+
+    pub fn draw(&self, canvas: &mut dyn Canvas) {
+        if !self.visible {
+            return;
+        }
+        let r = &self.report;
+        let timing = |t: Timing| format!("{:.2} ms  (max {:.2})", t.avg_ms, t.max_ms);
+        // (label, value, worth a warning)
+        let mut rows = vec![
+            ("fps", format!("{:.1}", r.fps), false),
+            ("frame", timing(r.frame), false),
+            ("analysis", timing(r.analysis), false),
+            ("render", timing(r.render), false),
+            ("latency", format!("~{:.0} ms", r.latency_ms), false),
+        ];
+        match &r.buffers {
+            Some(b) => {
+                rows.push((
+                    "ring",
+                    format!("{:.1}%  ({} waiting)", b.fill * 100.0, b.backlog),
+                    b.fill > 0.9,
+                ));
+                if let Some(fill) = b.playback_fill {
+                    rows.push(("playback", format!("{:.1}%", fill * 100.0), false));
+                }
+                rows.push(("dropped", b.dropped.to_string(), b.dropped > 0));
+                rows.push((
+                    "underruns",
+                    format!("{}  ({} frames)", b.underruns, b.underrun_frames),
+                    b.underruns > 0,
+                ));
+            }
+            None => rows.push(("ring", "none (file playback)".into(), false)),
+        }
+
+        let win = canvas.bounds();
+        let line_h = 15.0;
+        let (w, h) = (240.0, rows.len() as f32 * line_h + 12.0);
+        let (left, top) = (win.right() - 12.0 - w, win.top() - 12.0);
+        canvas.rect(
+            vec2(left + w * 0.5, top - h * 0.5),
+            vec2(w, h),
+            rgba(0.0, 0.0, 0.0, 0.75),
+        );
+        for (i, (label, value, warn)) in rows.iter().enumerate() {
+            let y = top - 6.0 - line_h * (i as f32 + 0.5);
+            let color = if *warn {
+                rgba(1.0, 0.6, 0.3, 1.0)
+            } else {
+                rgba(0.6, 1.0, 0.7, 1.0)
+            };
+            canvas.text(
+                label,
+                vec2(left + 10.0, y),
+                12,
+                Align::Left,
+                rgba(0.7, 0.7, 0.7, 1.0),
+            );
+            canvas.text(value, vec2(left + 85.0, y), 12, Align::Left, color);
+        }
+    }
+}
@@ -0,0 +1,551 @@
+//! Lighting output: band levels, bass envelopes, palette colours and beat
+//! strobes mapped onto DMX channels, sent as Art-Net or sACN.
+
+mod packet;
+
+use crate::analysis::{AnalysisFrame, LevelMeter, Source};
+use crate::pace::Pacer;
+use crate::preset::Preset;
+use crate::visual::palette::{Color, ColorParams, Palette};
+use anyhow::{Context, bail, ensure};
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::collections::hash_map::RandomState;
+use std::hash::{BuildHasher, Hasher};
+use std::net::{IpAddr, Ipv6Addr, SocketAddr, ToSocketAddrs, UdpSocket};
+use std::path::Path;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Protocol {
+    ArtNet,
+    Sacn,
+}
+
+/// A DMX mapping file: where to send and which channels follow what.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct DmxConfig {
+    pub protocol: Protocol,
+    /// `host` or `host:port`. Art-Net needs one (a node, or a broadcast
+    /// address like 2.255.255.255); sACN multicasts each universe without one.
+    pub target: Option<String>,
+    /// Packets per second per universe; DMX itself tops out around 44
+    pub rate: f32,
+    /// How long an onset keeps strobes and `onset` channels lit
+    pub strobe_ms: f32,
+    /// Sender name shown by sACN receivers
+    pub source_name: String,
+    pub fixtures: Vec<Fixture>,
+    pub channels: Vec<Channel>,
+}
+
+impl Default for DmxConfig {
+    fn default() -> Self {
+        Self {
+            protocol: Protocol::ArtNet,
+            target: None,
+            rate: 40.0,
+            strobe_ms: 50.0,
+            source_name: "audio_visualizer".into(),
+            fixtures: Vec::new(),
+            channels: Vec::new(),
+        }
+    }
+}
+
+/// Channel layouts of common pars, in patch order.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Profile {
+    Dimmer,
+    Rgb,
+    Rgbw,
+    DimmerRgb,
+    DimmerRgbw,
+}
+
+impl Profile {
+    fn footprint(self) -> usize {
+        match self {
+            Self::Dimmer => 1,
+            Self::Rgb => 3,
+            Self::Rgbw | Self::DimmerRgb => 4,
+            Self::DimmerRgbw => 5,
+        }
+    }
+}
+
+/// `count` identical fixtures patched back to back from `address`.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Fixture {
+    pub profile: Profile,
+    pub universe: u16,
+    /// First channel, 1-based
+    pub address: usize,
+    pub count: usize,
+    /// Brightness. With several fixtures, a band range is split between
+    /// them, low to high.
+    pub intensity: Source,
+    /// A fixed colour; without one, fixtures take theirs from the current
+    /// preset's radial palette, spread across it like the bars of the ring
+    pub color: Option<Color>,
+    /// Flash full white on every onset
+    pub strobe: bool,
+    pub gain: f32,
+}
+
+impl Default for Fixture {
+    fn default() -> Self {
+        Self {
+            profile: Profile::Rgb,
+            universe: 1,
+            address: 1,
+            count: 1,
+            intensity: Source::Bands(None),
+            color: None,
+            strobe: false,
+            gain: 1.0,
+        }
+    }
+}
+
+/// One raw channel, for dimmers, strobes, fog and the like.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Channel {
+    pub universe: u16,
+    /// 1-based
+    pub channel: usize,
+    pub value: Source,
+    #[serde(default = "unity")]
+    pub gain: f32,
+}
+
+fn unity() -> f32 {
+    1.0
+}
+
+impl DmxConfig {
+    fn validate(&self) -> anyhow::Result<()> {
+        ensure!(self.rate > 0.0, "rate must be positive");
+        ensure!(self.strobe_ms >= 0.0, "strobe_ms must not be negative");
+        let universes = match self.protocol {
+            Protocol::ArtNet => 0..=0x7fff,
+            Protocol::Sacn => 1..=63999,
+        };
+        let check = |what: String, universe: u16, first: usize, len: usize| {
+            ensure!(
+                universes.contains(&universe),
+                "{what}: universe {universe} is outside {universes:?}"
+            );
+            let last = first.saturating_add(len - 1);
+            ensure!(
+                first >= 1 && last <= 512,
+                "{what}: channels {first}–{last} don't fit in 1–512"
+            );
+            Ok(())
+        };
+        for (i, f) in self.fixtures.iter().enumerate() {
+            let what = format!("fixture {}", i + 1);
+            ensure!(f.count >= 1, "{what}: count must be at least 1");
+            let len = f
+                .profile
+                .footprint()
+                .checked_mul(f.count)
+                .with_context(|| format!("{what}: count {} is far too many", f.count))?;
+            check(what.clone(), f.universe, f.address, len)?;
+            if let Some(color) = &f.color {
+                color.to_rgb().context(what)?;
+            }
+        }
+        for (i, c) in self.channels.iter().enumerate() {
+            check(format!("channel {}", i + 1), c.universe, c.channel, 1)?;
+        }
+        Ok(())
+    }
+}
+
+struct Universe {
+    data: [u8; 512],
+    sequence: u8,
+}
+
+/// Sends the mapped channels at a fixed rate, whatever the frame rate.
+pub struct DmxOutput {
+    config: DmxConfig,
+    socket: UdpSocket,
+    /// `None` multicasts sACN per universe
+    dest: Option<SocketAddr>,
+    universes: BTreeMap<u16, Universe>,
+    cid: [u8; 16],
+    palette: Palette,
+    color: ColorParams,
+    time: f32,
+    pacer: Pacer,
+    strobe_left: f32,
+    onset: bool,
+}
+
+impl DmxOutput {
+    pub fn load(path: &Path, preset: &Preset) -> anyhow::Result<Self> {
+        let text = std::fs::read_to_string(path)
+            .with_context(|| format!("Failed to read {}", path.display()))?;
+        let config: DmxConfig = toml::from_str(&text)
+            .with_context(|| format!("Invalid DMX mapping {}", path.display()))?;
+        config
+            .validate()
+            .with_context(|| format!("Invalid DMX mapping {}", path.display()))?;
+        Self::new(config, preset)
+    }
+
+    fn new(config: DmxConfig, preset: &Preset) -> anyhow::Result<Self> {
+        let port = match config.protocol {
+            Protocol::ArtNet => packet::ARTNET_PORT,
+            Protocol::Sacn => packet::SACN_PORT,
+        };
+        let dest = match &config.target {
+            Some(target) => Some(target_addr(target, port)?),
+            None if config.protocol == Protocol::Sacn => None,
+            None => bail!("Art-Net needs a target (a node's address or a broadcast address)"),
+        };
+        let bind: SocketAddr = match dest {
+            Some(dest) if dest.is_ipv6() => (Ipv6Addr::UNSPECIFIED, 0).into(),
+            _ => ([0, 0, 0, 0], 0).into(),
+        };
+        let socket = UdpSocket::bind(bind).context("Failed to open a UDP socket for DMX")?;
+        socket.set_broadcast(true)?;
+        socket.set_nonblocking(true)?;
+
+        let mut universes = BTreeMap::new();
+        let used = config.fixtures.iter().map(|f| f.universe);
+        for universe in used.chain(config.channels.iter().map(|c| c.universe)) {
+            universes.insert(
+                universe,
+                Universe {
+                    data: [0; 512],
+                    sequence: 0,
+                },
+            );
+        }
+        // random per run, as the standard asks of senders without a stored CID
+        let random = || RandomState::new().build_hasher().finish().to_be_bytes();
+        let mut cid = [0u8; 16];
+        cid[..8].copy_from_slice(&random());
+        cid[8..].copy_from_slice(&random());
+
+        match dest {
+            Some(dest) => eprintln!(
+                "[dmx] {:?} to {dest}, {} universes",
+                config.protocol,
+                universes.len()
+            ),
+            None => eprintln!("[dmx] sACN multicast, {} universes", universes.len()),
+        }
+        let mut out = Self {
+            pacer: Pacer::new(config.rate),
+            config,
+            socket,
+            dest,
+            universes,
+            cid,
+            palette: Palette::Rainbow,
+            color: ColorParams::default(),
+            time: 0.0,
+            strobe_left: 0.0,
+            onset: false,
+        };
+        out.apply_preset(preset);
+        Ok(out)
+    }
+
+    /// Fixtures without a fixed colour follow the preset's radial palette.
+    pub fn apply_preset(&mut self, preset: &Preset) {
+        self.color = preset.radial.color.clone();
+        // presets are validated, so their palette builds
+        self.palette = preset
+            .palette(&self.color.palette)
+            .unwrap_or(Palette::Rainbow);
+    }
+
+    /// Called once per analysis frame, `dt` seconds after the previous one.
+    pub fn frame(&mut self, frame: &AnalysisFrame, levels: &LevelMeter, dt: f32) {
+        self.time += dt;
+        self.onset |= frame.onset;
+        self.strobe_left -= dt;
+        if !self.pacer.tick(dt) {
+            return;
+        }
+        if std::mem::take(&mut self.onset) {
+            self.strobe_left = self.config.strobe_ms / 1000.0;
+        }
+        self.fill(frame, levels);
+        self.send();
+    }
+
+    /// While the analysis is frozen: the last levels keep going out at the
+    /// configured rate, since receivers black out a source that goes quiet.
+    pub fn hold(&mut self, dt: f32) {
+        if self.pacer.tick(dt) {
+            self.send();
+        }
+    }
+
+    fn fill(&mut self, frame: &AnalysisFrame, levels: &LevelMeter) {
+        let strobe = self.strobe_left > 0.0;
+        for u in self.universes.values_mut() {
+            u.data = [0; 512];
+        }
+        for f in &self.config.fixtures {
+            let data = &mut self.universes.get_mut(&f.universe).unwrap().data;
+            for k in 0..f.count {
+                let mut level =
+                    (f.intensity.value(frame, levels, strobe, k, f.count) * f.gain).clamp(0.0, 1.0);
+                let mut rgb = match &f.color {
+                    Some(color) => color.to_rgb().unwrap_or([1.0; 3]),
+                    None => {
+                        let t =
+                            self.color
+                                .position(k, f.count, level, frame.bass_smooth, self.time);
+                        self.palette.sample(t)
+                    }
+                };
+                if f.strobe && strobe {
+                    level = 1.0;
+                    rgb = [1.0; 3];
+                }
+                let start = f.address - 1 + k * f.profile.footprint();
+                let byte = |v: f32| (v.clamp(0.0, 1.0) * 255.0).round() as u8;
+                let lit = rgb.map(|c| byte(c * level));
+                let slots = &mut data[start..start + f.profile.footprint()];
+                match f.profile {
+                    Profile::Dimmer => slots[0] = byte(level),
+                    Profile::Rgb => slots.copy_from_slice(&lit),
+                    Profile::Rgbw => slots.copy_from_slice(&rgbw(rgb).map(|c| byte(c * level))),
+                    Profile::DimmerRgb => {
+                        slots[0] = byte(level);
+                        slots[1..].copy_from_slice(&rgb.map(byte));
+                    }
+                    Profile::DimmerRgbw => {
+                        slots[0] = byte(level);
+                        slots[1..].copy_from_slice(&rgbw(rgb).map(byte));
+                    }
+                }
+            }
+        }
+        for c in &self.config.channels {
+            let v = (c.value.value(frame, levels, strobe, 0, 1) * c.gain).clamp(0.0, 1.0);
+            self.universes.get_mut(&c.universe).unwrap().data[c.channel - 1] =
+                (v * 255.0).round() as u8;
+        }
+    }
+
+    fn send(&mut self) {
+        for (&universe, u) in &mut self.universes {
+            // Art-Net reserves sequence 0 for "don't reorder"
+            u.sequence = u.sequence.wrapping_add(1).max(1);
+            let (packet, dest) = match self.config.protocol {
+                Protocol::ArtNet => (
+                    packet::artnet(universe, u.sequence, &u.data),
+                    self.dest.unwrap(),
+                ),
+                Protocol::Sacn => (
+                    packet::sacn(
+                        universe,
+                        u.sequence,
+                        &u.data,
+                        &self.cid,
+                        &self.config.source_name,
+                    ),
+                    self.dest.unwrap_or_else(|| {
+                        (packet::sacn_multicast(universe), packet::SACN_PORT).into()
+                    }),
+                ),
+            };
+            if let Err(e) = self.socket.send_to(&packet, dest)
+                && e.kind() != std::io::ErrorKind::WouldBlock
+            {
+                eprintln!("[dmx] send to {dest} failed: {e}");
+            }
+        }
+    }
+}
+
+/// `target` as an address: `host:port`, or an IPv4 or IPv6 address or a host
+/// name on the protocol's `port`.
+fn target_addr(target: &str, port: u16) -> anyhow::Result<SocketAddr> {
+    if let Ok(addr) = target.parse::<SocketAddr>() {
+        return Ok(addr);
+    }
+    // a bare IPv6 address is all colons, so it can't be told apart by those
+    let bare = target.trim_start_matches('[').trim_end_matches(']');
+    if let Ok(ip) = bare.parse::<IpAddr>() {
+        return Ok(SocketAddr::new(ip, port));
+    }
+    let with_port = if target.contains(':') {
+        target.to_string()
+    } else {
+        format!("{target}:{port}")
+    };
+    with_port
+        .to_socket_addrs()
+        .with_context(|| format!("Can't resolve DMX target '{target}'"))?
+        .next()
+        .with_context(|| format!("No address for DMX target '{target}'"))
+}
+
+/// White taken out of an RGB colour, for fixtures with a separate white LED.
+fn rgbw([r, g, b]: [f32; 3]) -> [f32; 4] {
+    let w = r.min(g).min(b);
+    [r - w, g - w, b - w, w]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    fn capture() -> (UdpSocket, String) {
+        let socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+        socket
+            .set_read_timeout(Some(Duration::from_secs(2)))
+            .unwrap();
+        let addr = socket.local_addr().unwrap().to_string();
+        (socket, addr)
+    }
+
+    fn output(text: &str) -> DmxOutput {
+        let config: DmxConfig = toml::from_str(text).unwrap();
+        config.validate().unwrap();
+        DmxOutput::new(config, &Preset::default()).unwrap()
+    }
+
+    #[test]
+    fn artnet_fixtures_follow_bands_and_strobe_on_onsets() {
+        let (rx, addr) = capture();
+        let mut dmx = output(&format!(
+            r##"
+            protocol = "artnet"
+            target = "{addr}"
+            [[fixtures]]
+            profile = "dimmer"
+            universe = 3
+            address = 10
+            count = 2
+            intensity = "bands:0-4"
+            [[fixtures]]
+            profile = "rgbw"
+            universe = 3
+            address = 20
+            intensity = "bass_fast"
+            color = "#ff8000"
+            strobe = true
+            [[channels]]
+            universe = 3
+            channel = 1
+            value = "0.5"
+            "##
+        ));
+        let mut frame = AnalysisFrame {
+            bands: vec![0.2, 0.2, 1.0, 1.0],
+            bass_fast: 0.5,
+            ..Default::default()
+        };
+        let levels = LevelMeter::new();
+        let mut buf = [0u8; 600];
+
+        dmx.frame(&frame, &levels, 0.01);
+        let n = rx.recv(&mut buf).unwrap();
+        assert_eq!(n, 18 + 512);
+        assert_eq!(&buf[14..16], &[3, 0]);
+        let data = &buf[18..n];
+        assert_eq!(data[0], 128);
+        // two dimmers: the lower and upper half of bands 0–4
+        assert_eq!(&data[9..11], &[51, 255]);
+        // orange at half level, no white in it
+        assert_eq!(&data[19..23], &[128, 64, 0, 0]);
+
+        frame.onset = true;
+        dmx.frame(&frame, &levels, 0.03);
+        let n = rx.recv(&mut buf).unwrap();
+        assert_eq!(&buf[18 + 19..18 + 23], &[0, 0, 0, 255]);
+        assert_eq!(buf[12], 2, "sequence counts up");
+        assert_eq!(n, 530);
+    }
+
+    #[test]
+    fn sacn_to_a_unicast_target() {
+        let (rx, addr) = capture();
+        let mut dmx = output(&format!(
+            r#"
+            protocol = "sacn"
+            target = "{addr}"
+            [[fixtures]]
+            universe = 7
+            address = 1
+            intensity = "1.0"
+            color = [0.0, 1.0, 0.0]
+            "#
+        ));
+        dmx.frame(&AnalysisFrame::default(), &LevelMeter::new(), 0.01);
+        let mut buf = [0u8; 700];
+        let n = rx.recv(&mut buf).unwrap();
+        assert_eq!(n, 638);
+        assert_eq!(&buf[113..115], &[0, 7]);
+        assert_eq!(&buf[126..129], &[0, 255, 0]);
+    }
+
+    #[test]
+    fn held_output_repeats_the_last_levels() {
+        let (rx, addr) = capture();
+        let mut dmx = output(&format!(
+            r#"
+            target = "{addr}"
+            [[fixtures]]
+            intensity = "1.0"
+            color = [1.0, 0.0, 0.0]
+            "#
+        ));
+        let mut first = [0u8; 600];
+        dmx.frame(&AnalysisFrame::default(), &LevelMeter::new(), 0.01);
+        let n = rx.recv(&mut first).unwrap();
+        dmx.hold(0.001); // not due yet
+        dmx.hold(0.05);
+        let mut held = [0u8; 600];
+        assert_eq!(rx.recv(&mut held).unwrap(), n);
+        // only the sequence number moves on
+        assert_eq!(held[12], first[12] + 1);
+        assert_eq!(&held[18..n], &first[18..n]);
+    }
+
+    #[test]
+    fn targets_take_the_protocol_port_unless_given() {
+        let addr = |t: &str| target_addr(t, 6454).unwrap().to_string();
+        assert_eq!(addr("10.0.0.5"), "10.0.0.5:6454");
+        assert_eq!(addr("10.0.0.5:7000"), "10.0.0.5:7000");
+        assert_eq!(addr("fe80::1"), "[fe80::1]:6454");
+        assert_eq!(addr("[fe80::1]"), "[fe80::1]:6454");
+        assert_eq!(addr("[fe80::1]:7000"), "[fe80::1]:7000");
+        assert!(target_addr("10.0.0.5:notaport", 6454).is_err());
+    }
+
+    #[test]
+    fn bad_mappings_are_rejected() {
+        let bad = |text: &str| {
+            toml::from_str::<DmxConfig>(text)
+                .map_err(anyhow::Error::from)
+                .and_then(|c| c.validate())
+                .is_err()
+        };
+        assert!(bad("[[fixtures]]\naddress = 511\n"));
+        assert!(bad("protocol = \"sacn\"\n[[fixtures]]\nuniverse = 0\n"));
+        assert!(bad("[[fixtures]]\nintensity = \"treble\"\n"));
+        assert!(bad("[[fixtures]]\nintensity = \"bands:5-2\"\n"));
+        assert!(bad(
+            "[[channels]]\nuniverse = 1\nchannel = 0\nvalue = \"onset\"\n"
+        ));
+        assert!(bad(&format!("[[fixtures]]\ncount = {}\n", usize::MAX / 2)));
+        assert!(!bad("[[fixtures]]\naddress = 510\n"));
+    }
+}
@@ -0,0 +1,107 @@
+//! DMX-over-IP packet encoders: Art-Net `ArtDmx` and sACN (E1.31) data packets.
+
+pub const ARTNET_PORT: u16 = 6454;
+pub const SACN_PORT: u16 = 5568;
+
+/// One universe for Art-Net. `universe` is the 15-bit port address
+/// (net, sub-net and universe); `sequence` 0 tells receivers not to reorder.
+pub fn artnet(universe: u16, sequence: u8, data: &[u8]) -> Vec<u8> {
+    // the length must be even, between 2 and 512
+    let len = (data.len().clamp(2, 512) + 1) & !1;
+    let mut out = Vec::with_capacity(18 + len);
+    out.extend(b"Art-Net\0");
+    out.extend(0x5000u16.to_le_bytes()); // OpDmx
+    out.extend(14u16.to_be_bytes()); // protocol version
+    out.push(sequence);
+    out.push(0); // physical port
+    out.extend(universe.to_le_bytes()); // SubUni, then Net
+    out.extend((len as u16).to_be_bytes());
+    out.extend(data.iter().take(len));
+    out.resize(18 + len, 0);
+    out
+}
+
+/// One universe for sACN, at the default priority of 100. `cid` identifies
+/// this sender and should stay the same for as long as it runs.
+pub fn sacn(universe: u16, sequence: u8, data: &[u8], cid: &[u8; 16], source: &str) -> Vec<u8> {
+    let slots = data.len().min(512);
+    let total = 126 + slots;
+    // each layer's length counts from its own flags field to the end
+    let flags_len = |from: usize| (0x7000 | (total - from) as u16).to_be_bytes();
+
+    let mut out = Vec::with_capacity(total);
+    // root layer
+    out.extend(0x0010u16.to_be_bytes()); // preamble size
+    out.extend(0u16.to_be_bytes()); // postamble size
+    out.extend(b"ASC-E1.17\0\0\0");
+    out.extend(flags_len(16));
+    out.extend(4u32.to_be_bytes()); // VECTOR_ROOT_E131_DATA
+    out.extend(cid);
+    // framing layer
+    out.extend(flags_len(38));
+    out.extend(2u32.to_be_bytes()); // VECTOR_E131_DATA_PACKET
+    let mut name = [0u8; 64];
+    let bytes = source.as_bytes();
+    let n = bytes.len().min(63);
+    name[..n].copy_from_slice(&bytes[..n]);
+    out.extend(name);
+    out.push(100); // priority
+    out.extend(0u16.to_be_bytes()); // no synchronization
+    out.push(sequence);
+    out.push(0); // options
+    out.extend(universe.to_be_bytes());
+    // DMP layer
+    out.extend(flags_len(115));
+    out.push(0x02); // VECTOR_DMP_SET_PROPERTY
+    out.push(0xa1); // address and data type
+    out.extend(0u16.to_be_bytes()); // first property address
+    out.extend(1u16.to_be_bytes()); // address increment
+    out.extend(((slots + 1) as u16).to_be_bytes());
+    out.push(0); // DMX start code
+    out.extend(&data[..slots]);
+    out
+}
+
+/// The multicast group receivers of an sACN universe listen on.
+pub fn sacn_multicast(universe: u16) -> [u8; 4] {
+    let [hi, lo] = universe.to_be_bytes();
+    [239, 255, hi, lo]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn artnet_layout() {
+        let p = artnet(0x0123, 7, &[1, 2, 3]);
+        assert_eq!(&p[..8], b"Art-Net\0");
+        assert_eq!(&p[8..10], &[0x00, 0x50]);
+        assert_eq!(&p[10..12], &[0, 14]);
+        assert_eq!(p[12], 7);
+        assert_eq!(&p[14..16], &[0x23, 0x01]);
+        // odd lengths are padded
+        assert_eq!(&p[16..18], &[0, 4]);
+        assert_eq!(&p[18..], &[1, 2, 3, 0]);
+    }
+
+    #[test]
+    fn sacn_layout() {
+        let data = [255u8; 512];
+        let p = sacn(1, 9, &data, &[0xab; 16], "test");
+        assert_eq!(p.len(), 638);
+        assert_eq!(&p[4..16], b"ASC-E1.17\0\0\0");
+        assert_eq!(&p[16..18], &(0x7000u16 | 622).to_be_bytes());
+        assert_eq!(&p[22..38], &[0xab; 16]);
+        assert_eq!(&p[38..40], &(0x7000u16 | 600).to_be_bytes());
+        assert_eq!(&p[44..48], b"test");
+        assert_eq!(p[108], 100);
+        assert_eq!(p[111], 9);
+        assert_eq!(&p[113..115], &[0, 1]);
+        assert_eq!(&p[115..117], &(0x7000u16 | 523).to_be_bytes());
+        assert_eq!(&p[123..125], &513u16.to_be_bytes());
+        assert_eq!(p[125], 0);
+        assert!(p[126..].iter().all(|&v| v == 255));
+        assert_eq!(sacn_multicast(0x0102), [239, 255, 1, 2]);
+    }
+}
@@ -0,0 +1,281 @@
+use crate::analysis::LevelMeter;
+use crate::analysis::level::FLOOR_DB;
+use crate::audio::TrackInfo;
+use crate::canvas::{Align, Canvas, Rgba, rgba};
+use anyhow::Context;
+use nannou::glam::vec2;
+use nannou::text::Font;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+
+/// Which corner of the window the HUD sits in.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Corner {
+    #[default]
+    TopLeft,
+    TopRight,
+    BottomLeft,
+    BottomRight,
+}
+
+/// The `[hud]` section of a preset.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct HudParams {
+    pub visible: bool, // shown at startup; H toggles it
+    pub position: Corner,
+    pub opacity: f32,
+    /// A .ttf/.otf file; relative paths start at the preset file
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub font: Option<PathBuf>,
+    pub font_size: u32,
+}
+
+impl Default for HudParams {
+    fn default() -> Self {
+        Self {
+            visible: false,
+            position: Corner::TopLeft,
+            opacity: 0.85,
+            font: None,
+            font_size: 14,
+        }
+    }
+}
+
+pub fn load_font(path: &Path) -> anyhow::Result<Font> {
+    nannou::text::font::from_file(path)
+        .with_context(|| format!("Failed to load font {}", path.display()))
+}
+
+/// Everything the HUD shows, gathered fresh each frame.
+pub struct HudInfo<'a> {
+    pub track: &'a TrackInfo,
+    /// Seconds into the track, or `None` for live input
+    pub elapsed: Option<f32>,
+    pub bpm: Option<f32>,
+    /// `bpm` came from tap tempo rather than the estimator
+    pub tapped: bool,
+    /// The part of the song we're in, once known
+    pub section: Option<&'static str>,
+    pub gain: f32,
+    pub frozen: bool,
+    /// Preset value waiting for a MIDI knob
+    pub learning: Option<&'a str>,
+    pub levels: &'a LevelMeter,
+    pub view: &'a str,
+    pub preset: &'a str,
+}
+
+/// Overlay with what's playing, where we are in it, tempo and levels.
+pub struct Hud {
+    pub visible: bool,
+    params: HudParams,
+    font: Option<Font>, // `None` uses the canvas default
+}
+
+enum Row {
+    Text(String, u32, Rgba),
+    Progress(f32),
+    Meter(&'static str, f32, Option<f32>), // label, level and held peak in dBFS
+}
+
+impl Row {
+    fn height(&self, size: f32) -> f32 {
+        match self {
+            Self::Text(_, s, _) => *s as f32 * 1.5,
+            Self::Progress(_) => size * 0.8,
+            Self::Meter(..) => size * 1.4,
+        }
+    }
+}
+
+impl Hud {
+    pub fn new(params: &HudParams) -> Self {
+        let mut hud = Self {
+            visible: params.visible,
+            params: HudParams::default(),
+            font: None,
+        };
+        hud.apply(params);
+        hud
+    }
+
+    /// Takes new settings, reloading the font only when its path changed.
+    pub fn apply(&mut self, params: &HudParams) {
+        if params.font != self.params.font {
+            self.font = params.font.as_deref().and_then(|path| {
+                load_font(path)
+                    .inspect_err(|e| eprintln!("[hud] {e:#}"))
+                    .ok()
+            });
+        }
+        self.params = params.clone();
+    }
+
+    pub fn draw(&self, canvas: &mut dyn Canvas, info: &HudInfo) {
+        if !self.visible {
+            return;
+        }
+        let size = self.params.font_size;
+        let s = size as f32;
+        let rows = rows(info, size);
+        let pad = s * 0.8;
+        let width = s * 22.0;
+        let height = rows.iter().map(|r| r.height(s)).sum::<f32>() + pad * 2.0;
+
+        let win = canvas.bounds();
+        let margin = 12.0;
+        let left = match self.params.position {
+            Corner::TopLeft | Corner::BottomLeft => win.left() + margin,
+            Corner::TopRight | Corner::BottomRight => win.right() - margin - width,
+        };
+        let top = match self.params.position {
+            Corner::TopLeft | Corner::TopRight => win.top() - margin,
+            Corner::BottomLeft | Corner::BottomRight => win.bottom() + margin + height,
+        };
+
+        canvas.set_opacity(self.params.opacity);
+        canvas.set_font(self.font.clone());
+        canvas.rect(
+            vec2(left + width * 0.5, top - height * 0.5),
+            vec2(width, height),
+            rgba(0.0, 0.0, 0.0, 0.6),
+        );
+
+        let (x0, x1) = (left + pad, left + width - pad);
+        let mut y = top - pad;
+        for row in &rows {
+            let h = row.height(s);
+            let mid = y - h * 0.5;
+            match row {
+                Row::Text(text, size, c) => {
+                    canvas.text(text, vec2(x0, mid), *size, Align::Left, *c);
+                }
+                Row::Progress(t) => {
+                    let w = x1 - x0;
+                    bar(canvas, x0, w, mid, s * 0.25, 1.0, rgba(1.0, 1.0, 1.0, 0.15));
+                    bar(canvas, x0, w, mid, s * 0.25, *t, rgba(1.0, 1.0, 1.0, 0.8));
+                }
+                Row::Meter(label, db, hold) => {
+                    let grey = rgba(0.7, 0.7, 0.7, 1.0);
+                    canvas.text(label, vec2(x0, mid), size - 2, Align::Left, grey);
+                    let bx = x0 + s * 3.5;
+                    let bw = x1 - s * 4.5 - bx;
+                    let level = |db: f32| ((db - FLOOR_DB) / -FLOOR_DB).clamp(0.0, 1.0);
+                    bar(canvas, bx, bw, mid, s * 0.5, 1.0, rgba(1.0, 1.0, 1.0, 0.12));
+                    bar(canvas, bx, bw, mid, s * 0.5, level(*db), meter_color(*db));
+                    if let Some(hold) = *hold {
+                        let hx = bx + bw * level(hold);
+                        canvas.rect(vec2(hx, mid), vec2(2.0, s * 0.7), meter_color(hold));
+                    }
+                    canvas.text(
+                        &format!("{db:.0} dB"),
+                        vec2(x1, mid),
+                        size - 2,
+                        Align::Right,
+                        grey,
+                    );
+                }
+            }
+            y -= h;
+        }
+        canvas.set_font(None);
+        canvas.set_opacity(1.0);
+    }
+}
+
+fn rows(info: &HudInfo, size: u32) -> Vec<Row> {
+    let white = rgba(1.0, 1.0, 1.0, 1.0);
+    let grey = rgba(0.7, 0.7, 0.7, 1.0);
+    // roughly what fits across the panel, at an average glyph width of half the size
+    let panel = (size as f32 * (22.0 - 1.6)) as usize;
+    let chars = |glyph: u32| panel * 2 / glyph.max(1) as usize;
+
+    let mut rows = Vec::new();
+    let title = info.track.title.as_deref().unwrap_or("—");
+    rows.push(Row::Text(
+        ellipsize(title, chars(size + 3)),
+        size + 3,
+        white,
+    ));
+    if let Some(artist) = &info.track.artist {
+        rows.push(Row::Text(ellipsize(artist, chars(size)), size, grey));
+    }
+
+    let total = info.track.duration;
+    let time = match (info.elapsed, total) {
+        (Some(t), Some(total)) => format!("{} / {}", clock(t), clock(total)),
+        (Some(t), None) => clock(t),
+        (None, _) => "live".into(),
+    };
+    let bpm = match info.bpm {
+        Some(bpm) if info.tapped => format!("{bpm:.0} BPM (tap)"),
+        Some(bpm) => format!("{bpm:.0} BPM"),
+        None => "— BPM".into(),
+    };
+    let mut line = format!("{time}    {bpm}");
+    if let Some(section) = info.section {
+        line += &format!("    {section}");
+    }
+    rows.push(Row::Text(line, size, white));
+    if let (Some(t), Some(total)) = (info.elapsed, total) {
+        rows.push(Row::Progress((t / total.max(0.001)).clamp(0.0, 1.0)));
+    }
+
+    let levels = info.levels;
+    rows.push(Row::Meter("RMS", levels.rms_db, None));
+    rows.push(Row::Meter("PEAK", levels.peak_db, Some(levels.hold_db)));
+    let mut footer = format!("{} · {}", info.view, info.preset);
+    if info.gain != 1.0 {
+        footer += &format!(" · gain ×{:.2}", info.gain);
+    }
+    if info.frozen {
+        footer += " · frozen";
+    }
+    if let Some(param) = info.learning {
+        footer += &format!(" · learn {param}");
+    }
+    rows.push(Row::Text(footer, size - 2, grey));
+    rows
+}
+
+/// Horizontal bar from `x` covering `fill` of `width`, centred on `y`.
+fn bar(canvas: &mut dyn Canvas, x: f32, width: f32, y: f32, h: f32, fill: f32, c: Rgba) {
+    let w = width * fill;
+    if w > 0.0 {
+        canvas.rect(vec2(x + w * 0.5, y), vec2(w, h), c);
+    }
+}
+
+/// Green in the comfortable range, yellow when hot, red near clipping.
+fn meter_color(db: f32) -> Rgba {
+    if db > -3.0 {
+        rgba(1.0, 0.25, 0.2, 1.0)
+    } else if db > -12.0 {
+        rgba(1.0, 0.85, 0.2, 1.0)
+    } else {
+        rgba(0.3, 0.9, 0.4, 1.0)
+    }
+}
+
+/// `m:ss`, or `h:mm:ss` past an hour.
+fn clock(secs: f32) -> String {
+    let s = secs.max(0.0) as u32;
+    let (h, m, s) = (s / 3600, s / 60 % 60, s % 60);
+    if h > 0 {
+        format!("{h}:{m:02}:{s:02}")
+    } else {
+        format!("{m}:{s:02}")
+    }
+}
+
+fn ellipsize(text: &str, max: usize) -> String {
+    if text.chars().count() <= max {
+        text.to_string()
+    } else {
+        let cut: String = text.chars().take(max.saturating_sub(1)).collect();
+        format!("{}…", cut.trim_end())
+    }
+}
@@ -1,12 +1,48 @@
 mod analysis;
 mod audio;
+mod canvas;
+mod control;
+mod diag;
+mod dmx;
+mod hud;
+mod midi;
+mod osc;
+mod pace;
+mod preset;
+mod record;
+mod render;
+#[cfg(test)]
+mod testutil;
 mod visual;
+mod ws;
 
-use analysis::Analyzer;
-use audio::{AudioData, AudioPlayer, MicCapture, UrlStream};
+use analysis::{
+    AnalysisFrame, Analyzer, LevelMeter, LookAhead, SectionInfo, SectionKind, Segmenter, TapTempo,
+    TempoEstimator, TrackAnalysis, TriggerMode,
+};
+use anyhow::Context;
+use audio::{AudioData, AudioPlayer, BufferHealth, MicCapture, TrackInfo, UrlStream};
+use canvas::{Align, Canvas, WindowCanvas, rgba};
 use clap::{Parser, Subcommand};
+use control::{Action, Command, Keymap, Target};
+use diag::Diagnostics;
+use dmx::DmxOutput;
+use hud::{Hud, HudInfo};
+use midi::{Change, Controller, MidiIn, MidiOutput};
 use nannou::prelude::*;
-use visual::RadialVisualizer;
+use osc::{Arg, Message, OscReceiver, OscSender, OscTarget};
+use preset::{Preset, PresetLibrary, PresetWatcher, SectionLooks};
+use record::{AnalyzeOptions, Record, Recorder, Recording, Replay};
+use render::RenderOptions;
+use serde_json::Value;
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
+use std::time::Instant;
+use visual::{
+    BarsVisualizer, Colormap, OscilloscopeVisualizer, ParticleVisualizer, RadialVisualizer,
+    Registry, Scroll, Snapshot, SpectrogramVisualizer, VectorscopeVisualizer,
+};
+use ws::WsServer;
 
 const FFT_SIZE: usize = 2048;
 const BARS: usize = 120;
@@ -17,9 +53,112 @@ const DEFAULT_WAV: &str = "assets/song.wav";
 struct Cli {
     #[command(subcommand)]
     mode: Option<Mode>,
+
+    /// Visualizer shown at startup
+    #[arg(long, value_enum, default_value_t = View::Radial, global = true)]
+    view: View,
+
+    /// Preset to start with: a name from `presets`, or a path to a .toml/.json file
+    #[arg(long, default_value = "default", global = true)]
+    preset: String,
+
+    /// Extra directory to load presets from, besides ./presets and
+    /// ~/.config/audio_visualizer/presets
+    #[arg(long, global = true)]
+    preset_dir: Option<PathBuf>,
+
+    // The options below override the starting preset.
+    /// Seconds to crossfade when switching visualizers (0 = hard cut)
+    #[arg(long, global = true)]
+    crossfade: Option<f32>,
+
+    /// Number of past spectra kept for the spectrogram
+    #[arg(long, global = true)]
+    history: Option<usize>,
+
+    /// Oscilloscope trigger mode (T cycles it at runtime)
+    #[arg(long, value_enum, global = true)]
+    trigger: Option<TriggerMode>,
+
+    /// Spectrogram colormap
+    #[arg(long, value_enum, global = true)]
+    colormap: Option<Colormap>,
+
+    /// Spectrogram scroll direction
+    #[arg(long, value_enum, global = true)]
+    scroll: Option<Scroll>,
+
+    /// Stream analysis frames to WebSocket clients on this port (or `host:port`),
+    /// with a demo overlay page at the same address
+    #[arg(long, global = true)]
+    ws: Option<String>,
+
+    /// Most WebSocket frames per second (0 = every analysis frame)
+    #[arg(long, default_value_t = 30.0, global = true)]
+    ws_rate: f32,
+
+    /// Send WebSocket frames as packed little-endian floats instead of JSON
+    #[arg(long, global = true)]
+    ws_binary: bool,
+
+    /// Drive lights over Art-Net or sACN, as described by this mapping file
+    #[arg(long, global = true)]
+    dmx: Option<PathBuf>,
+
+    /// Send MIDI CCs, notes and clock as set in the preset's `[midi]` section,
+    /// from a virtual port, or to the first output whose name contains PORT
+    #[arg(long, value_name = "PORT", global = true)]
+    midi: Option<Option<String>>,
+
+    /// Take knob moves from a MIDI controller for the preset's `[midi_in]`
+    /// bindings (M learns new ones), on a virtual port or from the first
+    /// input whose name contains PORT
+    #[arg(long, value_name = "PORT", global = true)]
+    midi_in: Option<Option<String>>,
+
+    /// Scale WAV files so their peak reaches full scale, for quiet masters
+    #[arg(long, global = true)]
+    normalize: bool,
+
+    /// Record every analysis frame to this file: .csv, .jsonl or .bin
+    #[arg(long, global = true)]
+    record: Option<PathBuf>,
+
+    /// Start fullscreen (F toggles it at runtime)
+    #[arg(long, global = true)]
+    fullscreen: bool,
+
+    /// Key bindings to use on top of the defaults (`key = "action"` lines)
+    #[arg(long, global = true)]
+    keymap: Option<PathBuf>,
+
+    /// Write diagnostics (timings, buffer health, latency) as JSON lines,
+    /// once a second, to this file or `-` for stderr
+    #[arg(long, global = true)]
+    diag_log: Option<PathBuf>,
+
+    /// Send analysis data as OSC over UDP to this `host:port`
+    #[arg(long, global = true)]
+    osc: Option<String>,
+
+    /// Take remote control commands over OSC on this port (or `host:port`)
+    #[arg(long, global = true)]
+    osc_listen: Option<String>,
+
+    /// Address prefix for OSC messages, sent and received
+    #[arg(long, default_value = "/audio", global = true, value_parser = osc::parse_prefix)]
+    osc_prefix: String,
+
+    /// Most OSC packets per second (0 = every frame)
+    #[arg(long, default_value_t = 60.0, global = true)]
+    osc_rate: f32,
+
+    /// Wrap each frame's OSC messages in one bundle
+    #[arg(long, global = true)]
+    osc_bundle: bool,
 }
 
-#[derive(Subcommand)]
+#[derive(Clone, Subcommand)]
 enum Mode {
     /// Visualize microphone input (default when no subcommand given)
     Mic,
@@ -35,6 +174,51 @@ enum Mode {
         /// URL to stream audio from
         url: String,
     },
+    /// Drive the visuals from a recorded analysis (see `analyze` and
+    /// `--record`) at its original timing, without audio
+    Replay {
+        /// A .csv, .jsonl or .bin recording
+        file: PathBuf,
+    },
+    /// Render a WAV to PNG frames or a video file without opening a window
+    Render {
+        /// Path to the WAV file
+        #[arg(default_value = DEFAULT_WAV)]
+        file: String,
+        /// Output directory for numbered PNGs, or a video file (e.g. out.mp4)
+        /// which is encoded by ffmpeg with the original audio muxed in
+        #[arg(short, long)]
+        out: PathBuf,
+        /// Frames per second
+        #[arg(long, default_value_t = 60)]
+        fps: u32,
+        #[arg(long, default_value_t = 800)]
+        width: u32,
+        #[arg(long, default_value_t = 800)]
+        height: u32,
+        /// Only render the first N seconds
+        #[arg(long)]
+        seconds: Option<f32>,
+    },
+    /// Analyze a WAV faster than real time and write every frame to a
+    /// .csv, .jsonl or .bin file, without opening a window
+    Analyze {
+        /// Path to the WAV file
+        #[arg(default_value = DEFAULT_WAV)]
+        file: String,
+        /// Output file; the extension picks the format
+        #[arg(short, long)]
+        out: PathBuf,
+        /// Analysis frames per second of audio (smoothing is per frame, so
+        /// match the frame rate the visuals will run at)
+        #[arg(long, default_value_t = 60.0, value_parser = record::parse_rate)]
+        rate: f32,
+        /// Only analyze the first N seconds
+        #[arg(long)]
+        seconds: Option<f32>,
+    },
+    /// List available presets and where they come from
+    Presets,
 }
 
 enum AudioSource {
@@ -42,18 +226,44 @@ enum AudioSource {
     Wav {
         audio: AudioData,
         player: AudioPlayer,
+        track: TrackInfo,
+        /// the whole file, analyzed before playback
+        ahead: Box<TrackAnalysis>,
     },
     Url(UrlStream),
+    /// Recorded analysis instead of audio; the windows stay silent
+    Replay {
+        replay: Replay,
+        track: TrackInfo,
+    },
 }
 
 impl AudioSource {
     fn fill_window(&mut self, scratch: &mut Vec<f32>, fft_size: usize) {
         match self {
             Self::Mic(mic) => mic.read_window(scratch, fft_size),
-            Self::Wav { audio, player } => {
+            Self::Wav { audio, player, .. } => {
                 audio.window_at_time(player.elapsed_secs(), fft_size, scratch)
             }
             Self::Url(stream) => stream.read_window(scratch, fft_size),
+            Self::Replay { .. } => {
+                scratch.clear();
+                scratch.resize(fft_size, 0.0);
+            }
+        }
+    }
+
+    fn fill_stereo_window(&mut self, scratch: &mut Vec<[f32; 2]>, fft_size: usize) {
+        match self {
+            Self::Mic(mic) => mic.read_stereo_window(scratch, fft_size),
+            Self::Wav { audio, player, .. } => {
+                audio.stereo_window_at_time(player.elapsed_secs(), fft_size, scratch)
+            }
+            Self::Url(stream) => stream.read_stereo_window(scratch, fft_size),
+            Self::Replay { .. } => {
+                scratch.clear();
+                scratch.resize(fft_size, [0.0; 2]);
+            }
         }
     }
 
@@ -62,6 +272,125 @@ impl AudioSource {
             Self::Mic(mic) => mic.sample_rate,
             Self::Wav { audio, .. } => audio.sample_rate,
             Self::Url(stream) => stream.sample_rate,
+            // nothing is sampled; a usual rate keeps the scopes' timebase sensible
+            Self::Replay { .. } => 44_100,
+        }
+    }
+
+    fn track(&self) -> TrackInfo {
+        match self {
+            Self::Mic(_) => TrackInfo {
+                title: Some("Microphone".into()),
+                ..Default::default()
+            },
+            Self::Wav { track, .. } | Self::Replay { track, .. } => track.clone(),
+            Self::Url(stream) => stream.track(),
+        }
+    }
+
+    /// Seconds into the track; `None` for live input.
+    fn elapsed_secs(&self) -> Option<f32> {
+        match self {
+            Self::Mic(_) => None,
+            // playback loops, so wrap like `window_at_time` does
+            Self::Wav { audio, player, .. } => Some(
+                player
+                    .elapsed_secs()
+                    .rem_euclid(audio.duration_sec.max(0.000_1)),
+            ),
+            Self::Url(stream) => Some(stream.elapsed_secs()),
+            Self::Replay { replay, .. } => Some(replay.position()),
+        }
+    }
+
+    /// Gain that brings a file's peak to full scale; live input's is unknown.
+    fn peak_gain(&self) -> f32 {
+        match self {
+            Self::Wav { ahead, .. } => ahead.gain(),
+            _ => 1.0,
+        }
+    }
+
+    /// What's coming, for files analyzed ahead.
+    fn lookahead(&self) -> Option<LookAhead> {
+        match self {
+            Self::Wav { ahead, player, .. } => Some(ahead.at(player.elapsed_secs())),
+            _ => None,
+        }
+    }
+
+    /// The section a file is in, from its analysis, or as recorded; live
+    /// input works it out as it goes.
+    fn section(&self) -> Option<SectionInfo> {
+        match self {
+            Self::Wav { ahead, player, .. } => ahead.section_at(player.elapsed_secs()),
+            Self::Replay { replay, .. } => replay.section(),
+            _ => None,
+        }
+    }
+
+    /// Pauses or resumes file playback; `None` toggles.
+    fn set_paused(&mut self, paused: Option<bool>) -> anyhow::Result<()> {
+        match self {
+            Self::Wav { player, .. } => {
+                let paused = paused.unwrap_or(!player.is_paused());
+                player.set_paused(paused);
+                Ok(())
+            }
+            Self::Replay { replay, .. } => {
+                replay.set_paused(paused.unwrap_or(!replay.is_paused()));
+                Ok(())
+            }
+            _ => anyhow::bail!("only file playback and replay can pause"),
+        }
+    }
+
+    fn is_paused(&self) -> bool {
+        match self {
+            Self::Wav { player, .. } => player.is_paused(),
+            Self::Replay { replay, .. } => replay.is_paused(),
+            _ => false,
+        }
+    }
+
+    fn seek(&mut self, secs: f32) -> anyhow::Result<()> {
+        match self {
+            Self::Wav { audio, player, .. } => {
+                anyhow::ensure!(
+                    (0.0..audio.duration_sec).contains(&secs),
+                    "position must be within 0–{:.1} s",
+                    audio.duration_sec
+                );
+                player.seek(secs)
+            }
+            Self::Replay { replay, .. } => {
+                anyhow::ensure!(
+                    (0.0..=replay.duration()).contains(&secs),
+                    "position must be within 0–{:.1} s",
+                    replay.duration()
+                );
+                replay.seek(secs);
+                Ok(())
+            }
+            _ => anyhow::bail!("only file playback and replay can seek"),
+        }
+    }
+
+    /// Ring buffer state for the live sources; file playback has none.
+    fn health(&self) -> Option<BufferHealth> {
+        match self {
+            Self::Mic(mic) => Some(mic.health()),
+            Self::Wav { .. } | Self::Replay { .. } => None,
+            Self::Url(stream) => Some(stream.health()),
+        }
+    }
+
+    /// How far the centre of the analysis window trails the newest audio.
+    fn window_lag(&self, fft_size: usize) -> f32 {
+        match self {
+            // the window is centred on the playback position
+            Self::Wav { .. } | Self::Replay { .. } => 0.0,
+            _ => fft_size as f32 * 0.5 / self.sample_rate() as f32,
         }
     }
 }
@@ -69,71 +398,885 @@ impl AudioSource {
 struct Model {
     source: AudioSource,
     analyzer: Analyzer,
-    visual: RadialVisualizer,
+    visuals: Registry,
+    presets: PresetLibrary,
+    show_presets: bool,
+    watcher: PresetWatcher,
+    preset_error: Option<String>, // last reload failure, shown until fixed
+    hud: Hud,
+    tempo: TempoEstimator,
+    segmenter: Segmenter,
+    picked: usize, // the preset last picked by hand; its `[sections]` are in charge
+    // those `[sections]` and the presets they name, looked up when the table
+    // changes so a section change in the middle of a drop is only a switch
+    section_table: SectionLooks,
+    section_presets: Vec<(SectionKind, usize)>,
+    section: Option<(usize, SectionKind)>,
+    levels: LevelMeter,
+    diag: Diagnostics,
+    osc: Option<OscSender>,
+    remote: Option<OscReceiver>,
+    ws: Option<WsServer>,
+    dmx: Option<DmxOutput>,
+    midi: Option<MidiOutput>,
+    midi_in: Option<MidiIn>,
+    controller: Controller,
+    recorder: Option<Recorder>,
+    started: Instant, // recordings are timed from here
+    osc_prefix: String,
+    keymap: Keymap,
+    tap: TapTempo,
+    gain: f32,          // applied to the input before analysis
+    normalize: bool,    // files also get their own peak gain
+    frozen: bool,       // analysis and visuals hold still; audio keeps playing
+    drag: Option<Vec2>, // last mouse position while a button is held
     scratch_window: Vec<f32>,
-    latest_bands: Vec<f32>,
-    bass_fast: f32,
-    bass_smooth: f32,
+    scratch_stereo: Vec<[f32; 2]>,
+    // what's drained while frozen, apart from the windows the visuals hold
+    drain_window: Vec<f32>,
+    drain_stereo: Vec<[f32; 2]>,
+    latest: AnalysisFrame,
+    window_size: Vec2,
+    clear_frame: bool, // the window changed size; old trails no longer line up
 }
 
 fn main() {
+    let cli = Cli::parse();
+
+    // headless modes run before nannou so no window (or GPU) is ever touched
+    if let Some(Mode::Presets) = &cli.mode {
+        let presets = load_presets(&cli);
+        for entry in presets.entries() {
+            match &entry.source {
+                Some(path) => println!("{:<16} {}", entry.preset.name, path.display()),
+                None => println!("{:<16} (built in)", entry.preset.name),
+            }
+        }
+        return;
+    }
+    if let Some(Mode::Render {
+        file,
+        out,
+        fps,
+        width,
+        height,
+        seconds,
+    }) = &cli.mode
+    {
+        let preset = startup_preset(&cli, &mut load_presets(&cli));
+        let opts = RenderOptions {
+            file: file.clone(),
+            out: out.clone(),
+            fps: *fps,
+            width: *width,
+            height: *height,
+            seconds: *seconds,
+            analyzer: preset.analyzer.clone(),
+            normalize: cli.normalize,
+        };
+        if let Err(e) = render::run(&opts, build_visuals(&cli, &preset)) {
+            eprintln!("[render] error: {e:#}");
+            std::process::exit(1);
+        }
+        return;
+    }
+    if let Some(Mode::Analyze {
+        file,
+        out,
+        rate,
+        seconds,
+    }) = &cli.mode
+    {
+        let preset = startup_preset(&cli, &mut load_presets(&cli));
+        let opts = AnalyzeOptions {
+            file: file.clone(),
+            out: out.clone(),
+            rate: *rate,
+            seconds: *seconds,
+            analyzer: preset.analyzer.clone(),
+        };
+        if let Err(e) = record::run(&opts) {
+            eprintln!("[analyze] error: {e:#}");
+            std::process::exit(1);
+        }
+        return;
+    }
+
     nannou::app(model).update(update).run();
 }
 
-fn model(app: &App) -> Model {
-    app.new_window().size(800, 800).view(view).build().unwrap();
+fn load_presets(cli: &Cli) -> PresetLibrary {
+    let mut dirs = preset::library::default_dirs();
+    dirs.extend(cli.preset_dir.clone());
+    PresetLibrary::load(&dirs)
+}
+
+/// The `--preset` selection with any explicit command-line options applied on top.
+fn startup_preset(cli: &Cli, presets: &mut PresetLibrary) -> Preset {
+    let mut preset = presets
+        .select(&cli.preset)
+        .unwrap_or_else(|e| panic!("{e:#}"))
+        .clone();
+    if let Some(crossfade) = cli.crossfade {
+        preset.crossfade = crossfade;
+    }
+    if let Some(history) = cli.history {
+        preset.analyzer.history = history;
+    }
+    if let Some(trigger) = cli.trigger {
+        preset.oscilloscope.trigger = trigger;
+    }
+    if let Some(colormap) = cli.colormap {
+        preset.spectrogram.colormap = colormap;
+    }
+    if let Some(scroll) = cli.scroll {
+        preset.spectrogram.scroll = scroll;
+    }
+    preset
+}
+
+/// The built-in visualizers, in number-key order.
+#[derive(Clone, Copy, clap::ValueEnum)]
+enum View {
+    Radial,
+    Bars,
+    Oscilloscope,
+    Spectrogram,
+    Vectorscope,
+    Particles,
+}
+
+/// Every built-in visualizer, configured from `preset`, with `--view` selected.
+fn build_visuals(cli: &Cli, preset: &Preset) -> Registry {
+    // order here is the number-key order (and `View`'s): 1 = radial, 2 = bars, …
+    let mut visuals = Registry::new(vec![
+        Box::new(RadialVisualizer::new(BARS)),
+        Box::new(BarsVisualizer::new(BARS)),
+        Box::new(OscilloscopeVisualizer::new()),
+        Box::new(SpectrogramVisualizer::new()),
+        Box::new(VectorscopeVisualizer::new()),
+        Box::new(ParticleVisualizer::new()),
+    ]);
+    visuals.select(cli.view as usize);
+    visuals.apply_preset(preset);
+    visuals
+}
 
+fn model(app: &App) -> Model {
     let cli = Cli::parse();
-    let source = match cli.mode.unwrap_or(Mode::Mic) {
+    let window = app
+        .new_window()
+        .size(800, 800)
+        .min_size(200, 200)
+        .view(view)
+        .key_pressed(key_pressed)
+        .mouse_pressed(mouse_pressed)
+        .mouse_released(mouse_released)
+        .mouse_moved(mouse_moved)
+        .mouse_wheel(mouse_wheel)
+        .build()
+        .unwrap();
+    app.window(window).unwrap().set_fullscreen(cli.fullscreen);
+
+    let source = match cli.mode.clone().unwrap_or(Mode::Mic) {
         Mode::Mic => {
             let mic = MicCapture::start(FFT_SIZE).expect("Failed to start mic capture");
             AudioSource::Mic(mic)
         }
         Mode::Wav { file } => {
             let audio = AudioData::load_wav(&file).expect("Failed to load WAV");
+            let ahead = Box::new(
+                TrackAnalysis::load(Path::new(&file), &audio).expect("Failed to analyze WAV"),
+            );
             let player = AudioPlayer::start(&file);
-            AudioSource::Wav { audio, player }
+            let track = TrackInfo::from_wav(Path::new(&file), audio.duration_sec);
+            AudioSource::Wav {
+                audio,
+                player,
+                track,
+                ahead,
+            }
         }
         Mode::Url { url } => {
             let stream = UrlStream::start(&url, FFT_SIZE)
                 .expect("Failed to start URL stream — is yt-dlp and ffmpeg installed?");
             AudioSource::Url(stream)
         }
+        Mode::Replay { file } => {
+            let recording = Recording::load(&file).unwrap_or_else(|e| panic!("{e:#}"));
+            eprintln!(
+                "[replay] {} frames, {:.1}s",
+                recording.frames.len(),
+                recording.duration()
+            );
+            let track = TrackInfo {
+                title: file.file_stem().map(|s| s.to_string_lossy().into_owned()),
+                artist: Some("replay".into()),
+                duration: Some(recording.duration()),
+            };
+            AudioSource::Replay {
+                replay: Replay::new(recording),
+                track,
+            }
+        }
+        Mode::Render { .. } | Mode::Analyze { .. } | Mode::Presets => {
+            unreachable!("headless modes run from main")
+        }
     };
 
-    let analyzer = Analyzer::new(source.sample_rate(), FFT_SIZE, BARS);
-    let visual = RadialVisualizer::new(BARS);
+    let mut presets = load_presets(&cli);
+    let preset = startup_preset(&cli, &mut presets);
+    let mut analyzer = Analyzer::new(FFT_SIZE, BARS);
+    analyzer.set_params(&preset.analyzer);
+    let visuals = build_visuals(&cli, &preset);
+    app.window(window)
+        .unwrap()
+        .set_title(&title(&visuals, &preset));
+    let mut watcher = PresetWatcher::new();
+    watcher.watch(presets.current_source());
+    let picked = presets.current_index();
+    let section_table = presets.current().sections.clone();
+    let section_presets = section_presets(&mut presets, &section_table);
+    let keymap = match &cli.keymap {
+        Some(path) => Keymap::load(path).unwrap_or_else(|e| panic!("{e:#}")),
+        None => Keymap::default(),
+    };
+    let mut diag = Diagnostics::new();
+    if let Some(path) = &cli.diag_log {
+        diag.log_to(path).unwrap_or_else(|e| panic!("{e:#}"));
+    }
+
+    let osc_prefix = cli.osc_prefix.clone();
+    let osc = cli.osc.clone().map(|dest| {
+        OscSender::new(OscTarget {
+            dest,
+            prefix: osc_prefix.clone(),
+            rate: cli.osc_rate,
+            bundle: cli.osc_bundle,
+        })
+        .unwrap_or_else(|e| panic!("{e:#}"))
+    });
+    let ws = cli.ws.as_deref().map(|addr| {
+        WsServer::bind(addr, cli.ws_rate, cli.ws_binary).unwrap_or_else(|e| panic!("{e:#}"))
+    });
+    let dmx = cli
+        .dmx
+        .as_deref()
+        .map(|path| DmxOutput::load(path, presets.current()).unwrap_or_else(|e| panic!("{e:#}")));
+    let midi = cli.midi.as_ref().map(|port| {
+        MidiOutput::open(port.as_deref(), presets.current()).unwrap_or_else(|e| panic!("{e:#}"))
+    });
+    let midi_in = cli.midi_in.as_ref().map(|port| {
+        MidiIn::open("audio_visualizer", port.as_deref()).unwrap_or_else(|e| panic!("{e:#}"))
+    });
+    let recorder = cli.record.as_deref().map(|path| {
+        let recorder = Recorder::create(path).unwrap_or_else(|e| panic!("{e:#}"));
+        eprintln!("[record] writing {}", path.display());
+        recorder
+    });
+    let remote = cli
+        .osc_listen
+        .as_deref()
+        .map(|addr| OscReceiver::bind(addr).unwrap_or_else(|e| panic!("{e:#}")));
 
     Model {
         source,
         analyzer,
-        visual,
+        visuals,
+        presets,
+        show_presets: false,
+        watcher,
+        preset_error: None,
+        hud: Hud::new(&preset.hud),
+        tempo: TempoEstimator::new(),
+        segmenter: Segmenter::new(),
+        picked,
+        section_table,
+        section_presets,
+        section: None,
+        levels: LevelMeter::new(),
+        diag,
+        osc,
+        remote,
+        ws,
+        dmx,
+        midi,
+        midi_in,
+        controller: Controller::new(&preset.midi_in),
+        recorder,
+        started: Instant::now(),
+        osc_prefix,
+        keymap,
+        tap: TapTempo::new(),
+        gain: 1.0,
+        normalize: cli.normalize,
+        frozen: false,
+        drag: None,
         scratch_window: Vec::with_capacity(FFT_SIZE),
-        latest_bands: vec![0.0; BARS],
-        bass_fast: 0.0,
-        bass_smooth: 0.0,
+        scratch_stereo: Vec::with_capacity(FFT_SIZE),
+        drain_window: Vec::with_capacity(FFT_SIZE),
+        drain_stereo: Vec::with_capacity(FFT_SIZE),
+        latest: AnalysisFrame {
+            bands: vec![0.0; BARS],
+            ..Default::default()
+        },
+        window_size: Vec2::ZERO,
+        clear_frame: true,
     }
 }
 
-fn update(_app: &App, model: &mut Model, _update: Update) {
+fn update(app: &App, model: &mut Model, update: Update) {
+    let size = app.window_rect().wh();
+    model.clear_frame = size != model.window_size;
+    model.window_size = size;
+
+    // edits to the active preset file apply live; audio is never touched.
+    // `[sections]` is read from the library, so a reload of the hand-picked
+    // preset takes effect there too
+    match model.watcher.poll() {
+        Some(Ok(preset)) => {
+            eprintln!("[preset] reloaded {}", preset.name);
+            model.presets.replace_current(preset.clone());
+            apply_preset(app, model, &preset);
+        }
+        Some(Err(e)) => {
+            eprintln!("[preset] {e:#}");
+            model.preset_error = Some(format!("{e:#}"));
+        }
+        None => {}
+    }
+    remote_control(app, model);
+    midi_control(app, model);
+
+    let dt = update.since_last.as_secs_f32();
+    if model.frozen {
+        // keep draining the live buffers so nothing backs up or drops,
+        // but leave the windows the visuals see as they were
+        model.source.fill_window(&mut model.drain_window, FFT_SIZE);
+        model
+            .source
+            .fill_stereo_window(&mut model.drain_stereo, FFT_SIZE);
+        let lag = model.source.window_lag(FFT_SIZE);
+        model.diag.frame(dt, 0.0, lag, model.source.health());
+        if let Some(dmx) = &mut model.dmx {
+            dmx.hold(dt);
+        }
+        if let Some(midi) = &mut model.midi {
+            midi.hold(dt);
+        }
+        return;
+    }
+
     model
         .source
         .fill_window(&mut model.scratch_window, FFT_SIZE);
-    let frame = model
-        .analyzer
-        .analyze(&model.scratch_window, model.source.sample_rate());
-    model.latest_bands = frame.bands;
-    model.bass_fast = frame.bass_fast;
-    model.bass_smooth = frame.bass_smooth;
+    model
+        .source
+        .fill_stereo_window(&mut model.scratch_stereo, FFT_SIZE);
+    // meters show the source as it is; gain only drives the visuals
+    if !matches!(model.source, AudioSource::Replay { .. }) {
+        model.levels.update(&model.scratch_window, dt);
+    }
+    let mut gain = model.gain;
+    if model.normalize {
+        gain *= model.source.peak_gain();
+    }
+    if gain != 1.0 {
+        model.scratch_window.iter_mut().for_each(|s| *s *= gain);
+        for frame in &mut model.scratch_stereo {
+            frame[0] *= gain;
+            frame[1] *= gain;
+        }
+    }
+
+    let started = Instant::now();
+    model.latest = match &mut model.source {
+        // the recording stands in for the analyzer and the meters
+        AudioSource::Replay { replay, .. } => {
+            let record = replay.advance(dt);
+            model.levels.set(record.rms_db, record.peak_db, dt);
+            model.analyzer.replay(&record.bands);
+            record.frame()
+        }
+        source => AnalysisFrame {
+            lookahead: source.lookahead(),
+            ..model
+                .analyzer
+                .analyze(&model.scratch_window, source.sample_rate())
+        },
+    };
+    let analysis = started.elapsed().as_secs_f32();
+    let section = match model.source.section() {
+        Some(section) => section,
+        None => model
+            .segmenter
+            .push(&model.latest.bands, model.levels.rms_db, dt),
+    };
+    model.latest.section = Some(section);
+    if model.section != Some((section.index, section.kind)) {
+        model.section = Some((section.index, section.kind));
+        eprintln!("[section] {}", section.kind.name());
+        enter_section(app, model, section.kind);
+    }
+
+    model.diag.frame(
+        dt,
+        analysis,
+        model.source.window_lag(FFT_SIZE),
+        model.source.health(),
+    );
+    model.tempo.push(model.latest.flux, dt);
+    let bpm = model.tap.bpm().or(model.tempo.bpm());
+    if let Some(osc) = &mut model.osc {
+        osc.frame(&model.latest, bpm, dt);
+    }
+    if let Some(ws) = &mut model.ws {
+        ws.frame(&model.latest, &model.levels, bpm, dt);
+    }
+    if let Some(dmx) = &mut model.dmx {
+        dmx.frame(&model.latest, &model.levels, dt);
+    }
+    if let Some(midi) = &mut model.midi {
+        midi.frame(&model.latest, &model.levels, bpm, dt);
+    }
+    if let Some(recorder) = &mut model.recorder {
+        let t = model.started.elapsed().as_secs_f32();
+        if let Err(e) = recorder.write(&Record::new(t, &model.latest, &model.levels, bpm)) {
+            eprintln!("[record] stopped: {e:#}");
+            model.recorder = None;
+        }
+    }
+
+    let snap = Snapshot {
+        frame: &model.latest,
+        waveform: &model.scratch_window,
+        stereo: &model.scratch_stereo,
+        history: model.analyzer.history(),
+        sample_rate: model.source.sample_rate(),
+    };
+    model.visuals.update(&snap, dt);
+}
+
+fn key_pressed(app: &App, model: &mut Model, key: Key) {
+    match model.keymap.get(key) {
+        Some(action) => perform(app, model, action),
+        None => model.visuals.key_pressed(key),
+    }
+}
+
+fn perform(app: &App, model: &mut Model, action: Action) {
+    match action {
+        Action::View(slot) => model.visuals.select(slot),
+        Action::NextView => model.visuals.cycle(1),
+        Action::PrevView => model.visuals.cycle(-1),
+        Action::NextPreset | Action::PrevPreset => {
+            let step = if action == Action::NextPreset { 1 } else { -1 };
+            let preset = model.presets.cycle(step).clone();
+            eprintln!("[preset] {}", preset.name);
+            model.watcher.watch(model.presets.current_source());
+            model.picked = model.presets.current_index();
+            apply_preset(app, model, &preset);
+        }
+        Action::PresetList => model.show_presets = !model.show_presets,
+        Action::GainUp | Action::GainDown => {
+            let factor = if action == Action::GainUp { 1.25 } else { 0.8 };
+            model.gain = (model.gain * factor).clamp(0.05, 20.0);
+            eprintln!("[control] gain {:.2}", model.gain);
+        }
+        Action::SmoothingUp | Action::SmoothingDown => {
+            // more smoothing = a smaller step towards each new frame
+            let factor = if action == Action::SmoothingUp {
+                0.8
+            } else {
+                1.25
+            };
+            let mut params = model.analyzer.params().clone();
+            params.alpha_bands = (params.alpha_bands * factor).clamp(0.01, 1.0);
+            eprintln!("[control] alpha_bands {:.3}", params.alpha_bands);
+            model.analyzer.set_params(&params);
+        }
+        Action::HueUp => model.visuals.shift_hue(0.05),
+        Action::HueDown => model.visuals.shift_hue(-0.05),
+        Action::Freeze => model.frozen = !model.frozen,
+        Action::Fullscreen => {
+            let window = app.main_window();
+            window.set_fullscreen(!window.is_fullscreen());
+        }
+        Action::Hud => model.hud.visible = !model.hud.visible,
+        Action::Diagnostics => model.diag.visible = !model.diag.visible,
+        Action::TapTempo => {
+            if let Some(bpm) = model.tap.tap(app.time) {
+                eprintln!("[control] tapped {bpm:.1} BPM");
+            }
+        }
+        Action::SeekBack | Action::SeekForward => {
+            let step = if action == Action::SeekForward {
+                5.0
+            } else {
+                -5.0
+            };
+            // not `clamp`: sources shorter than the margin would make it panic
+            if let (Some(pos), Some(total)) =
+                (model.source.elapsed_secs(), model.source.track().duration)
+                && let Err(e) = model.source.seek((pos + step).min(total - 0.01).max(0.0))
+            {
+                eprintln!("[control] {e:#}");
+            }
+        }
+        Action::Pause => {
+            if let Err(e) = model.source.set_paused(None) {
+                eprintln!("[control] {e:#}");
+            }
+        }
+        Action::MidiLearn => match model.controller.step_learn() {
+            Some(param) => eprintln!("[midi] move a knob to bind {param}"),
+            None => eprintln!("[midi] learn off"),
+        },
+        Action::Reset => {
+            model.gain = 1.0;
+            model.tap.clear();
+            model.visuals.reset_view();
+            let preset = model.presets.current().clone();
+            apply_preset(app, model, &preset);
+        }
+    }
+    app.main_window()
+        .set_title(&title(&model.visuals, model.presets.current()));
+}
+
+fn remote_control(app: &App, model: &mut Model) {
+    let Some(remote) = &model.remote else {
+        return;
+    };
+    for (msg, from) in remote.poll() {
+        match Command::parse(&msg, &model.osc_prefix) {
+            Some(Ok(command)) => {
+                if let Err(e) = run_command(app, model, command, from) {
+                    eprintln!("[osc] {}: {e:#}", msg.addr);
+                }
+            }
+            Some(Err(e)) => eprintln!("[osc] {e:#}"),
+            None => {}
+        }
+    }
+}
+
+fn run_command(
+    app: &App,
+    model: &mut Model,
+    command: Command,
+    from: SocketAddr,
+) -> anyhow::Result<()> {
+    match command {
+        Command::View(target) => {
+            let slot = match target {
+                Target::Index(i) => i,
+                Target::Name(name) => model
+                    .visuals
+                    .index_of(&name)
+                    .with_context(|| format!("no visualizer named '{name}'"))?,
+            };
+            anyhow::ensure!(
+                slot < model.visuals.names().count(),
+                "no visualizer {}",
+                slot + 1
+            );
+            model.visuals.select(slot);
+        }
+        Command::Preset(target) => {
+            let spec = match target {
+                Target::Index(i) => model
+                    .presets
+                    .entries()
+                    .get(i)
+                    .with_context(|| format!("no preset {}", i + 1))?
+                    .preset
+                    .name
+                    .clone(),
+                Target::Name(name) => name,
+            };
+            let preset = model.presets.select(&spec)?.clone();
+            eprintln!("[preset] {}", preset.name);
+            model.watcher.watch(model.presets.current_source());
+            model.picked = model.presets.current_index();
+            apply_preset(app, model, &preset);
+        }
+        Command::Action(action) => perform(app, model, action),
+        Command::Param(path, value) => set_param(app, model, &path, value)?,
+        Command::Learn(path) => {
+            model.controller.learn(&path, model.presets.current())?;
+            eprintln!("[midi] move a knob to bind {path}");
+        }
+        Command::Gain(gain) => model.gain = gain.clamp(0.05, 20.0),
+        Command::Pause(paused) => model.source.set_paused(paused)?,
+        Command::Seek(secs) => model.source.seek(secs)?,
+        Command::Query(under) => {
+            let mut state = remote_state(model);
+            if let Some(under) = under {
+                let under = format!("{}/{}", model.osc_prefix, under.trim_matches('/'));
+                state.retain(|m| m.addr.starts_with(&under));
+            }
+            if let Some(remote) = &model.remote {
+                remote.reply(from, &state);
+            }
+        }
+    }
+    app.main_window()
+        .set_title(&title(&model.visuals, model.presets.current()));
+    Ok(())
+}
+
+/// Changes the preset in memory only; the file stays as it is.
+fn set_param(app: &App, model: &mut Model, path: &str, value: Value) -> anyhow::Result<()> {
+    let preset = model.presets.current().with_value(path, value)?;
+    model.presets.replace_current(preset.clone());
+    apply_preset(app, model, &preset);
+    Ok(())
+}
+
+fn midi_control(app: &App, model: &mut Model) {
+    let Some(midi_in) = &model.midi_in else {
+        return;
+    };
+    // a turned knob sends dozens of changes a frame; gather them on a copy
+    // and apply the result once
+    let mut edited: Option<Preset> = None;
+    for cc in midi_in.poll() {
+        let preset = edited.as_ref().unwrap_or(model.presets.current());
+        match model.controller.control(cc, preset) {
+            Some(Change::Set(path, value)) => match preset.with_value(&path, value) {
+                Ok(preset) => edited = Some(preset),
+                Err(e) => eprintln!("[midi] {path}: {e:#}"),
+            },
+            Some(Change::Learned(midi_in)) => {
+                let b = midi_in.bindings.last().expect("just learned");
+                eprintln!("[midi] channel {} cc {} → {}", b.channel, b.cc, b.param);
+                let mut preset = preset.clone();
+                preset.midi_in = midi_in;
+                // keep the binding with the preset; built-in ones have no file
+                if let Some(path) = model.presets.current_source() {
+                    if let Err(e) = preset::save_bindings(path, &preset.midi_in) {
+                        eprintln!("[midi] {e:#}");
+                    }
+                }
+                edited = Some(preset);
+            }
+            None => {}
+        }
+    }
+    if let Some(preset) = edited {
+        model.presets.replace_current(preset.clone());
+        apply_preset(app, model, &preset);
+    }
+}
+
+/// Everything a control surface can set, at the addresses it would set it
+/// with, so replies to `/query` line up with the surface's own controls.
+fn remote_state(model: &Model) -> Vec<Message> {
+    let addr = |name: &str| format!("{}/{name}", model.osc_prefix);
+    let preset = model.presets.current();
+    let mut out = vec![
+        Message::new(
+            addr("view"),
+            vec![Arg::Str(model.visuals.active_name().into())],
+        ),
+        Message::new(addr("preset"), vec![Arg::Str(preset.name.clone())]),
+        Message::new(addr("gain"), vec![Arg::Float(model.gain)]),
+        Message::new(addr("frozen"), vec![Arg::Int(model.frozen as i32)]),
+    ];
+    if let Some(bpm) = model.tap.bpm().or(model.tempo.bpm()) {
+        out.push(Message::new(addr("bpm"), vec![Arg::Float(bpm)]));
+    }
+    if let Some(pos) = model.source.elapsed_secs() {
+        let paused = model.source.is_paused() as i32;
+        out.push(Message::new(
+            addr("transport/pause"),
+            vec![Arg::Int(paused)],
+        ));
+        out.push(Message::new(addr("transport/seek"), vec![Arg::Float(pos)]));
+    }
+    if let Some(duration) = model.source.track().duration {
+        out.push(Message::new(
+            addr("transport/duration"),
+            vec![Arg::Float(duration)],
+        ));
+    }
+    for (path, value) in preset.values() {
+        if let Some(arg) = control::value_arg(&value) {
+            out.push(Message::new(addr(&format!("param/{path}")), vec![arg]));
+        }
+    }
+    out
+}
+
+fn mouse_pressed(app: &App, model: &mut Model, _button: MouseButton) {
+    model.drag = Some(app.mouse.position());
+}
+
+fn mouse_released(_app: &App, model: &mut Model, _button: MouseButton) {
+    model.drag = None;
+}
+
+fn mouse_moved(_app: &App, model: &mut Model, pos: Point2) {
+    if let Some(last) = model.drag.replace(pos) {
+        model.visuals.mouse_dragged(pos - last);
+    }
+}
+
+fn mouse_wheel(_app: &App, model: &mut Model, delta: MouseScrollDelta, _phase: TouchPhase) {
+    let lines = match delta {
+        MouseScrollDelta::LineDelta(_, y) => y,
+        // trackpads report pixels; ~20 px feels like one wheel notch
+        MouseScrollDelta::PixelDelta(p) => p.y as f32 / 20.0,
+    };
+    model.visuals.mouse_scrolled(lines);
+}
+
+fn apply_preset(app: &App, model: &mut Model, preset: &Preset) {
+    model.analyzer.set_params(&preset.analyzer);
+    model.visuals.apply_preset(preset);
+    model.hud.apply(&preset.hud);
+    if let Some(dmx) = &mut model.dmx {
+        dmx.apply_preset(preset);
+    }
+    if let Some(midi) = &mut model.midi {
+        midi.apply_preset(preset);
+    }
+    model.controller.apply(&preset.midi_in);
+    // picked by hand, reloaded or edited: look up a changed `[sections]`
+    let table = &model.presets.entries()[model.picked].preset.sections;
+    if *table != model.section_table {
+        model.section_table = table.clone();
+        model.section_presets = section_presets(&mut model.presets, &model.section_table);
+    }
+    model.preset_error = None;
+    app.main_window().set_title(&title(&model.visuals, preset));
+}
+
+/// The library entries `table` names, by section. Specs that name nothing
+/// are reported now rather than at every section change.
+fn section_presets(presets: &mut PresetLibrary, table: &SectionLooks) -> Vec<(SectionKind, usize)> {
+    SectionKind::ALL
+        .into_iter()
+        .filter_map(|kind| {
+            let spec = table.get(kind)?;
+            presets
+                .resolve(spec)
+                .inspect_err(|e| eprintln!("[section] {}: {e:#}", kind.name()))
+                .ok()
+                .map(|i| (kind, i))
+        })
+        .collect()
+}
+
+/// Switches to the preset the hand-picked one's `[sections]` table names for
+/// `kind`, if any. The table itself stays in charge.
+fn enter_section(app: &App, model: &mut Model, kind: SectionKind) {
+    let Some(&(_, i)) = model.section_presets.iter().find(|(k, _)| *k == kind) else {
+        return;
+    };
+    // already showing it; reapplying would restart the crossfade
+    if i == model.presets.current_index() {
+        return;
+    }
+    let preset = model.presets.select_index(i).clone();
+    eprintln!("[preset] {} for the {}", preset.name, kind.name());
+    model.watcher.watch(model.presets.current_source());
+    apply_preset(app, model, &preset);
+}
+
+fn title(visuals: &Registry, preset: &Preset) -> String {
+    format!(
+        "audio_visualizer — {} — {}",
+        visuals.active_name(),
+        preset.name
+    )
+}
+
+/// A failed preset reload, boxed along the bottom edge. The previous values
+/// stay active underneath until the file is fixed.
+fn draw_preset_error(canvas: &mut dyn Canvas, error: &str) {
+    let win = canvas.bounds();
+    let lines: Vec<&str> = error.lines().take(8).collect();
+    let line_h = 16.0;
+    let box_h = (lines.len() + 1) as f32 * line_h + 12.0;
+    canvas.rect(
+        pt2(0.0, win.bottom() + box_h * 0.5),
+        vec2(win.w(), box_h),
+        rgba(0.25, 0.0, 0.0, 0.85),
+    );
+    let mut y = win.bottom() + box_h - 14.0;
+    let header = "preset error: keeping previous values";
+    for (i, line) in std::iter::once(header).chain(lines).enumerate() {
+        let color = if i == 0 {
+            rgba(1.0, 0.45, 0.4, 1.0)
+        } else {
+            rgba(1.0, 0.85, 0.85, 1.0)
+        };
+        canvas.text(line, pt2(win.left() + 12.0, y), 12, Align::Left, color);
+        y -= line_h;
+    }
+}
+
+/// Preset names down the left edge with the current one highlighted.
+fn draw_preset_list(canvas: &mut dyn Canvas, presets: &PresetLibrary) {
+    let win = canvas.bounds();
+    let current = presets.current_index();
+    for (i, entry) in presets.entries().iter().enumerate() {
+        let (marker, color) = if i == current {
+            ("▸ ", rgba(1.0, 1.0, 1.0, 1.0))
+        } else {
+            ("  ", rgba(0.6, 0.6, 0.6, 0.8))
+        };
+        canvas.text(
+            &format!("{marker}{}", entry.preset.name),
+            pt2(win.left() + 12.0, win.top() - 18.0 - i as f32 * 18.0),
+            13,
+            Align::Left,
+            color,
+        );
+    }
 }
 
 fn view(app: &App, model: &Model, frame: Frame) {
-    model.visual.draw(
-        app,
-        frame,
-        &model.latest_bands,
-        model.bass_fast,
-        model.bass_smooth,
-        &model.scratch_window,
+    let started = Instant::now();
+    let draw = app.draw();
+    if model.clear_frame {
+        draw.background().color(BLACK);
+    }
+    let snap = Snapshot {
+        frame: &model.latest,
+        waveform: &model.scratch_window,
+        stereo: &model.scratch_stereo,
+        history: model.analyzer.history(),
+        sample_rate: model.source.sample_rate(),
+    };
+    let mut canvas = WindowCanvas::new(&draw, app.window_rect());
+    model.visuals.draw(&mut canvas, &snap);
+    let track = model.source.track();
+    model.hud.draw(
+        &mut canvas,
+        &HudInfo {
+            track: &track,
+            elapsed: model.source.elapsed_secs(),
+            bpm: model.tap.bpm().or(model.tempo.bpm()),
+            tapped: model.tap.bpm().is_some(),
+            section: model.latest.section.map(|s| s.kind.name()),
+            gain: model.gain,
+            frozen: model.frozen,
+            learning: model.controller.learning(),
+            levels: &model.levels,
+            view: model.visuals.active_name(),
+            preset: &model.presets.current().name,
+        },
     );
+    if model.show_presets {
+        draw_preset_list(&mut canvas, &model.presets);
+    }
+    if let Some(error) = &model.preset_error {
+        draw_preset_error(&mut canvas, error);
+    }
+    model.diag.draw(&mut canvas);
+    draw.to_frame(app, &frame).unwrap();
+    model.diag.last_render.set(started.elapsed().as_secs_f32());
 }
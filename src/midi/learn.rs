@@ -0,0 +1,293 @@
+use super::Cc;
+use crate::preset::Preset;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+/// The `[midi_in]` preset section: controller knobs bound to preset values.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct MidiInParams {
+    /// Soft takeover: a knob does nothing until it reaches (or passes) the
+    /// value it controls, so nothing jumps after a preset change
+    pub takeover: bool,
+    pub bindings: Vec<Binding>,
+}
+
+impl Default for MidiInParams {
+    fn default() -> Self {
+        Self {
+            takeover: true,
+            bindings: Vec::new(),
+        }
+    }
+}
+
+/// One knob and the preset value it sets, across `min..max` over its travel.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Binding {
+    /// 1–16
+    pub channel: u8,
+    pub cc: u8,
+    /// A preset value by path, as OSC `/param` takes them, e.g. `radial/bar_gain`
+    pub param: String,
+    pub min: f32,
+    pub max: f32,
+}
+
+/// Values worth a knob, in the order the learn key steps through them, with
+/// the range a knob learned for them covers.
+const LEARNABLE: &[(&str, f32, f32)] = &[
+    ("radial/bar_gain", 0.0, 1000.0),
+    ("radial/pulse_gain", 0.0, 200.0),
+    ("radial/color/offset", 0.0, 1.0),
+    ("radial/color/bass_shift", 0.0, 1.0),
+    ("radial/fade_alpha", 0.01, 1.0),
+    ("analyzer/alpha_bands", 0.01, 1.0),
+    ("analyzer/alpha_bass_fast", 0.01, 1.0),
+    ("analyzer/alpha_bass_slow", 0.01, 1.0),
+    ("bars/bar_gain", 0.0, 4.0),
+    ("particles/hue_base", 0.0, 1.0),
+    ("particles/speed", 0.0, 800.0),
+];
+
+/// What a control change asks for.
+#[derive(Debug, PartialEq)]
+pub enum Change {
+    /// A preset value, as OSC `/param` sets it
+    Set(String, Value),
+    /// A knob was learned; these are the preset's bindings now
+    Learned(MidiInParams),
+}
+
+#[derive(Clone, Copy, Default)]
+struct Takeover {
+    caught: bool,
+    /// knob position at the last message, 0..1
+    knob: Option<f32>,
+    /// what the knob last set, 0..1 of the binding's range
+    sent: Option<f32>,
+}
+
+/// A value waiting for a knob to be moved.
+struct Learning {
+    param: String,
+    min: f32,
+    max: f32,
+}
+
+/// Turns control changes into preset changes for the current preset's
+/// bindings, and binds the next knob moved while learning.
+pub struct Controller {
+    params: MidiInParams,
+    state: Vec<Takeover>,
+    learning: Option<Learning>,
+    /// position in `LEARNABLE` the learn key is at
+    step: Option<usize>,
+}
+
+impl Controller {
+    pub fn new(params: &MidiInParams) -> Self {
+        Self {
+            params: params.clone(),
+            state: vec![Takeover::default(); params.bindings.len()],
+            learning: None,
+            step: None,
+        }
+    }
+
+    /// New bindings start out waiting to be caught.
+    pub fn apply(&mut self, params: &MidiInParams) {
+        if *params != self.params {
+            self.params = params.clone();
+            self.state = vec![Takeover::default(); params.bindings.len()];
+        }
+    }
+
+    /// Arms learning for the next of the commonly tweaked values; stepping
+    /// past the last one turns learning off.
+    pub fn step_learn(&mut self) -> Option<&str> {
+        let next = self.step.map_or(0, |i| i + 1);
+        match LEARNABLE.get(next) {
+            Some(&(param, min, max)) => {
+                self.step = Some(next);
+                self.learning = Some(Learning {
+                    param: param.to_string(),
+                    min,
+                    max,
+                });
+            }
+            None => {
+                self.step = None;
+                self.learning = None;
+            }
+        }
+        self.learning()
+    }
+
+    /// Arms learning for any preset value. Its range is guessed from where
+    /// it is now: 0..1 for values already in it, otherwise out to twice the
+    /// current value. Edit the preset file for anything else.
+    pub fn learn(&mut self, param: &str, preset: &Preset) -> anyhow::Result<()> {
+        let (min, max) = match preset.value(param) {
+            Some(Value::Bool(_)) => (0.0, 1.0),
+            Some(Value::Number(n)) => {
+                let v = n.as_f64().unwrap_or_default() as f32;
+                if (0.0..=1.0).contains(&v) {
+                    (0.0, 1.0)
+                } else {
+                    (v.min(0.0) * 2.0, v.max(0.0) * 2.0)
+                }
+            }
+            _ => anyhow::bail!("No preset value '{param}' to bind"),
+        };
+        self.learning = Some(Learning {
+            param: param.to_string(),
+            min,
+            max,
+        });
+        Ok(())
+    }
+
+    /// The value waiting for a knob, if learning.
+    pub fn learning(&self) -> Option<&str> {
+        self.learning.as_ref().map(|l| l.param.as_str())
+    }
+
+    /// What `cc` asks of `preset`, if anything.
+    pub fn control(&mut self, cc: Cc, preset: &Preset) -> Option<Change> {
+        if let Some(l) = self.learning.take() {
+            self.step = None;
+            // a knob drives one value and a value follows one knob
+            let bindings = &mut self.params.bindings;
+            bindings.retain(|b| (b.channel, b.cc) != (cc.channel, cc.cc) && b.param != l.param);
+            bindings.push(Binding {
+                channel: cc.channel,
+                cc: cc.cc,
+                param: l.param,
+                min: l.min,
+                max: l.max,
+            });
+            self.state = vec![Takeover::default(); bindings.len()];
+            return Some(Change::Learned(self.params.clone()));
+        }
+
+        let i = self
+            .params
+            .bindings
+            .iter()
+            .position(|b| (b.channel, b.cc) == (cc.channel, cc.cc))?;
+        let b = &self.params.bindings[i];
+        let knob = cc.value as f32 / 127.0;
+        let current = match preset.value(&b.param)? {
+            // switches flip at half travel, there's nothing to catch
+            Value::Bool(_) => return Some(Change::Set(b.param.clone(), (knob >= 0.5).into())),
+            Value::Number(n) => n.as_f64()? as f32,
+            _ => return None,
+        };
+        let at = ((current - b.min) / (b.max - b.min)).clamp(0.0, 1.0);
+
+        let s = &mut self.state[i];
+        let tolerance = 1.5 / 127.0;
+        // moved by something else since (a preset switch, a key, OSC)
+        if s.sent.is_some_and(|sent| (sent - at).abs() > tolerance) {
+            s.caught = false;
+        }
+        if !s.caught {
+            let near = (knob - at).abs() <= tolerance;
+            let crossed = s.knob.is_some_and(|last| (last - at) * (knob - at) <= 0.0);
+            s.caught = !self.params.takeover || near || crossed;
+        }
+        s.knob = Some(knob);
+        if !s.caught {
+            return None;
+        }
+        s.sent = Some(knob);
+        let v = b.min + knob * (b.max - b.min);
+        Some(Change::Set(b.param.clone(), v.into()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cc(value: u8) -> Cc {
+        Cc {
+            channel: 1,
+            cc: 21,
+            value,
+        }
+    }
+
+    fn set(change: Option<Change>) -> Option<f32> {
+        match change? {
+            Change::Set(_, v) => v.as_f64().map(|v| v as f32),
+            Change::Learned(_) => None,
+        }
+    }
+
+    #[test]
+    fn learning_binds_the_next_knob_moved() {
+        let preset = Preset::default();
+        let mut c = Controller::new(&MidiInParams::default());
+        assert_eq!(c.step_learn(), Some("radial/bar_gain"));
+        assert_eq!(c.step_learn(), Some("radial/pulse_gain"));
+        let Some(Change::Learned(params)) = c.control(cc(64), &preset) else {
+            panic!("expected a binding");
+        };
+        assert_eq!(params.bindings.len(), 1);
+        assert_eq!(params.bindings[0].param, "radial/pulse_gain");
+        assert_eq!(
+            (params.bindings[0].min, params.bindings[0].max),
+            (0.0, 200.0)
+        );
+        assert_eq!(c.learning(), None);
+
+        // relearning a value moves it to the new knob
+        c.learn("radial/pulse_gain", &preset).unwrap();
+        let Some(Change::Learned(params)) = c.control(Cc { cc: 22, ..cc(0) }, &preset) else {
+            panic!("expected a binding");
+        };
+        assert_eq!(params.bindings.len(), 1);
+        assert_eq!(params.bindings[0].cc, 22);
+        assert!(c.learn("radial/nope", &preset).is_err());
+    }
+
+    #[test]
+    fn knobs_take_over_once_they_reach_the_value() {
+        let params = MidiInParams {
+            takeover: true,
+            bindings: vec![Binding {
+                channel: 1,
+                cc: 21,
+                param: "radial/fade_alpha".into(),
+                min: 0.0,
+                max: 1.0,
+            }],
+        };
+        let mut c = Controller::new(&params);
+        let mut preset = Preset::default();
+        preset.radial.fade_alpha = 0.5;
+
+        // below the value: ignored until the knob passes it
+        assert_eq!(c.control(cc(10), &preset), None);
+        assert_eq!(c.control(cc(40), &preset), None);
+        assert!(set(c.control(cc(70), &preset)).is_some_and(|v| (v - 70.0 / 127.0).abs() < 1e-6));
+        preset.radial.fade_alpha = 70.0 / 127.0;
+        assert!(set(c.control(cc(20), &preset)).is_some());
+        preset.radial.fade_alpha = 20.0 / 127.0;
+
+        // a preset switch puts the value somewhere else: catch it again
+        preset.radial.fade_alpha = 0.9;
+        assert_eq!(c.control(cc(25), &preset), None);
+        assert!(set(c.control(cc(114), &preset)).is_some());
+
+        // without takeover the knob always applies
+        let mut c = Controller::new(&MidiInParams {
+            takeover: false,
+            ..params
+        });
+        assert!(set(c.control(cc(0), &preset)).is_some());
+    }
+}
@@ -0,0 +1,439 @@
+//! MIDI output: CCs from band groups and envelopes, notes on percussion
+//! triggers and a MIDI clock at the tapped or detected tempo. MIDI input:
+//! controller knobs bound to preset values, learned live.
+
+pub mod learn;
+mod port;
+
+pub use learn::{Change, Controller, MidiInParams};
+pub use port::{Cc, MidiIn, MidiOut};
+
+use crate::analysis::{AnalysisFrame, LevelMeter, Source};
+use crate::preset::Preset;
+use serde::{Deserialize, Serialize};
+use std::sync::{Arc, Mutex};
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+
+const NOTE_ON: u8 = 0x90;
+const NOTE_OFF: u8 = 0x80;
+const CONTROL_CHANGE: u8 = 0xb0;
+const CLOCK: u8 = 0xf8;
+const START: u8 = 0xfa;
+const STOP: u8 = 0xfc;
+
+/// The `[midi]` preset section.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct MidiParams {
+    /// 1–16
+    pub channel: u8,
+    /// Send MIDI clock (24 ticks per beat) once a tempo is known
+    pub clock: bool,
+    pub cc: Vec<CcMap>,
+    pub notes: Vec<NoteMap>,
+}
+
+impl Default for MidiParams {
+    fn default() -> Self {
+        let cc = |cc, source: &str| CcMap {
+            cc,
+            source: Source::try_from(source.to_string()).unwrap(),
+            gain: 1.0,
+        };
+        Self {
+            channel: 1,
+            clock: true,
+            // lows, mids and highs of the default 120 bands, then the bass envelope
+            cc: vec![
+                cc(20, "bands:0-20"),
+                cc(21, "bands:20-70"),
+                cc(22, "bands:70-120"),
+                cc(23, "bass_smooth"),
+            ],
+            notes: vec![NoteMap {
+                note: 36,
+                ..NoteMap::default()
+            }],
+        }
+    }
+}
+
+/// A controller that follows a source, sent whenever its 7-bit value changes.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct CcMap {
+    pub cc: u8,
+    pub source: Source,
+    #[serde(default = "unity")]
+    pub gain: f32,
+}
+
+fn unity() -> f32 {
+    1.0
+}
+
+/// A note played each time its source rises through `threshold`; with the
+/// default `onset` source, once per detected onset.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct NoteMap {
+    pub note: u8,
+    pub source: Source,
+    pub threshold: f32,
+    pub length_ms: f32,
+    /// Fixed velocity; without one it follows the source's level
+    pub velocity: Option<u8>,
+}
+
+impl Default for NoteMap {
+    fn default() -> Self {
+        Self {
+            note: 36,
+            source: Source::Onset,
+            threshold: 0.5,
+            length_ms: 100.0,
+            velocity: None,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Default)]
+struct NoteState {
+    above: bool,
+    /// seconds until note-off; the note is sounding while positive
+    left: f32,
+}
+
+/// Turns analysis frames into channel messages for the current preset.
+struct Mapper {
+    params: MidiParams,
+    last_cc: Vec<Option<u8>>,
+    notes: Vec<NoteState>,
+}
+
+impl Mapper {
+    fn new(params: &MidiParams) -> Self {
+        Self {
+            params: params.clone(),
+            last_cc: vec![None; params.cc.len()],
+            notes: vec![NoteState::default(); params.notes.len()],
+        }
+    }
+
+    fn status(&self, kind: u8) -> u8 {
+        kind | (self.params.channel.clamp(1, 16) - 1)
+    }
+
+    /// Switches mappings, releasing whatever the old ones left sounding.
+    fn apply(&mut self, params: &MidiParams, out: &mut Vec<[u8; 3]>) {
+        if *params != self.params {
+            self.release_all(out);
+            *self = Self::new(params);
+        }
+    }
+
+    fn release_all(&mut self, out: &mut Vec<[u8; 3]>) {
+        let off = self.status(NOTE_OFF);
+        for (map, state) in self.params.notes.iter().zip(&mut self.notes) {
+            if state.left > 0.0 {
+                out.push([off, map.note, 0]);
+                state.left = 0.0;
+            }
+        }
+    }
+
+    fn frame(
+        &mut self,
+        frame: &AnalysisFrame,
+        levels: &LevelMeter,
+        dt: f32,
+        out: &mut Vec<[u8; 3]>,
+    ) {
+        let cc_status = self.status(CONTROL_CHANGE);
+        for (map, last) in self.params.cc.iter().zip(&mut self.last_cc) {
+            let v = map.source.value(frame, levels, frame.onset, 0, 1) * map.gain;
+            let v = seven_bit(v);
+            if *last != Some(v) {
+                out.push([cc_status, map.cc, v]);
+                *last = Some(v);
+            }
+        }
+
+        self.hold(dt, out);
+        let (on, off) = (self.status(NOTE_ON), self.status(NOTE_OFF));
+        for (map, state) in self.params.notes.iter().zip(&mut self.notes) {
+            let v = map.source.value(frame, levels, frame.onset, 0, 1);
+            let above = v >= map.threshold;
+            if above && !state.above {
+                if state.left > 0.0 {
+                    out.push([off, map.note, 0]);
+                }
+                let velocity = map.velocity.unwrap_or(seven_bit(v)).clamp(1, 127);
+                out.push([on, map.note, velocity]);
+                state.left = map.length_ms / 1000.0;
+            }
+            state.above = above;
+        }
+    }
+
+    /// Lets time pass without new analysis, ending notes that are due.
+    fn hold(&mut self, dt: f32, out: &mut Vec<[u8; 3]>) {
+        let off = self.status(NOTE_OFF);
+        for (map, state) in self.params.notes.iter().zip(&mut self.notes) {
+            if state.left > 0.0 {
+                state.left -= dt;
+                if state.left <= 0.0 {
+                    out.push([off, map.note, 0]);
+                }
+            }
+        }
+    }
+}
+
+fn seven_bit(v: f32) -> u8 {
+    (v.clamp(0.0, 1.0) * 127.0).round() as u8
+}
+
+/// MIDI clock timing, polled by the clock thread with the current time.
+struct Clock {
+    /// when the next tick is due, once started
+    next: Option<f64>,
+    bpm: f32,
+}
+
+impl Clock {
+    const PPQN: f64 = 24.0;
+
+    fn new() -> Self {
+        Self {
+            next: None,
+            bpm: 0.0,
+        }
+    }
+
+    /// Bytes due at `now` (seconds) and when to poll again. The clock
+    /// starts with the first tempo and keeps the last one if it's lost, since
+    /// receivers handle a steady clock better than stops and starts.
+    fn poll(&mut self, now: f64, bpm: Option<f32>, out: &mut Vec<u8>) -> f64 {
+        if let Some(bpm) = bpm {
+            self.bpm = bpm;
+        }
+        if self.bpm <= 0.0 {
+            return now + 0.01;
+        }
+        let interval = 60.0 / (self.bpm as f64 * Self::PPQN);
+        let next = self.next.get_or_insert_with(|| {
+            out.push(START);
+            now
+        });
+        // after a stall, pick up from now rather than sending a burst
+        if now - *next > 0.1 {
+            *next = now;
+        }
+        while *next <= now {
+            out.push(CLOCK);
+            *next += interval;
+        }
+        *next
+    }
+
+    fn stop(&mut self, out: &mut Vec<u8>) {
+        if self.next.take().is_some() {
+            out.push(STOP);
+        }
+    }
+}
+
+/// What the frame loop tells the clock thread.
+struct ClockState {
+    bpm: Option<f32>,
+    enabled: bool,
+    running: bool,
+}
+
+/// Sends the current preset's `[midi]` mappings, with the clock on a thread
+/// of its own so ticks stay even whatever the frame rate.
+pub struct MidiOutput {
+    port: Arc<Mutex<MidiOut>>,
+    mapper: Mapper,
+    state: Arc<Mutex<ClockState>>,
+    clock: Option<JoinHandle<()>>,
+    scratch: Vec<[u8; 3]>,
+}
+
+impl MidiOutput {
+    /// Opens a virtual output, or connects to the first existing output
+    /// whose name contains `port`.
+    pub fn open(port: Option<&str>, preset: &Preset) -> anyhow::Result<Self> {
+        let port = Arc::new(Mutex::new(MidiOut::open("audio_visualizer", port)?));
+        let state = Arc::new(Mutex::new(ClockState {
+            bpm: None,
+            enabled: preset.midi.clock,
+            running: true,
+        }));
+        let clock = {
+            let (port, state) = (port.clone(), state.clone());
+            std::thread::spawn(move || run_clock(&port, &state))
+        };
+        Ok(Self {
+            port,
+            mapper: Mapper::new(&preset.midi),
+            state,
+            clock: Some(clock),
+            scratch: Vec::new(),
+        })
+    }
+
+    pub fn apply_preset(&mut self, preset: &Preset) {
+        self.mapper.apply(&preset.midi, &mut self.scratch);
+        self.state.lock().unwrap().enabled = preset.midi.clock;
+        self.flush();
+    }
+
+    /// Called once per analysis frame, `dt` seconds after the previous one.
+    pub fn frame(&mut self, frame: &AnalysisFrame, levels: &LevelMeter, bpm: Option<f32>, dt: f32) {
+        self.state.lock().unwrap().bpm = bpm;
+        self.mapper.frame(frame, levels, dt, &mut self.scratch);
+        self.flush();
+    }
+
+    /// While the analysis is frozen: held notes still end on time.
+    pub fn hold(&mut self, dt: f32) {
+        self.mapper.hold(dt, &mut self.scratch);
+        self.flush();
+    }
+
+    fn flush(&mut self) {
+        if self.scratch.is_empty() {
+            return;
+        }
+        let mut port = self.port.lock().unwrap();
+        for message in self.scratch.drain(..) {
+            port.send(&message);
+        }
+    }
+}
+
+impl Drop for MidiOutput {
+    /// Leaves receivers stopped with no notes hanging.
+    fn drop(&mut self) {
+        self.mapper.release_all(&mut self.scratch);
+        self.flush();
+        self.state.lock().unwrap().running = false;
+        if let Some(clock) = self.clock.take() {
+            let _ = clock.join();
+        }
+    }
+}
+
+fn run_clock(port: &Mutex<MidiOut>, state: &Mutex<ClockState>) {
+    let started = Instant::now();
+    let mut clock = Clock::new();
+    let mut out = Vec::new();
+    loop {
+        let now = started.elapsed().as_secs_f64();
+        let (bpm, enabled, running) = {
+            let s = state.lock().unwrap();
+            (s.bpm, s.enabled, s.running)
+        };
+        let next = if running && enabled {
+            clock.poll(now, bpm, &mut out)
+        } else {
+            clock.stop(&mut out);
+            now + 0.01
+        };
+        if !out.is_empty() {
+            let mut port = port.lock().unwrap();
+            for byte in out.drain(..) {
+                port.send(&[byte]);
+            }
+        }
+        if !running {
+            return;
+        }
+        // wake at least every 10 ms to notice tempo changes and shutdown
+        let wait = (next - started.elapsed().as_secs_f64()).clamp(0.0, 0.01);
+        std::thread::sleep(Duration::from_secs_f64(wait));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn onset(on: bool) -> AnalysisFrame {
+        AnalysisFrame {
+            bands: vec![0.5; 120],
+            bass_smooth: 1.0,
+            onset: on,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn ccs_send_changes_and_notes_follow_onsets() {
+        let mut params = MidiParams {
+            channel: 10,
+            ..Default::default()
+        };
+        params.notes[0].velocity = Some(100);
+        let mut mapper = Mapper::new(&params);
+        let levels = LevelMeter::new();
+        let mut out = Vec::new();
+
+        mapper.frame(&onset(true), &levels, 0.016, &mut out);
+        assert_eq!(
+            out,
+            [
+                [0xb9, 20, 64],
+                [0xb9, 21, 64],
+                [0xb9, 22, 64],
+                [0xb9, 23, 127],
+                [0x99, 36, 100],
+            ]
+        );
+
+        // nothing changed: no CCs, and the note is still held
+        out.clear();
+        mapper.frame(&onset(false), &levels, 0.05, &mut out);
+        assert!(out.is_empty());
+        mapper.frame(&onset(false), &levels, 0.06, &mut out);
+        assert_eq!(out, [[0x89, 36, 0]]);
+
+        // a preset switch releases sounding notes
+        out.clear();
+        mapper.frame(&onset(true), &levels, 0.016, &mut out);
+        out.clear();
+        mapper.apply(&MidiParams::default(), &mut out);
+        assert_eq!(out, [[0x89, 36, 0]]);
+    }
+
+    #[test]
+    fn clock_ticks_24_times_a_beat() {
+        let mut clock = Clock::new();
+        let mut out = Vec::new();
+        assert!(clock.poll(0.0, None, &mut out) > 0.0);
+        assert!(out.is_empty());
+
+        let mut now = 0.0;
+        while now < 1.0 {
+            clock.poll(now, Some(120.0), &mut out);
+            now += 0.001;
+        }
+        assert_eq!(out[0], START);
+        // two beats in a second at 120 BPM
+        assert_eq!(out.iter().filter(|&&b| b == CLOCK).count(), 48);
+
+        // losing the tempo keeps the last one going
+        out.clear();
+        while now < 1.5 {
+            clock.poll(now, None, &mut out);
+            now += 0.001;
+        }
+        assert_eq!(out.len(), 24);
+        out.clear();
+        clock.stop(&mut out);
+        assert_eq!(out, [STOP]);
+    }
+}
@@ -0,0 +1,196 @@
+use super::CONTROL_CHANGE;
+use anyhow::{anyhow, bail};
+use midir::{Ignore, MidiInputConnection, MidiOutputConnection};
+use std::sync::mpsc::{Receiver, channel};
+
+/// An open MIDI output: a virtual port others subscribe to, or a connection
+/// to an existing one.
+pub struct MidiOut {
+    conn: MidiOutputConnection,
+    failing: bool,
+}
+
+impl MidiOut {
+    /// Connects to the first output whose name contains `port`, or without
+    /// one opens a virtual port called `name` (an ALSA sequencer client on
+    /// Linux) for synths and VJ tools to connect to.
+    pub fn open(name: &str, port: Option<&str>) -> anyhow::Result<Self> {
+        let midi = midir::MidiOutput::new(name).map_err(|e| anyhow!("Can't open MIDI: {e}"))?;
+        let conn = match port {
+            Some(wanted) => {
+                let ports = midi.ports();
+                let names: Vec<String> = ports
+                    .iter()
+                    .map(|p| midi.port_name(p).unwrap_or_default())
+                    .collect();
+                let i = find(&names, wanted, "output")?;
+                eprintln!("[midi] connecting to {}", names[i]);
+                midi.connect(&ports[i], name)
+                    .map_err(|e| anyhow!("Can't connect to MIDI output '{}': {e}", names[i]))?
+            }
+            None => {
+                let conn = virtual_output(midi, name)?;
+                eprintln!("[midi] opened virtual output '{name}'");
+                conn
+            }
+        };
+        Ok(Self {
+            conn,
+            failing: false,
+        })
+    }
+
+    /// Sends one message, reporting the first failure of a run rather than
+    /// one per message.
+    pub fn send(&mut self, message: &[u8]) {
+        match self.conn.send(message) {
+            Ok(()) => self.failing = false,
+            Err(e) if !self.failing => {
+                eprintln!("[midi] send failed: {e}");
+                self.failing = true;
+            }
+            Err(_) => {}
+        }
+    }
+}
+
+/// A control change as received.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Cc {
+    /// 1–16
+    pub channel: u8,
+    pub cc: u8,
+    pub value: u8,
+}
+
+impl Cc {
+    fn parse(message: &[u8]) -> Option<Self> {
+        match *message {
+            [status, cc, value] if status & 0xf0 == CONTROL_CHANGE => Some(Self {
+                channel: (status & 0x0f) + 1,
+                cc,
+                value,
+            }),
+            _ => None,
+        }
+    }
+}
+
+/// An open MIDI input, collecting control changes for the frame loop to
+/// poll. Everything else a controller sends is ignored.
+pub struct MidiIn {
+    _conn: MidiInputConnection<()>,
+    received: Receiver<Cc>,
+}
+
+impl MidiIn {
+    /// Connects to the first input whose name contains `port`, or without
+    /// one opens a virtual port called `name` for controllers to be
+    /// connected to.
+    pub fn open(name: &str, port: Option<&str>) -> anyhow::Result<Self> {
+        let mut midi = midir::MidiInput::new(name).map_err(|e| anyhow!("Can't open MIDI: {e}"))?;
+        midi.ignore(Ignore::All);
+        let (tx, received) = channel();
+        let callback = move |_: u64, message: &[u8], _: &mut ()| {
+            if let Some(cc) = Cc::parse(message) {
+                let _ = tx.send(cc);
+            }
+        };
+        let conn = match port {
+            Some(wanted) => {
+                let ports = midi.ports();
+                let names: Vec<String> = ports
+                    .iter()
+                    .map(|p| midi.port_name(p).unwrap_or_default())
+                    .collect();
+                let i = find(&names, wanted, "input")?;
+                eprintln!("[midi] listening to {}", names[i]);
+                midi.connect(&ports[i], name, callback, ())
+                    .map_err(|e| anyhow!("Can't connect to MIDI input '{}': {e}", names[i]))?
+            }
+            None => {
+                let conn = virtual_input(midi, name, callback)?;
+                eprintln!("[midi] opened virtual input '{name}'");
+                conn
+            }
+        };
+        Ok(Self {
+            _conn: conn,
+            received,
+        })
+    }
+
+    /// Every control change since the last poll, oldest first.
+    pub fn poll(&self) -> Vec<Cc> {
+        self.received.try_iter().collect()
+    }
+}
+
+/// The first port whose name contains `wanted`.
+fn find(names: &[String], wanted: &str, kind: &str) -> anyhow::Result<usize> {
+    match names.iter().position(|n| n.contains(wanted)) {
+        Some(i) => Ok(i),
+        None if names.is_empty() => bail!("No MIDI {kind} matching '{wanted}'; there are none"),
+        None => bail!(
+            "No MIDI {kind} matching '{wanted}'; available: {}",
+            names.join(", ")
+        ),
+    }
+}
+
+#[cfg(unix)]
+fn virtual_input<F>(
+    midi: midir::MidiInput,
+    name: &str,
+    callback: F,
+) -> anyhow::Result<MidiInputConnection<()>>
+where
+    F: FnMut(u64, &[u8], &mut ()) + Send + 'static,
+{
+    use midir::os::unix::VirtualInput;
+    midi.create_virtual(name, callback, ())
+        .map_err(|e| anyhow!("Can't create virtual MIDI input '{name}': {e}"))
+}
+
+#[cfg(not(unix))]
+fn virtual_input<F>(
+    _midi: midir::MidiInput,
+    _name: &str,
+    _callback: F,
+) -> anyhow::Result<MidiInputConnection<()>>
+where
+    F: FnMut(u64, &[u8], &mut ()) + Send + 'static,
+{
+    bail!("Virtual MIDI ports need ALSA or CoreMIDI; name an existing input to connect to instead")
+}
+
+#[cfg(unix)]
+fn virtual_output(midi: midir::MidiOutput, name: &str) -> anyhow::Result<MidiOutputConnection> {
+    use midir::os::unix::VirtualOutput;
+    midi.create_virtual(name)
+        .map_err(|e| anyhow!("Can't create virtual MIDI output '{name}': {e}"))
+}
+
+#[cfg(not(unix))]
+fn virtual_output(_midi: midir::MidiOutput, _name: &str) -> anyhow::Result<MidiOutputConnection> {
+    bail!("Virtual MIDI ports need ALSA or CoreMIDI; name an existing output to connect to instead")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn only_control_changes_are_kept() {
+        assert_eq!(
+            Cc::parse(&[0xb3, 74, 100]),
+            Some(Cc {
+                channel: 4,
+                cc: 74,
+                value: 100
+            })
+        );
+        assert_eq!(Cc::parse(&[0x90, 60, 100]), None);
+        assert_eq!(Cc::parse(&[0xf8]), None);
+    }
+}
@@ -0,0 +1,215 @@
+//! Just enough of OSC 1.0 to publish analysis data and take remote control:
+//! messages with int, float and string arguments, optionally in bundles.
+
+mod receiver;
+mod sender;
+
+use anyhow::{Context, bail, ensure};
+
+pub use receiver::OscReceiver;
+pub use sender::{OscSender, OscTarget};
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Arg {
+    Int(i32),
+    Float(f32),
+    Str(String),
+}
+
+impl Arg {
+    /// Numbers of either kind; control surfaces tend to send floats for everything.
+    pub fn as_f32(&self) -> Option<f32> {
+        match self {
+            Self::Int(v) => Some(*v as f32),
+            Self::Float(v) => Some(*v),
+            Self::Str(_) => None,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Message {
+    pub addr: String,
+    pub args: Vec<Arg>,
+}
+
+impl Message {
+    pub fn new(addr: impl Into<String>, args: Vec<Arg>) -> Self {
+        Self {
+            addr: addr.into(),
+            args,
+        }
+    }
+
+    pub fn encode(&self, out: &mut Vec<u8>) {
+        write_str(out, &self.addr);
+        let mut tags = String::from(",");
+        for arg in &self.args {
+            tags.push(match arg {
+                Arg::Int(_) => 'i',
+                Arg::Float(_) => 'f',
+                Arg::Str(_) => 's',
+            });
+        }
+        write_str(out, &tags);
+        for arg in &self.args {
+            match arg {
+                Arg::Int(v) => out.extend(v.to_be_bytes()),
+                Arg::Float(v) => out.extend(v.to_be_bytes()),
+                Arg::Str(s) => write_str(out, s),
+            }
+        }
+    }
+
+    fn decode(mut bytes: &[u8]) -> anyhow::Result<Self> {
+        let addr = read_str(&mut bytes).context("bad address")?;
+        ensure!(addr.starts_with('/'), "bad address '{addr}'");
+        // a message without a type tag string is allowed to mean "no arguments"
+        let tags = if bytes.is_empty() {
+            ",".to_string()
+        } else {
+            read_str(&mut bytes).context("bad type tags")?
+        };
+        let Some(tags) = tags.strip_prefix(',') else {
+            bail!("bad type tags '{tags}'");
+        };
+        let mut args = Vec::new();
+        for tag in tags.chars() {
+            args.push(match tag {
+                'i' => Arg::Int(i32::from_be_bytes(take(&mut bytes)?)),
+                'f' => Arg::Float(f32::from_be_bytes(take(&mut bytes)?)),
+                'd' => Arg::Float(f64::from_be_bytes(take(&mut bytes)?) as f32),
+                's' => Arg::Str(read_str(&mut bytes)?),
+                'T' => Arg::Int(1),
+                'F' => Arg::Int(0),
+                _ => bail!("unsupported argument type '{tag}' in {addr}"),
+            });
+        }
+        Ok(Self { addr, args })
+    }
+}
+
+/// Checks an address prefix such as `/audio` and drops any trailing `/`.
+pub fn parse_prefix(prefix: &str) -> anyhow::Result<String> {
+    ensure!(prefix.starts_with('/'), "OSC addresses start with '/'");
+    if let Some(c) = prefix
+        .chars()
+        .find(|c| c.is_whitespace() || "#*,?[]{}".contains(*c))
+    {
+        bail!("'{c}' isn't allowed in an OSC address");
+    }
+    Ok(prefix.trim_end_matches('/').to_string())
+}
+
+/// The messages in a packet, with bundles (nested or not) flattened.
+pub fn decode(packet: &[u8]) -> anyhow::Result<Vec<Message>> {
+    let mut out = Vec::new();
+    decode_into(packet, &mut out)?;
+    Ok(out)
+}
+
+fn decode_into(mut packet: &[u8], out: &mut Vec<Message>) -> anyhow::Result<()> {
+    if !packet.starts_with(b"#bundle\0") {
+        out.push(Message::decode(packet)?);
+        return Ok(());
+    }
+    // timetags are ignored: everything applies on arrival
+    packet = &packet[16.min(packet.len())..];
+    while !packet.is_empty() {
+        let len = u32::from_be_bytes(take(&mut packet)?) as usize;
+        ensure!(len <= packet.len(), "bundle element runs past the packet");
+        let (element, rest) = packet.split_at(len);
+        decode_into(element, out)?;
+        packet = rest;
+    }
+    Ok(())
+}
+
+/// All `messages` in one bundle, timetagged "immediately".
+pub fn encode_bundle(messages: &[Message]) -> Vec<u8> {
+    let mut out = Vec::new();
+    write_str(&mut out, "#bundle");
+    out.extend(1u64.to_be_bytes());
+    let mut element = Vec::new();
+    for msg in messages {
+        element.clear();
+        msg.encode(&mut element);
+        out.extend((element.len() as u32).to_be_bytes());
+        out.extend(&element);
+    }
+    out
+}
+
+/// OSC strings are null terminated and padded to a multiple of four bytes.
+fn write_str(out: &mut Vec<u8>, s: &str) {
+    out.extend(s.as_bytes());
+    let pad = 4 - s.len() % 4;
+    out.extend(std::iter::repeat_n(0, pad));
+}
+
+fn read_str(bytes: &mut &[u8]) -> anyhow::Result<String> {
+    let end = bytes
+        .iter()
+        .position(|&b| b == 0)
+        .context("unterminated string")?;
+    let s = std::str::from_utf8(&bytes[..end])?.to_string();
+    // skip the terminator and padding up to the next four-byte boundary
+    let next = ((end / 4 + 1) * 4).min(bytes.len());
+    *bytes = &bytes[next..];
+    Ok(s)
+}
+
+fn take<const N: usize>(bytes: &mut &[u8]) -> anyhow::Result<[u8; N]> {
+    ensure!(bytes.len() >= N, "packet too short");
+    let (head, rest) = bytes.split_at(N);
+    *bytes = rest;
+    Ok(head.try_into().unwrap())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn encodes_messages_and_bundles() {
+        let mut out = Vec::new();
+        Message::new("/a", vec![Arg::Int(1), Arg::Float(0.5)]).encode(&mut out);
+        let mut expected = b"/a\0\0,if\0".to_vec();
+        expected.extend(1i32.to_be_bytes());
+        expected.extend(0.5f32.to_be_bytes());
+        assert_eq!(out, expected);
+
+        let bundle = encode_bundle(&[Message::new("/b", vec![])]);
+        assert_eq!(&bundle[..8], b"#bundle\0");
+        assert_eq!(&bundle[8..16], &1u64.to_be_bytes());
+        assert_eq!(&bundle[16..20], &8u32.to_be_bytes());
+        assert_eq!(&bundle[20..], b"/b\0\0,\0\0\0");
+    }
+
+    #[test]
+    fn prefixes_are_checked() {
+        assert_eq!(parse_prefix("/audio/").unwrap(), "/audio");
+        assert_eq!(parse_prefix("/").unwrap(), "");
+        assert!(parse_prefix("viz").is_err());
+        assert!(parse_prefix("/my viz").is_err());
+    }
+
+    #[test]
+    fn decodes_what_it_encodes() {
+        let a = Message::new("/view", vec![Arg::Str("bars".into())]);
+        let b = Message::new("/mix", vec![Arg::Float(0.25), Arg::Int(-3)]);
+        let mut packet = Vec::new();
+        a.encode(&mut packet);
+        assert_eq!(decode(&packet).unwrap(), std::slice::from_ref(&a));
+
+        // a bundle inside a bundle comes out flat
+        let inner = encode_bundle(std::slice::from_ref(&b));
+        let mut outer = encode_bundle(std::slice::from_ref(&a));
+        outer.extend((inner.len() as u32).to_be_bytes());
+        outer.extend(&inner);
+        assert_eq!(decode(&outer).unwrap(), [a, b]);
+
+        assert!(decode(b"/x\0\0,f\0\0").is_err());
+        assert!(decode(b"nope").is_err());
+    }
+}
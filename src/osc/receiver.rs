@@ -0,0 +1,98 @@
+use super::{Message, decode};
+use anyhow::Context;
+use std::net::{SocketAddr, UdpSocket};
+
+/// Listens for OSC on a UDP port, polled once per frame.
+pub struct OscReceiver {
+    socket: UdpSocket,
+}
+
+impl OscReceiver {
+    /// `addr` is `host:port`, or just a port to listen on every interface.
+    pub fn bind(addr: &str) -> anyhow::Result<Self> {
+        let addr = match addr.parse::<u16>() {
+            Ok(port) => format!("0.0.0.0:{port}"),
+            Err(_) => addr.to_string(),
+        };
+        let socket =
+            UdpSocket::bind(&addr).with_context(|| format!("Can't listen for OSC on {addr}"))?;
+        socket.set_nonblocking(true)?;
+        eprintln!("[osc] listening on {}", socket.local_addr()?);
+        Ok(Self { socket })
+    }
+
+    /// Every message that arrived since the last poll, with who sent it.
+    /// Malformed packets are logged and skipped.
+    pub fn poll(&self) -> Vec<(Message, SocketAddr)> {
+        let mut out = Vec::new();
+        let mut buf = [0u8; 65536];
+        loop {
+            match self.socket.recv_from(&mut buf) {
+                Ok((n, from)) => match decode(&buf[..n]) {
+                    Ok(messages) => out.extend(messages.into_iter().map(|m| (m, from))),
+                    Err(e) => eprintln!("[osc] bad packet from {from}: {e:#}"),
+                },
+                Err(e) if e.kind() == std::io::ErrorKind::WouldBlock => break,
+                // e.g. ICMP "port unreachable" for an earlier reply; not fatal
+                Err(e) => {
+                    eprintln!("[osc] receive failed: {e}");
+                    break;
+                }
+            }
+        }
+        out
+    }
+
+    /// Sends `messages` back to `to`, one datagram each, from the listening port.
+    pub fn reply(&self, to: SocketAddr, messages: &[Message]) {
+        let mut buf = Vec::new();
+        for msg in messages {
+            buf.clear();
+            msg.encode(&mut buf);
+            if let Err(e) = self.socket.send_to(&buf, to) {
+                eprintln!("[osc] reply to {to} failed: {e}");
+                return;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::osc::Arg;
+    use std::time::Duration;
+
+    #[test]
+    fn receives_and_replies() {
+        let rx = OscReceiver::bind("127.0.0.1:0").unwrap();
+        let port = rx.socket.local_addr().unwrap();
+        let client = UdpSocket::bind("127.0.0.1:0").unwrap();
+        client
+            .set_read_timeout(Some(Duration::from_secs(2)))
+            .unwrap();
+
+        let mut packet = Vec::new();
+        Message::new("/audio/query", vec![]).encode(&mut packet);
+        client.send_to(&packet, port).unwrap();
+        client.send_to(b"garbage", port).unwrap();
+
+        let mut got = Vec::new();
+        for _ in 0..100 {
+            got.extend(rx.poll());
+            if !got.is_empty() {
+                break;
+            }
+            std::thread::sleep(Duration::from_millis(10));
+        }
+        let (msg, from) = &got[0];
+        assert_eq!(msg.addr, "/audio/query");
+        assert_eq!(*from, client.local_addr().unwrap());
+
+        rx.reply(*from, &[Message::new("/audio/gain", vec![Arg::Float(1.5)])]);
+        let mut buf = [0u8; 256];
+        let n = client.recv(&mut buf).unwrap();
+        let reply = decode(&buf[..n]).unwrap();
+        assert_eq!(reply[0].args, [Arg::Float(1.5)]);
+    }
+}
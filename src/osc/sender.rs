@@ -0,0 +1,231 @@
+use super::{Arg, Message, encode_bundle};
+use crate::analysis::AnalysisFrame;
+use crate::pace::Pacer;
+use anyhow::Context;
+use std::net::{SocketAddr, ToSocketAddrs, UdpSocket};
+
+/// Where and how to publish.
+#[derive(Clone, Debug)]
+pub struct OscTarget {
+    /// `host:port`
+    pub dest: String,
+    /// prepended to every address, e.g. `/audio` gives `/audio/bands`
+    pub prefix: String,
+    /// most packets per second; 0 sends every analysis frame
+    pub rate: f32,
+    /// one bundle per frame instead of one datagram per message
+    pub bundle: bool,
+}
+
+/// Publishes each analysis frame over UDP:
+///
+/// - `/bands f…`, one float per band
+/// - `/bass/fast f`, `/bass/smooth f`
+/// - `/onset i` (1 if there was an onset since the last packet) and `/onset/strength f`
+/// - `/centroid f`, `/flux f`
+/// - `/bpm f`, only while a tempo is known
+/// - `/section s i`, the kind of section and its number, once known
+pub struct OscSender {
+    socket: UdpSocket,
+    dest: SocketAddr,
+    target: OscTarget,
+    pacer: Pacer,
+    // onsets between packets aren't lost when rate limiting
+    onset: bool,
+    onset_strength: f32,
+    failing: bool,
+}
+
+impl OscSender {
+    pub fn new(target: OscTarget) -> anyhow::Result<Self> {
+        let dest = target
+            .dest
+            .to_socket_addrs()
+            .with_context(|| format!("Can't resolve OSC destination '{}'", target.dest))?
+            .next()
+            .with_context(|| format!("No address for OSC destination '{}'", target.dest))?;
+        let bind: SocketAddr = if dest.is_ipv4() {
+            ([0, 0, 0, 0], 0).into()
+        } else {
+            (std::net::Ipv6Addr::UNSPECIFIED, 0).into()
+        };
+        let socket = UdpSocket::bind(bind).context("Failed to open a UDP socket for OSC")?;
+        // never stall a frame on the network
+        socket.set_nonblocking(true)?;
+        eprintln!("[osc] sending to {dest} under '{}'", target.prefix);
+        Ok(Self {
+            socket,
+            dest,
+            pacer: Pacer::new(target.rate),
+            target,
+            onset: false,
+            onset_strength: 0.0,
+            failing: false,
+        })
+    }
+
+    /// Called once per analysis frame, `dt` seconds after the previous one.
+    pub fn frame(&mut self, frame: &AnalysisFrame, bpm: Option<f32>, dt: f32) {
+        self.onset |= frame.onset;
+        self.onset_strength = self.onset_strength.max(frame.onset_strength);
+        if !self.pacer.tick(dt) {
+            return;
+        }
+
+        let messages = self.messages(frame, bpm);
+        self.onset = false;
+        self.onset_strength = 0.0;
+
+        let result = if self.target.bundle {
+            self.send(&encode_bundle(&messages))
+        } else {
+            let mut buf = Vec::new();
+            messages.iter().try_for_each(|msg| {
+                buf.clear();
+                msg.encode(&mut buf);
+                self.send(&buf)
+            })
+        };
+        // say so once, not sixty times a second
+        match result {
+            Err(e) if !self.failing => {
+                eprintln!("[osc] send to {} failed: {e}", self.dest);
+                self.failing = true;
+            }
+            Ok(()) if self.failing => {
+                eprintln!("[osc] sending again");
+                self.failing = false;
+            }
+            _ => {}
+        }
+    }
+
+    fn messages(&self, frame: &AnalysisFrame, bpm: Option<f32>) -> Vec<Message> {
+        let addr = |name: &str| format!("{}/{name}", self.target.prefix);
+        let float = |name: &str, v: f32| Message::new(addr(name), vec![Arg::Float(v)]);
+        let mut messages = vec![
+            Message::new(
+                addr("bands"),
+                frame.bands.iter().map(|&b| Arg::Float(b)).collect(),
+            ),
+            float("bass/fast", frame.bass_fast),
+            float("bass/smooth", frame.bass_smooth),
+            Message::new(addr("onset"), vec![Arg::Int(self.onset as i32)]),
+            float("onset/strength", self.onset_strength),
+            float("centroid", frame.centroid),
+            float("flux", frame.flux),
+        ];
+        if let Some(bpm) = bpm {
+            messages.push(float("bpm", bpm));
+        }
+        if let Some(section) = frame.section {
+            messages.push(Message::new(
+                addr("section"),
+                vec![
+                    Arg::Str(section.kind.name().into()),
+                    Arg::Int(section.index as i32),
+                ],
+            ));
+        }
+        messages
+    }
+
+    fn send(&self, packet: &[u8]) -> std::io::Result<()> {
+        match self.socket.send_to(packet, self.dest) {
+            // a full socket buffer just drops this packet
+            Err(e) if e.kind() == std::io::ErrorKind::WouldBlock => Ok(()),
+            other => other.map(|_| ()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    fn listener() -> UdpSocket {
+        let socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+        socket
+            .set_read_timeout(Some(Duration::from_secs(2)))
+            .unwrap();
+        socket
+    }
+
+    fn sender(listener: &UdpSocket, rate: f32, bundle: bool) -> OscSender {
+        OscSender::new(OscTarget {
+            dest: listener.local_addr().unwrap().to_string(),
+            prefix: "/viz".into(),
+            rate,
+            bundle,
+        })
+        .unwrap()
+    }
+
+    /// The address pattern a packet starts with.
+    fn address(packet: &[u8]) -> String {
+        let end = packet.iter().position(|&b| b == 0).unwrap();
+        String::from_utf8(packet[..end].to_vec()).unwrap()
+    }
+
+    fn frame() -> AnalysisFrame {
+        AnalysisFrame {
+            bands: vec![0.1, 0.2, 0.3],
+            bass_fast: 0.5,
+            onset: true,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn sends_one_datagram_per_message() {
+        let rx = listener();
+        let mut osc = sender(&rx, 0.0, false);
+        osc.frame(&frame(), Some(120.0), 0.016);
+
+        let mut buf = [0u8; 1024];
+        let mut addrs = Vec::new();
+        for _ in 0..8 {
+            let n = rx.recv(&mut buf).unwrap();
+            addrs.push(address(&buf[..n]));
+            if addrs.last().unwrap() == "/viz/bands" {
+                // 3 floats after the address and ",fff" tags
+                assert_eq!(n, 12 + 8 + 12);
+            }
+        }
+        addrs.sort();
+        assert_eq!(
+            addrs,
+            [
+                "/viz/bands",
+                "/viz/bass/fast",
+                "/viz/bass/smooth",
+                "/viz/bpm",
+                "/viz/centroid",
+                "/viz/flux",
+                "/viz/onset",
+                "/viz/onset/strength",
+            ]
+        );
+    }
+
+    #[test]
+    fn bundles_and_rate_limits() {
+        let rx = listener();
+        let mut osc = sender(&rx, 10.0, true);
+        // 30 frames at 60 fps is half a second: the first frame plus one
+        // packet per 100 ms
+        for _ in 0..30 {
+            osc.frame(&frame(), None, 1.0 / 60.0);
+        }
+        rx.set_read_timeout(Some(Duration::from_millis(200)))
+            .unwrap();
+        let mut buf = [0u8; 1024];
+        let mut packets = 0;
+        while let Ok(n) = rx.recv(&mut buf) {
+            assert_eq!(address(&buf[..n]), "#bundle");
+            packets += 1;
+        }
+        assert!((4..=6).contains(&packets), "{packets} packets");
+    }
+}
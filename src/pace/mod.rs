@@ -0,0 +1,66 @@
+/// Spaces sends `1 / rate` seconds apart on average, whatever the frame rate.
+///
+/// Time left over after a send counts towards the next one, so 40 sends per
+/// second at 60 fps alternates one and two frames apart instead of settling
+/// on every second frame. After a stall it sends once, not a burst to catch up.
+pub struct Pacer {
+    /// seconds between sends; 0 sends every frame
+    period: f32,
+    since: f32,
+}
+
+impl Pacer {
+    /// `rate` sends per second; 0 or less sends every frame.
+    pub fn new(rate: f32) -> Self {
+        Self {
+            period: if rate > 0.0 { 1.0 / rate } else { 0.0 },
+            // the first frame sends right away
+            since: f32::INFINITY,
+        }
+    }
+
+    /// Moves on `dt` seconds and says whether a send is due.
+    pub fn tick(&mut self, dt: f32) -> bool {
+        self.since += dt;
+        if self.since < self.period {
+            return false;
+        }
+        self.since -= self.period;
+        // a whole period over means a stall, or frames slower than the rate:
+        // start afresh rather than catch up
+        if self.since >= self.period {
+            self.since = 0.0;
+        }
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sends(pacer: &mut Pacer, frames: usize, dt: f32) -> usize {
+        (0..frames).filter(|_| pacer.tick(dt)).count()
+    }
+
+    #[test]
+    fn holds_the_rate_between_frames() {
+        // 40 a second doesn't divide 60 fps; a second later it's still 40
+        let mut pacer = Pacer::new(40.0);
+        assert_eq!(sends(&mut pacer, 60, 1.0 / 60.0), 40);
+        assert_eq!(sends(&mut pacer, 600, 1.0 / 60.0), 400);
+        // faster than the frames is every frame
+        assert_eq!(sends(&mut Pacer::new(100.0), 60, 1.0 / 60.0), 60);
+        assert_eq!(sends(&mut Pacer::new(0.0), 60, 1.0 / 60.0), 60);
+    }
+
+    #[test]
+    fn a_stall_does_not_cause_a_burst() {
+        let mut pacer = Pacer::new(10.0);
+        sends(&mut pacer, 60, 1.0 / 60.0);
+        assert!(pacer.tick(2.0));
+        // then a period's wait, as after any other send
+        assert_eq!(sends(&mut pacer, 5, 1.0 / 60.0), 0);
+        assert_eq!(sends(&mut pacer, 52, 1.0 / 60.0), 9);
+    }
+}
@@ -0,0 +1,189 @@
+use super::{Format, Preset};
+use std::path::{Path, PathBuf};
+
+/// Looks that ship inside the binary, so they work from any directory.
+pub const BUNDLED: &[(&str, &str)] = &[
+    ("neon.toml", include_str!("../../presets/neon.toml")),
+    ("ambient.toml", include_str!("../../presets/ambient.toml")),
+    ("club.json", include_str!("../../presets/club.json")),
+];
+
+pub struct Entry {
+    pub preset: Preset,
+    pub source: Option<PathBuf>, // None = built in
+}
+
+/// All known presets in cycling order, with one of them current.
+///
+/// Order: `default` (the built-in values), the bundled looks, then files from
+/// the preset directories sorted by name. A file whose name matches an earlier
+/// preset replaces it in place, so a local copy can shadow a bundled look.
+pub struct PresetLibrary {
+    entries: Vec<Entry>,
+    current: usize,
+}
+
+impl PresetLibrary {
+    pub fn load(dirs: &[PathBuf]) -> Self {
+        let mut lib = Self {
+            entries: vec![Entry {
+                preset: Preset {
+                    name: "default".into(),
+                    ..Default::default()
+                },
+                source: None,
+            }],
+            current: 0,
+        };
+        for (file, text) in BUNDLED {
+            let path = Path::new(file);
+            // covered by a test; a broken bundled preset is a build mistake
+            let mut preset = Preset::parse(text, Format::from_path(path))
+                .unwrap_or_else(|e| panic!("bundled preset {file} is invalid: {e:#}"));
+            if preset.name.is_empty() {
+                preset.name = super::stem(path);
+            }
+            lib.insert(Entry {
+                preset,
+                source: None,
+            });
+        }
+        for dir in dirs {
+            lib.load_dir(dir);
+        }
+        lib
+    }
+
+    /// Adds every `.toml` and `.json` file in `dir`. Missing directories are
+    /// fine; broken files are reported and skipped.
+    fn load_dir(&mut self, dir: &Path) {
+        let Ok(read) = std::fs::read_dir(dir) else {
+            return;
+        };
+        let mut paths: Vec<PathBuf> = read
+            .filter_map(|e| e.ok().map(|e| e.path()))
+            .filter(|p| {
+                matches!(
+                    p.extension().and_then(|e| e.to_str()),
+                    Some("toml" | "json")
+                ) && !super::is_bindings(p)
+            })
+            .collect();
+        paths.sort();
+        for path in paths {
+            match Preset::load(&path) {
+                Ok(preset) => {
+                    self.insert(Entry {
+                        preset,
+                        source: Some(path),
+                    });
+                }
+                Err(e) => eprintln!("[preset] skipping: {e:#}"),
+            }
+        }
+    }
+
+    /// Adds `entry`, replacing any preset with the same name. Returns its index.
+    fn insert(&mut self, entry: Entry) -> usize {
+        match self.index_of(&entry.preset.name) {
+            Some(i) => {
+                self.entries[i] = entry;
+                i
+            }
+            None => {
+                self.entries.push(entry);
+                self.entries.len() - 1
+            }
+        }
+    }
+
+    pub fn entries(&self) -> &[Entry] {
+        &self.entries
+    }
+
+    pub fn index_of(&self, name: &str) -> Option<usize> {
+        self.entries.iter().position(|e| e.preset.name == name)
+    }
+
+    pub fn current_index(&self) -> usize {
+        self.current
+    }
+
+    pub fn current(&self) -> &Preset {
+        &self.entries[self.current].preset
+    }
+
+    /// The file behind the current preset, if it has one.
+    pub fn current_source(&self) -> Option<&Path> {
+        self.entries[self.current].source.as_deref()
+    }
+
+    /// Swaps in a freshly reloaded version of the current preset.
+    pub fn replace_current(&mut self, preset: Preset) {
+        self.entries[self.current].preset = preset;
+    }
+
+    /// Selects a preset by name, or loads it from a path when no preset has that
+    /// name (adding it to the library so it can be cycled back to).
+    pub fn select(&mut self, spec: &str) -> anyhow::Result<&Preset> {
+        self.current = self.resolve(spec)?;
+        Ok(self.current())
+    }
+
+    /// The index `select` would pick: a preset with that name, one already
+    /// loaded from that file, or the file loaded now.
+    pub fn resolve(&mut self, spec: &str) -> anyhow::Result<usize> {
+        if let Some(i) = self.index_of(spec) {
+            return Ok(i);
+        }
+        let path = PathBuf::from(spec);
+        if let Ok(want) = path.canonicalize()
+            && let Some(i) = self.entries.iter().position(|e| {
+                e.source
+                    .as_deref()
+                    .is_some_and(|s| s.canonicalize().is_ok_and(|s| s == want))
+            })
+        {
+            return Ok(i);
+        }
+        anyhow::ensure!(
+            path.is_file(),
+            "No preset named '{spec}' and no such file; available: {}",
+            self.entries
+                .iter()
+                .map(|e| e.preset.name.as_str())
+                .collect::<Vec<_>>()
+                .join(", ")
+        );
+        let preset = Preset::load(&path)?;
+        Ok(self.insert(Entry {
+            preset,
+            source: Some(path),
+        }))
+    }
+
+    /// Selects the preset at `index`, as `resolve` found it.
+    pub fn select_index(&mut self, index: usize) -> &Preset {
+        self.current = index;
+        self.current()
+    }
+
+    /// Moves `step` places through the list, wrapping around.
+    pub fn cycle(&mut self, step: isize) -> &Preset {
+        let n = self.entries.len() as isize;
+        self.current = (self.current as isize + step).rem_euclid(n) as usize;
+        self.current()
+    }
+}
+
+/// Where user presets live: `./presets` and the per-user config directory.
+pub fn default_dirs() -> Vec<PathBuf> {
+    let mut dirs = vec![PathBuf::from("presets")];
+    let config = std::env::var_os("XDG_CONFIG_HOME")
+        .map(PathBuf::from)
+        .or_else(|| std::env::var_os("HOME").map(|h| PathBuf::from(h).join(".config")));
+    if let Some(config) = config {
+        dirs.push(config.join("audio_visualizer").join("presets"));
+    }
+    dirs
+}
@@ -0,0 +1,592 @@
+pub mod library;
+pub mod watch;
+
+pub use library::PresetLibrary;
+pub use watch::PresetWatcher;
+
+use crate::analysis::{AnalyzerParams, SectionKind};
+use crate::hud::{self, HudParams};
+use crate::midi::{MidiInParams, MidiParams};
+use crate::visual::palette::{Palette, PaletteDef};
+use crate::visual::{
+    BarsParams, OscilloscopeParams, ParticleParams, RadialParams, SpectrogramParams,
+    VectorscopeParams,
+};
+use anyhow::{Context, bail, ensure};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+/// Every tunable analyzer and visualizer parameter, one section per component.
+///
+/// All fields are optional in the file; anything left out keeps its built-in
+/// default, so a preset only needs the values it changes. Unknown keys are an
+/// error rather than silently ignored, which catches typos.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Preset {
+    /// Shown in the title bar and preset list; defaults to the file name
+    pub name: String,
+    /// Seconds to crossfade when switching visualizers (0 = hard cut)
+    pub crossfade: f32,
+    /// Named palettes the sections below can refer to, besides the built-ins
+    pub palettes: BTreeMap<String, PaletteDef>,
+
+    pub analyzer: AnalyzerParams,
+    pub radial: RadialParams,
+    pub bars: BarsParams,
+    pub oscilloscope: OscilloscopeParams,
+    pub spectrogram: SpectrogramParams,
+    pub vectorscope: VectorscopeParams,
+    pub particles: ParticleParams,
+    pub hud: HudParams,
+    pub midi: MidiParams,
+    pub midi_in: MidiInParams,
+    pub sections: SectionLooks,
+}
+
+/// The `[sections]` table: presets to switch to as the song moves into each
+/// kind of section, by name or path. Switching keeps this table in charge
+/// until another preset is picked by hand.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct SectionLooks {
+    pub intro: Option<String>,
+    pub build: Option<String>,
+    pub drop: Option<String>,
+    pub breakdown: Option<String>,
+    pub outro: Option<String>,
+}
+
+impl SectionLooks {
+    pub fn get(&self, kind: SectionKind) -> Option<&str> {
+        match kind {
+            SectionKind::Intro => &self.intro,
+            SectionKind::Build => &self.build,
+            SectionKind::Drop => &self.drop,
+            SectionKind::Breakdown => &self.breakdown,
+            SectionKind::Outro => &self.outro,
+        }
+        .as_deref()
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Format {
+    Toml,
+    Json,
+}
+
+impl Format {
+    /// `.json` files are JSON; everything else is read as TOML.
+    pub fn from_path(path: &Path) -> Self {
+        match path.extension().and_then(|e| e.to_str()) {
+            Some(ext) if ext.eq_ignore_ascii_case("json") => Self::Json,
+            _ => Self::Toml,
+        }
+    }
+}
+
+impl Preset {
+    pub fn parse(text: &str, format: Format) -> anyhow::Result<Self> {
+        let preset = Self::decode(text, format)?;
+        preset.validate()?;
+        Ok(preset)
+    }
+
+    fn decode(text: &str, format: Format) -> anyhow::Result<Self> {
+        Ok(match format {
+            Format::Toml => toml::from_str(text)?,
+            Format::Json => serde_json::from_str(text)?,
+        })
+    }
+
+    /// Builds a palette by name from this preset's `[palettes]` or the built-ins.
+    pub fn palette(&self, name: &str) -> anyhow::Result<Palette> {
+        Palette::resolve(name, &self.palettes)
+    }
+
+    /// Rejects values that parse fine but would break the analysis or drawing.
+    pub fn validate(&self) -> anyhow::Result<()> {
+        self.validate_values()?;
+        self.validate_files()
+    }
+
+    /// The checks that need nothing but the preset itself.
+    fn validate_values(&self) -> anyhow::Result<()> {
+        // `nan` and `inf` parse fine from TOML, and no range check below
+        // catches a `nan`
+        let tree = toml::Value::try_from(self).context("Preset doesn't serialize")?;
+        if let Some(path) = non_finite("", &tree) {
+            bail!("{path} must be a finite number");
+        }
+        let unit = |v: f32| (0.0..=1.0).contains(&v);
+        let a = &self.analyzer;
+        ensure!(self.crossfade >= 0.0, "crossfade must not be negative");
+        for (name, v) in [
+            ("alpha_bands", a.alpha_bands),
+            ("alpha_bass_slow", a.alpha_bass_slow),
+            ("alpha_bass_fast", a.alpha_bass_fast),
+        ] {
+            ensure!(
+                v > 0.0 && v <= 1.0,
+                "analyzer.{name} must be in (0, 1], got {v}"
+            );
+        }
+        ensure!(
+            a.f_min > 0.0 && a.f_max > a.f_min,
+            "analyzer needs 0 < f_min < f_max, got {} and {}",
+            a.f_min,
+            a.f_max
+        );
+        ensure!(
+            a.onset_sensitivity > 0.0,
+            "analyzer.onset_sensitivity must be positive"
+        );
+        ensure!(a.history >= 1, "analyzer.history must be at least 1");
+
+        let r = &self.radial;
+        ensure!(
+            unit(r.fade_alpha),
+            "radial.fade_alpha must be in [0, 1], got {}",
+            r.fade_alpha
+        );
+        ensure!(
+            r.base_radius >= 0.0,
+            "radial.base_radius must not be negative"
+        );
+
+        let s = &self.spectrogram;
+        ensure!(
+            s.max_db > s.min_db,
+            "spectrogram.max_db must be above min_db"
+        );
+        ensure!(
+            self.oscilloscope.timebase_ms > 0.0,
+            "oscilloscope.timebase_ms must be positive"
+        );
+        ensure!(
+            unit(self.vectorscope.persistence),
+            "vectorscope.persistence must be in [0, 1]"
+        );
+
+        let p = &self.particles;
+        ensure!(
+            unit(p.fade_alpha),
+            "particles.fade_alpha must be in [0, 1], got {}",
+            p.fade_alpha
+        );
+        ensure!(p.life > 0.0, "particles.life must be positive");
+        ensure!(p.drag >= 0.0, "particles.drag must not be negative");
+
+        let h = &self.hud;
+        ensure!(
+            unit(h.opacity),
+            "hud.opacity must be in [0, 1], got {}",
+            h.opacity
+        );
+        ensure!(
+            (6..=96).contains(&h.font_size),
+            "hud.font_size must be between 6 and 96, got {}",
+            h.font_size
+        );
+
+        let m = &self.midi;
+        ensure!(
+            (1..=16).contains(&m.channel),
+            "midi.channel must be between 1 and 16, got {}",
+            m.channel
+        );
+        for c in &m.cc {
+            ensure!(c.cc < 128, "midi.cc {} is not a controller number", c.cc);
+        }
+        for n in &m.notes {
+            ensure!(n.note < 128, "midi.notes: {} is not a note number", n.note);
+            ensure!(
+                n.length_ms > 0.0,
+                "midi.notes: length_ms of note {} must be positive",
+                n.note
+            );
+            if let Some(v) = n.velocity {
+                ensure!(
+                    (1..=127).contains(&v),
+                    "midi.notes: velocity of note {} must be between 1 and 127",
+                    n.note
+                );
+            }
+        }
+        for b in &self.midi_in.bindings {
+            ensure!(
+                (1..=16).contains(&b.channel) && b.cc < 128,
+                "midi_in: channel {} cc {} is not a controller",
+                b.channel,
+                b.cc
+            );
+            ensure!(
+                matches!(
+                    self.value(&b.param),
+                    Some(Value::Number(_) | Value::Bool(_))
+                ),
+                "midi_in: no preset value '{}' to bind",
+                b.param
+            );
+            ensure!(
+                b.min != b.max,
+                "midi_in: '{}' needs min and max to differ",
+                b.param
+            );
+        }
+
+        let s = &self.sections;
+        for name in [&s.intro, &s.build, &s.drop, &s.breakdown, &s.outro]
+            .into_iter()
+            .flatten()
+        {
+            ensure!(
+                !name.trim().is_empty(),
+                "sections: preset names can't be empty"
+            );
+        }
+        Ok(())
+    }
+
+    /// The checks that load fonts and palette images from disk.
+    fn validate_files(&self) -> anyhow::Result<()> {
+        if let Some(font) = &self.hud.font {
+            hud::load_font(font).context("hud.font")?;
+        }
+        // build every palette now so a bad colour or missing image shows up
+        // as a preset error rather than a silent fallback later
+        for def in self.palettes.values() {
+            Palette::build(def)?;
+        }
+        self.palette(&self.radial.color.palette)
+            .context("radial.color.palette")?;
+        Ok(())
+    }
+
+    /// A copy with the value at `path` (e.g. `radial/fade_alpha`) replaced.
+    /// Numbers are converted to the field's type, so a float from a fader can
+    /// set an integer or a switch; the result must still validate. Only a
+    /// change to the palettes or the font reloads files, so a knob stays cheap.
+    pub fn with_value(&self, path: &str, value: Value) -> anyhow::Result<Self> {
+        let mut tree = serde_json::to_value(self)?;
+        let mut slot = &mut tree;
+        for key in path.split('/') {
+            slot = match slot.get_mut(key) {
+                Some(v) if !v.is_array() => v,
+                _ => bail!("No preset value '{path}'"),
+            };
+        }
+        *slot = match (&*slot, value) {
+            (Value::Bool(_), Value::Number(n)) => Value::Bool(n.as_f64() != Some(0.0)),
+            (Value::Number(old), Value::Number(n)) if !old.is_f64() => {
+                let v = n.as_f64().unwrap_or_default().round();
+                if old.is_u64() && v >= 0.0 {
+                    (v as u64).into()
+                } else {
+                    (v as i64).into()
+                }
+            }
+            (Value::Object(_), _) => bail!("'{path}' is a section, not a value"),
+            (Value::Null, v) => v,
+            (old, v) if std::mem::discriminant(old) == std::mem::discriminant(&v) => v,
+            (old, v) => bail!("'{path}' takes a value like {old}, got {v}"),
+        };
+        let preset: Self =
+            serde_json::from_value(tree).with_context(|| format!("Bad value for '{path}'"))?;
+        preset.validate_values()?;
+        if path.starts_with("palettes/") || ["hud/font", "radial/color/palette"].contains(&path) {
+            preset.validate_files()?;
+        }
+        Ok(preset)
+    }
+
+    /// The value at `path`, as `with_value` takes them.
+    pub fn value(&self, path: &str) -> Option<Value> {
+        let mut tree = serde_json::to_value(self).ok()?;
+        for key in path.split('/') {
+            tree = tree.get_mut(key)?.take();
+        }
+        Some(tree)
+    }
+
+    /// Every single value with its path, as `with_value` takes them. Lists
+    /// (palette stops and the like) are left out.
+    pub fn values(&self) -> Vec<(String, Value)> {
+        fn walk(prefix: &str, v: &Value, out: &mut Vec<(String, Value)>) {
+            match v {
+                Value::Object(map) => {
+                    for (key, v) in map {
+                        let path = if prefix.is_empty() {
+                            key.clone()
+                        } else {
+                            format!("{prefix}/{key}")
+                        };
+                        walk(&path, v, out);
+                    }
+                }
+                Value::Array(_) | Value::Null => {}
+                v => out.push((prefix.to_string(), v.clone())),
+            }
+        }
+        let mut out = Vec::new();
+        walk(
+            "",
+            &serde_json::to_value(self).expect("presets serialize"),
+            &mut out,
+        );
+        out
+    }
+
+    /// Reads a preset file, naming it after the file when it doesn't name itself.
+    pub fn load(path: &Path) -> anyhow::Result<Self> {
+        let text = std::fs::read_to_string(path)
+            .with_context(|| format!("Failed to read {}", path.display()))?;
+        let mut preset = Self::decode(&text, Format::from_path(path))
+            .with_context(|| format!("Invalid preset {}", path.display()))?;
+        // learned bindings replace the file's own `[midi_in]`
+        let bindings = bindings_path(path);
+        if bindings.exists() {
+            let text = std::fs::read_to_string(&bindings)
+                .with_context(|| format!("Failed to read {}", bindings.display()))?;
+            preset.midi_in = toml::from_str(&text)
+                .with_context(|| format!("Invalid MIDI bindings {}", bindings.display()))?;
+        }
+        // palette images and fonts are relative to the preset, not the working directory
+        let dir = path.parent().unwrap_or(Path::new(""));
+        for def in preset.palettes.values_mut() {
+            if let PaletteDef::Image { path, .. } = def {
+                *path = dir.join(&*path);
+            }
+        }
+        if let Some(font) = &mut preset.hud.font {
+            *font = dir.join(&*font);
+        }
+        preset
+            .validate()
+            .with_context(|| format!("Invalid preset {}", path.display()))?;
+        if preset.name.is_empty() {
+            preset.name = stem(path);
+        }
+        Ok(preset)
+    }
+}
+
+/// Where learned MIDI bindings for the preset file at `path` are kept:
+/// `live.toml` → `live.bindings.toml`, next to it.
+/// Where in `v` the first `nan` or infinity is, as a dotted path.
+fn non_finite(prefix: &str, v: &toml::Value) -> Option<String> {
+    match v {
+        toml::Value::Float(f) if !f.is_finite() => Some(prefix.to_string()),
+        toml::Value::Table(t) => t.iter().find_map(|(k, v)| {
+            let path = if prefix.is_empty() {
+                k.clone()
+            } else {
+                format!("{prefix}.{k}")
+            };
+            non_finite(&path, v)
+        }),
+        toml::Value::Array(a) => a
+            .iter()
+            .enumerate()
+            .find_map(|(i, v)| non_finite(&format!("{prefix}[{i}]"), v)),
+        _ => None,
+    }
+}
+
+fn bindings_path(path: &Path) -> PathBuf {
+    path.with_extension("bindings.toml")
+}
+
+fn is_bindings(path: &Path) -> bool {
+    path.file_name()
+        .and_then(|n| n.to_str())
+        .is_some_and(|n| n.ends_with(".bindings.toml"))
+}
+
+/// Writes `midi_in` to the bindings file next to the preset at `path`. The
+/// preset file itself is never rewritten, so its comments and layout stay.
+pub fn save_bindings(path: &Path, midi_in: &MidiInParams) -> anyhow::Result<()> {
+    let path = bindings_path(path);
+    let text = toml::to_string(midi_in)?;
+    std::fs::write(&path, text).with_context(|| format!("Failed to write {}", path.display()))
+}
+
+fn stem(path: &Path) -> String {
+    path.file_stem()
+        .map(|s| s.to_string_lossy().into_owned())
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testutil::TempDir;
+
+    #[test]
+    fn missing_fields_keep_defaults() {
+        let p = Preset::parse("[radial]\nbar_gain = 500.0\n", Format::Toml).unwrap();
+        assert_eq!(p.radial.bar_gain, 500.0);
+        assert_eq!(p.radial.fade_alpha, RadialParams::default().fade_alpha);
+        assert_eq!(p.analyzer, AnalyzerParams::default());
+    }
+
+    #[test]
+    fn presets_from_before_palettes_still_load() {
+        let old = "[radial]\nhue_base = 0.8\nhue_range = 0.45\npulse_gain = 90.0\n";
+        let p = Preset::parse(old, Format::Toml).unwrap();
+        assert_eq!(p.radial.color.palette, "rainbow");
+        assert_eq!(p.radial.color.offset, 0.8);
+        assert_eq!(p.radial.color.bass_shift, 0.45);
+        assert_eq!(p.radial.pulse_gain, 90.0);
+        // and save in the new form
+        let saved = serde_json::to_value(&p).unwrap();
+        assert!(saved["radial"].get("hue_base").is_none());
+        assert_eq!(Preset::parse(&saved.to_string(), Format::Json).unwrap(), p);
+    }
+
+    #[test]
+    fn sections_name_presets_by_kind() {
+        let p = Preset::parse("[sections]\ndrop = \"club\"\n", Format::Toml).unwrap();
+        assert_eq!(p.sections.get(SectionKind::Drop), Some("club"));
+        assert_eq!(p.sections.get(SectionKind::Breakdown), None);
+        assert!(Preset::parse("[sections]\nchorus = \"club\"\n", Format::Toml).is_err());
+        assert!(Preset::parse("[sections]\nintro = \"\"\n", Format::Toml).is_err());
+    }
+
+    #[test]
+    fn json_and_toml_agree() {
+        let toml = "crossfade = 0.5\n[spectrogram]\ncolormap = \"magma\"\n";
+        let json = r#"{ "crossfade": 0.5, "spectrogram": { "colormap": "magma" } }"#;
+        assert_eq!(
+            Preset::parse(toml, Format::Toml).unwrap(),
+            Preset::parse(json, Format::Json).unwrap()
+        );
+    }
+
+    #[test]
+    fn unknown_keys_are_rejected() {
+        assert!(Preset::parse("[radial]\nbar_gian = 1.0\n", Format::Toml).is_err());
+        assert!(Preset::parse("[radail]\n", Format::Toml).is_err());
+    }
+
+    #[test]
+    fn out_of_range_values_are_rejected() {
+        assert!(Preset::parse("[radial]\nbar_gain = inf\n", Format::Toml).is_err());
+        assert!(Preset::parse("[radial]\nbase_radius = nan\n", Format::Toml).is_err());
+        assert!(Preset::parse("[particles]\nspeed = -inf\n", Format::Toml).is_err());
+        assert!(Preset::parse("[radial]\nfade_alpha = 1.5\n", Format::Toml).is_err());
+        assert!(Preset::parse("[analyzer]\nalpha_bands = 0.0\n", Format::Toml).is_err());
+        assert!(Preset::parse("[analyzer]\nf_min = 500.0\nf_max = 100.0\n", Format::Toml).is_err());
+        assert!(Preset::parse("crossfade = -1.0\n", Format::Toml).is_err());
+        assert!(Preset::parse("[hud]\nopacity = 2.0\n", Format::Toml).is_err());
+        assert!(Preset::parse("[hud]\nfont = \"missing.ttf\"\n", Format::Toml).is_err());
+        assert!(Preset::parse("[midi]\nchannel = 17\n", Format::Toml).is_err());
+        assert!(
+            Preset::parse(
+                "[[midi.notes]]\nnote = 200\nsource = \"onset\"\n",
+                Format::Toml
+            )
+            .is_err()
+        );
+    }
+
+    #[test]
+    fn single_values_by_path() {
+        let p = Preset::default();
+        let q = p.with_value("radial/fade_alpha", 0.5.into()).unwrap();
+        assert_eq!(q.radial.fade_alpha, 0.5);
+        // faders send floats for integers and switches too
+        let q = q.with_value("hud/font_size", 20.4.into()).unwrap();
+        assert_eq!(q.hud.font_size, 20);
+        let q = q.with_value("hud/visible", 1.0.into()).unwrap();
+        assert!(q.hud.visible);
+        let q = q
+            .with_value("spectrogram/colormap", "magma".into())
+            .unwrap();
+        let text = "[radial]\nfade_alpha = 0.5\n[hud]\nfont_size = 20\nvisible = true\n\
+                    [spectrogram]\ncolormap = \"magma\"\n";
+        assert_eq!(q, Preset::parse(text, Format::Toml).unwrap());
+
+        assert!(p.with_value("radial/fade_alpha", 1.5.into()).is_err());
+        assert!(p.with_value("radial/nope", 1.0.into()).is_err());
+        assert!(p.with_value("radial", 1.0.into()).is_err());
+        assert!(p.with_value("spectrogram/colormap", 1.0.into()).is_err());
+
+        // only a change to the font or palettes goes back to the files
+        let mut moved = p.clone();
+        moved.hud.font = Some("no/such/font.ttf".into());
+        assert!(moved.with_value("hud/font_size", 20.0.into()).is_ok());
+        assert!(
+            moved
+                .with_value("hud/font", "no/such/font.ttf".into())
+                .is_err()
+        );
+
+        let values = p.values();
+        assert!(values.contains(&("analyzer/alpha_bands".into(), p.analyzer.alpha_bands.into())));
+        assert!(
+            values
+                .iter()
+                .all(|(path, _)| !path.starts_with("hud/font/"))
+        );
+    }
+
+    #[test]
+    fn bindings_are_saved_next_to_the_file() {
+        let dir = TempDir::new("preset-bindings");
+        let path = dir.join("live.toml");
+        let text = "# tuned for the club\n[radial]\nbar_gain = 300.0\n";
+        std::fs::write(&path, text).unwrap();
+
+        let midi_in = MidiInParams {
+            takeover: true,
+            bindings: vec![crate::midi::learn::Binding {
+                channel: 1,
+                cc: 21,
+                param: "radial/pulse_gain".into(),
+                min: 0.0,
+                max: 200.0,
+            }],
+        };
+        save_bindings(&path, &midi_in).unwrap();
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), text);
+        let p = Preset::load(&path).unwrap();
+        assert_eq!(p.midi_in, midi_in);
+        assert_eq!(p.radial.bar_gain, 300.0);
+        assert_eq!(p.value("radial/bar_gain"), Some(300.0.into()));
+
+        // the bindings file isn't a preset of its own
+        let lib = PresetLibrary::load(&[dir.path().to_path_buf()]);
+        assert!(lib.index_of("live").is_some());
+        assert!(lib.index_of("live.bindings").is_none());
+
+        let unknown = "[[midi_in.bindings]]\nchannel = 1\ncc = 1\nparam = \"radial/nope\"\n\
+                       min = 0.0\nmax = 1.0\n";
+        assert!(Preset::parse(unknown, Format::Toml).is_err());
+    }
+
+    #[test]
+    fn paths_select_presets_already_loaded() {
+        let dir = TempDir::new("preset-paths");
+        let path = dir.join("live.toml");
+        std::fs::write(&path, "[radial]\nbar_gain = 300.0\n").unwrap();
+
+        let mut lib = PresetLibrary::load(&[dir.path().to_path_buf()]);
+        let live = lib.index_of("live").unwrap();
+        let count = lib.entries().len();
+        lib.select(path.to_str().unwrap()).unwrap();
+        assert_eq!(lib.current_index(), live);
+        assert_eq!(lib.entries().len(), count);
+    }
+
+    #[test]
+    fn bundled_presets_parse() {
+        for (file, text) in library::BUNDLED {
+            Preset::parse(text, Format::from_path(Path::new(file)))
+                .unwrap_or_else(|e| panic!("{file}: {e:#}"));
+        }
+    }
+}
@@ -0,0 +1,86 @@
+use super::Preset;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant, SystemTime};
+
+/// Polls one preset file for changes. Polling a single file a few times a
+/// second is cheap and behaves the same with every editor's save strategy
+/// (in-place writes, rename-over, truncate-then-write).
+pub struct PresetWatcher {
+    pub interval: Duration,
+    path: Option<PathBuf>,
+    stamp: Option<(SystemTime, u64)>, // mtime and length at the last load
+    next_poll: Instant,
+}
+
+impl PresetWatcher {
+    pub fn new() -> Self {
+        Self {
+            interval: Duration::from_millis(250),
+            path: None,
+            stamp: None,
+            next_poll: Instant::now(),
+        }
+    }
+
+    /// Starts watching `path` (or nothing, for built-in presets) from its
+    /// current contents; only later edits are reported.
+    pub fn watch(&mut self, path: Option<&Path>) {
+        self.path = path.map(Path::to_path_buf);
+        self.stamp = path.and_then(stamp);
+    }
+
+    /// Reloads the file if it changed since the last call. Returns `None` when
+    /// nothing changed, otherwise the parsed preset or why it was rejected.
+    pub fn poll(&mut self) -> Option<anyhow::Result<Preset>> {
+        let now = Instant::now();
+        if now < self.next_poll {
+            return None;
+        }
+        self.next_poll = now + self.interval;
+
+        let path = self.path.as_ref()?;
+        let current = stamp(path);
+        // a missing file is usually an editor mid-save; wait for it to reappear
+        if current.is_none() || current == self.stamp {
+            return None;
+        }
+        self.stamp = current;
+        Some(Preset::load(path))
+    }
+}
+
+fn stamp(path: &Path) -> Option<(SystemTime, u64)> {
+    let meta = std::fs::metadata(path).ok()?;
+    Some((meta.modified().ok()?, meta.len()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testutil::TempDir;
+
+    #[test]
+    fn reports_edits_and_errors_once() {
+        let dir = TempDir::new("preset-watch");
+        let path = dir.join("live.toml");
+        std::fs::write(&path, "[radial]\nbar_gain = 300.0\n").unwrap();
+
+        let mut w = PresetWatcher::new();
+        w.interval = Duration::ZERO;
+        w.watch(Some(&path));
+        assert!(w.poll().is_none(), "unchanged file must not reload");
+
+        std::fs::write(&path, "[radial]\nbar_gain = 420.0\npulse_gain = 80.0\n").unwrap();
+        let p = w.poll().expect("edit not noticed").unwrap();
+        assert_eq!(p.radial.bar_gain, 420.0);
+        assert_eq!(p.name, "live");
+        assert!(w.poll().is_none());
+
+        std::fs::write(&path, "[radial]\nfade_alpha = 7.0\n").unwrap();
+        assert!(w.poll().expect("edit not noticed").is_err());
+        assert!(
+            w.poll().is_none(),
+            "a bad file is reported once, not every poll"
+        );
+    }
+}
@@ -0,0 +1,88 @@
+use super::{Record, Recorder};
+use crate::analysis::{
+    AnalysisFrame, Analyzer, AnalyzerParams, LevelMeter, TempoEstimator, TrackAnalysis,
+};
+use crate::audio::AudioData;
+use crate::{BARS, FFT_SIZE};
+use std::path::{Path, PathBuf};
+use std::time::Instant;
+
+pub struct AnalyzeOptions {
+    pub file: String,
+    pub out: PathBuf,
+    /// analysis frames per second of audio
+    pub rate: f32,
+    pub seconds: Option<f32>,
+    pub analyzer: AnalyzerParams,
+}
+
+/// Checks a `--rate`: frames per second must be a positive, finite number,
+/// or the analysis would never move on.
+pub fn parse_rate(rate: &str) -> anyhow::Result<f32> {
+    let rate: f32 = rate.parse()?;
+    anyhow::ensure!(
+        rate.is_finite() && rate > 0.0,
+        "frames per second must be positive"
+    );
+    Ok(rate)
+}
+
+/// Analyzes a whole WAV as fast as the CPU allows and writes every frame.
+/// Frame `k` is the window centred on `k / rate` seconds, as in `render`, so
+/// the same file and settings always give the same recording.
+pub fn run(opts: &AnalyzeOptions) -> anyhow::Result<()> {
+    anyhow::ensure!(
+        opts.rate.is_finite() && opts.rate > 0.0,
+        "--rate must be positive"
+    );
+    let audio = AudioData::load_wav(Path::new(&opts.file))?;
+    let ahead = TrackAnalysis::load(Path::new(&opts.file), &audio)?;
+    let mut analyzer = Analyzer::new(FFT_SIZE, BARS);
+    analyzer.set_params(&opts.analyzer);
+    let mut levels = LevelMeter::new();
+    let mut tempo = TempoEstimator::new();
+    let mut recorder = Recorder::create(&opts.out)?;
+
+    let seconds = opts
+        .seconds
+        .map_or(audio.duration_sec, |s| s.min(audio.duration_sec));
+    let frames = (seconds * opts.rate).ceil() as usize;
+    let dt = 1.0 / opts.rate;
+
+    let started = Instant::now();
+    let mut window = Vec::with_capacity(FFT_SIZE);
+    eprintln!("[analyze] {frames} frames at {} per second…", opts.rate);
+    for k in 0..frames {
+        let t = k as f32 * dt;
+        audio.window_at_time(t, FFT_SIZE, &mut window);
+        levels.update(&window, dt);
+        let frame = AnalysisFrame {
+            section: ahead.section_at(t),
+            ..analyzer.analyze(&window, audio.sample_rate)
+        };
+        tempo.push(frame.flux, dt);
+        recorder.write(&Record::new(t, &frame, &levels, tempo.bpm()))?;
+    }
+    recorder.finish()?;
+
+    let took = started.elapsed().as_secs_f32();
+    eprintln!(
+        "[analyze] wrote {} ({seconds:.0}s of audio in {took:.1}s, {:.0}× real time)",
+        opts.out.display(),
+        seconds / took.max(0.001)
+    );
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rates_must_be_positive_and_finite() {
+        assert_eq!(parse_rate("60").unwrap(), 60.0);
+        for bad in ["0", "-30", "inf", "NaN", "fast"] {
+            assert!(parse_rate(bad).is_err(), "{bad}");
+        }
+    }
+}
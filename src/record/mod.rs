@@ -0,0 +1,306 @@
+//! Analysis frames written to disk, live or from a whole file at once, for
+//! comparing tracks and masters offline.
+
+mod analyze;
+mod replay;
+
+pub use analyze::{AnalyzeOptions, parse_rate, run};
+pub use replay::{Recording, Replay};
+
+use crate::analysis::{AnalysisFrame, LevelMeter, SectionKind};
+use anyhow::{Context, bail, ensure};
+use serde::{Deserialize, Serialize};
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+
+/// Leads binary recordings, followed by a little-endian u32 version and
+/// band count.
+const MAGIC: &[u8; 4] = b"AVRF";
+const VERSION: u32 = 2;
+
+/// One recorded frame.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct Record {
+    /// seconds since the recording started (or into the file, offline)
+    pub t: f32,
+    pub bass_fast: f32,
+    pub bass_smooth: f32,
+    pub rms_db: f32,
+    pub peak_db: f32,
+    pub onset: bool,
+    pub onset_strength: f32,
+    pub centroid: f32,
+    pub flux: f32,
+    /// tapped or detected tempo, while there is one
+    pub bpm: Option<f32>,
+    /// the part of the song, once known, and its number
+    pub section: Option<SectionKind>,
+    #[serde(default)]
+    pub section_index: usize,
+    pub bands: Vec<f32>,
+}
+
+impl Record {
+    pub fn new(t: f32, frame: &AnalysisFrame, levels: &LevelMeter, bpm: Option<f32>) -> Self {
+        Self {
+            t,
+            bass_fast: frame.bass_fast,
+            bass_smooth: frame.bass_smooth,
+            rms_db: levels.rms_db,
+            peak_db: levels.peak_db,
+            onset: frame.onset,
+            onset_strength: frame.onset_strength,
+            centroid: frame.centroid,
+            flux: frame.flux,
+            bpm,
+            section: frame.section.map(|s| s.kind),
+            section_index: frame.section.map_or(0, |s| s.index),
+            bands: frame.bands.clone(),
+        }
+    }
+
+    /// The frame as the analyzer made it, for replay. The section comes from
+    /// `Replay::section`, which knows when it began.
+    pub fn frame(&self) -> AnalysisFrame {
+        AnalysisFrame {
+            bands: self.bands.clone(),
+            bass_fast: self.bass_fast,
+            bass_smooth: self.bass_smooth,
+            onset: self.onset,
+            onset_strength: self.onset_strength,
+            centroid: self.centroid,
+            flux: self.flux,
+            lookahead: None,
+            section: None,
+        }
+    }
+
+    /// The scalar fields in file order; binary recordings store a missing
+    /// `bpm` as 0, `onset` as 0 or 1 and the section as 1 (intro) to 5
+    /// (outro), or 0 while unknown.
+    fn head(&self) -> [f32; 12] {
+        [
+            self.t,
+            self.bass_fast,
+            self.bass_smooth,
+            self.rms_db,
+            self.peak_db,
+            self.onset as u8 as f32,
+            self.onset_strength,
+            self.centroid,
+            self.flux,
+            self.bpm.unwrap_or(0.0),
+            self.section.map_or(0, section_code) as f32,
+            self.section_index as f32,
+        ]
+    }
+}
+
+fn section_code(kind: SectionKind) -> usize {
+    SectionKind::ALL.iter().position(|&k| k == kind).unwrap() + 1
+}
+
+fn section_from_code(code: f32) -> Option<SectionKind> {
+    (code >= 1.0)
+        .then(|| SectionKind::ALL.get(code as usize - 1).copied())
+        .flatten()
+}
+
+const COLUMNS: [&str; 12] = [
+    "t",
+    "bass_fast",
+    "bass_smooth",
+    "rms_db",
+    "peak_db",
+    "onset",
+    "onset_strength",
+    "centroid",
+    "flux",
+    "bpm",
+    "section",
+    "section_index",
+];
+
+/// Recordings from before sections were kept end their scalars at `bpm`.
+const V1_COLUMNS: usize = 10;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Format {
+    /// A header row, then one row per frame with the bands as `band_<i>` columns
+    Csv,
+    /// One JSON object per line
+    Jsonl,
+    /// Packed little-endian f32s: the smallest, and the fastest to read back
+    Bin,
+}
+
+impl Format {
+    /// By extension: `.csv`, `.jsonl` (or `.ndjson`) and `.bin`.
+    pub fn from_path(path: &Path) -> anyhow::Result<Self> {
+        let ext = path.extension().and_then(|e| e.to_str()).unwrap_or("");
+        Ok(match ext.to_ascii_lowercase().as_str() {
+            "csv" => Self::Csv,
+            "jsonl" | "ndjson" => Self::Jsonl,
+            "bin" => Self::Bin,
+            _ => bail!(
+                "Can't tell the recording format of {}; use .csv, .jsonl or .bin",
+                path.display()
+            ),
+        })
+    }
+}
+
+/// Writes frames to a file as they come, in the format its extension names.
+/// Output is flushed every `FLUSH_EVERY` frames, so a live recording loses
+/// at most a second or so when the window is closed.
+pub struct Recorder {
+    out: BufWriter<File>,
+    format: Format,
+    /// band count of the first frame; the header (CSV columns, binary
+    /// record size) is written then
+    bands: Option<usize>,
+    written: usize,
+}
+
+const FLUSH_EVERY: usize = 64;
+
+impl Recorder {
+    pub fn create(path: &Path) -> anyhow::Result<Self> {
+        let format = Format::from_path(path)?;
+        let file =
+            File::create(path).with_context(|| format!("Failed to create {}", path.display()))?;
+        Ok(Self {
+            out: BufWriter::new(file),
+            format,
+            bands: None,
+            written: 0,
+        })
+    }
+
+    pub fn write(&mut self, record: &Record) -> anyhow::Result<()> {
+        let n = record.bands.len();
+        match self.bands {
+            None => {
+                self.header(n)?;
+                self.bands = Some(n);
+            }
+            Some(bands) => ensure!(
+                bands == n,
+                "band count changed from {bands} to {n} mid-recording"
+            ),
+        }
+        let out = &mut self.out;
+        match self.format {
+            Format::Csv => {
+                let mut row: Vec<String> = record.head().iter().map(f32::to_string).collect();
+                if record.bpm.is_none() {
+                    row[9].clear();
+                }
+                match record.section {
+                    Some(kind) => row[10] = kind.name().into(),
+                    None => {
+                        row[10].clear();
+                        row[11].clear();
+                    }
+                }
+                row.extend(record.bands.iter().map(f32::to_string));
+                writeln!(out, "{}", row.join(","))?;
+            }
+            Format::Jsonl => {
+                serde_json::to_writer(&mut *out, record)?;
+                out.write_all(b"\n")?;
+            }
+            Format::Bin => {
+                for v in record.head().iter().chain(&record.bands) {
+                    out.write_all(&v.to_le_bytes())?;
+                }
+            }
+        }
+        self.written += 1;
+        if self.written.is_multiple_of(FLUSH_EVERY) {
+            out.flush()?;
+        }
+        Ok(())
+    }
+
+    fn header(&mut self, bands: usize) -> std::io::Result<()> {
+        let out = &mut self.out;
+        match self.format {
+            Format::Csv => {
+                let mut header = COLUMNS.join(",");
+                for i in 0..bands {
+                    header += &format!(",band_{i}");
+                }
+                writeln!(out, "{header}")
+            }
+            Format::Jsonl => Ok(()),
+            Format::Bin => {
+                out.write_all(MAGIC)?;
+                out.write_all(&VERSION.to_le_bytes())?;
+                out.write_all(&(bands as u32).to_le_bytes())
+            }
+        }
+    }
+
+    /// Flushes what's buffered; dropping the recorder does too, but can't
+    /// report a failure.
+    pub fn finish(mut self) -> anyhow::Result<()> {
+        self.out.flush()?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testutil::TempDir;
+
+    fn record(t: f32, bpm: Option<f32>) -> Record {
+        Record {
+            t,
+            onset: bpm.is_some(),
+            bpm,
+            section: bpm.map(|_| SectionKind::Drop),
+            section_index: bpm.map_or(0, |_| 2),
+            bands: vec![0.25, 0.5],
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn writes_each_format() {
+        let dir = TempDir::new("record");
+        let write = |name: &str| {
+            let path = dir.join(name);
+            let mut rec = Recorder::create(&path).unwrap();
+            rec.write(&record(0.0, None)).unwrap();
+            rec.write(&record(0.5, Some(120.0))).unwrap();
+            assert!(
+                rec.write(&Record::default()).is_err(),
+                "band count is fixed"
+            );
+            rec.finish().unwrap();
+            std::fs::read(&path).unwrap()
+        };
+
+        let csv = String::from_utf8(write("a.csv")).unwrap();
+        let lines: Vec<&str> = csv.lines().collect();
+        assert_eq!(
+            lines[0],
+            "t,bass_fast,bass_smooth,rms_db,peak_db,onset,onset_strength,centroid,flux,bpm,section,section_index,band_0,band_1"
+        );
+        assert_eq!(lines[1], "0,0,0,0,0,0,0,0,0,,,,0.25,0.5");
+        assert_eq!(lines[2], "0.5,0,0,0,0,1,0,0,0,120,drop,2,0.25,0.5");
+
+        let jsonl = String::from_utf8(write("a.jsonl")).unwrap();
+        let second: Record = serde_json::from_str(jsonl.lines().nth(1).unwrap()).unwrap();
+        assert_eq!(second, record(0.5, Some(120.0)));
+
+        let bin = write("a.bin");
+        assert_eq!(&bin[..4], MAGIC);
+        assert_eq!(bin[8], 2);
+        assert_eq!(bin.len(), 12 + 2 * 14 * 4);
+        assert!(Recorder::create(&dir.join("a.txt")).is_err());
+    }
+}
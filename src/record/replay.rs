@@ -0,0 +1,339 @@
+use super::{COLUMNS, Format, MAGIC, Record, V1_COLUMNS, VERSION, section_code, section_from_code};
+use crate::analysis::{SectionInfo, SectionKind};
+use anyhow::{Context, bail, ensure};
+use std::path::Path;
+
+/// A whole recording, in memory.
+pub struct Recording {
+    pub frames: Vec<Record>,
+}
+
+impl Recording {
+    /// Reads any of the formats `Recorder` writes, picked by extension.
+    pub fn load(path: &Path) -> anyhow::Result<Self> {
+        let format = Format::from_path(path)?;
+        let bytes =
+            std::fs::read(path).with_context(|| format!("Failed to read {}", path.display()))?;
+        let frames = match format {
+            Format::Csv => parse_csv(std::str::from_utf8(&bytes)?),
+            Format::Jsonl => std::str::from_utf8(&bytes)?
+                .lines()
+                .filter(|line| !line.trim().is_empty())
+                .enumerate()
+                .map(|(i, line)| {
+                    serde_json::from_str(line).with_context(|| format!("line {}", i + 1))
+                })
+                .collect(),
+            Format::Bin => parse_bin(&bytes),
+        }
+        .with_context(|| format!("Invalid recording {}", path.display()))?;
+        ensure!(!frames.is_empty(), "{} has no frames", path.display());
+        ensure!(
+            frames.windows(2).all(|w| w[0].t <= w[1].t),
+            "{} is out of time order",
+            path.display()
+        );
+        Ok(Self { frames })
+    }
+
+    /// Time of the last frame.
+    pub fn duration(&self) -> f32 {
+        self.frames.last().map_or(0.0, |r| r.t)
+    }
+
+    /// Index of the frame showing at `t`: the last one at or before it.
+    fn index_at(&self, t: f32) -> usize {
+        self.frames.partition_point(|r| r.t <= t).saturating_sub(1)
+    }
+}
+
+fn parse_csv(text: &str) -> anyhow::Result<Vec<Record>> {
+    let mut lines = text.lines().filter(|line| !line.trim().is_empty());
+    let header: Vec<&str> = lines.next().context("no header row")?.split(',').collect();
+    ensure!(
+        header.len() >= V1_COLUMNS && header[..V1_COLUMNS] == COLUMNS[..V1_COLUMNS],
+        "header should start with {}",
+        COLUMNS.join(",")
+    );
+    let sections = header.get(V1_COLUMNS..COLUMNS.len()) == Some(&COLUMNS[V1_COLUMNS..]);
+    lines
+        .enumerate()
+        .map(|(i, line)| {
+            let row = i + 2;
+            let cells: Vec<&str> = line.split(',').collect();
+            ensure!(
+                cells.len() == header.len(),
+                "row {row} has {} columns, the header {}",
+                cells.len(),
+                header.len()
+            );
+            let mut values = Vec::with_capacity(cells.len() + 2);
+            for (cell, name) in cells.iter().zip(&header) {
+                values.push(match (*name, cell.trim()) {
+                    ("bpm" | "section" | "section_index", "") => 0.0,
+                    ("section", cell) => {
+                        match SectionKind::ALL.into_iter().find(|k| k.name() == cell) {
+                            Some(kind) => section_code(kind) as f32,
+                            None => bail!("row {row}: no section '{cell}'"),
+                        }
+                    }
+                    (_, cell) => cell
+                        .parse::<f32>()
+                        .with_context(|| format!("row {row}: bad {name} '{cell}'"))?,
+                });
+            }
+            if !sections {
+                let missing = COLUMNS.len() - V1_COLUMNS;
+                values.splice(V1_COLUMNS..V1_COLUMNS, std::iter::repeat_n(0.0, missing));
+            }
+            Ok(from_values(&values))
+        })
+        .collect()
+}
+
+fn parse_bin(mut bytes: &[u8]) -> anyhow::Result<Vec<Record>> {
+    let mut word = || -> anyhow::Result<[u8; 4]> {
+        ensure!(bytes.len() >= 4, "cut short");
+        let (head, rest) = bytes.split_at(4);
+        bytes = rest;
+        Ok(head.try_into().unwrap())
+    };
+    ensure!(word()? == *MAGIC, "not a binary recording");
+    let version = u32::from_le_bytes(word()?);
+    let head = match version {
+        1 => V1_COLUMNS,
+        VERSION => COLUMNS.len(),
+        _ => bail!("version {version} recordings aren't supported (expected {VERSION})"),
+    };
+    let bands = u32::from_le_bytes(word()?) as usize;
+    let size = (head + bands) * 4;
+    ensure!(bytes.len().is_multiple_of(size), "cut off mid-frame");
+    Ok(bytes
+        .chunks_exact(size)
+        .map(|chunk| {
+            let mut values: Vec<f32> = chunk
+                .chunks_exact(4)
+                .map(|b| f32::from_le_bytes(b.try_into().unwrap()))
+                .collect();
+            // version 1 had no section columns
+            values.splice(head..head, std::iter::repeat_n(0.0, COLUMNS.len() - head));
+            from_values(&values)
+        })
+        .collect())
+}
+
+/// A frame from its columns in file order, bands last. A `bpm` of 0 means
+/// none was known.
+fn from_values(v: &[f32]) -> Record {
+    Record {
+        t: v[0],
+        bass_fast: v[1],
+        bass_smooth: v[2],
+        rms_db: v[3],
+        peak_db: v[4],
+        onset: v[5] != 0.0,
+        onset_strength: v[6],
+        centroid: v[7],
+        flux: v[8],
+        bpm: (v[9] > 0.0).then_some(v[9]),
+        section: section_from_code(v[10]),
+        section_index: v[11] as usize,
+        bands: v[COLUMNS.len()..].to_vec(),
+    }
+}
+
+/// Plays a recording back on its own clock, looping like file playback.
+pub struct Replay {
+    recording: Recording,
+    /// when each frame's section began
+    starts: Vec<f32>,
+    position: f32,
+    paused: bool,
+    /// frame shown last, so onsets between two displayed frames aren't lost
+    /// and a frame shown twice doesn't repeat its onset; `None` after a jump
+    shown: Option<usize>,
+}
+
+impl Replay {
+    pub fn new(recording: Recording) -> Self {
+        let mut starts = Vec::with_capacity(recording.frames.len());
+        for (i, r) in recording.frames.iter().enumerate() {
+            let same = i > 0 && {
+                let prev = &recording.frames[i - 1];
+                (prev.section, prev.section_index) == (r.section, r.section_index)
+            };
+            starts.push(if same { starts[i - 1] } else { r.t });
+        }
+        Self {
+            recording,
+            starts,
+            position: 0.0,
+            paused: false,
+            shown: None,
+        }
+    }
+
+    pub fn duration(&self) -> f32 {
+        self.recording.duration()
+    }
+
+    pub fn position(&self) -> f32 {
+        self.position
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.paused
+    }
+
+    pub fn set_paused(&mut self, paused: bool) {
+        self.paused = paused;
+    }
+
+    pub fn seek(&mut self, secs: f32) {
+        self.position = secs.clamp(0.0, self.duration());
+        self.shown = None;
+    }
+
+    /// The recorded section at the current position, if the recording has one.
+    pub fn section(&self) -> Option<SectionInfo> {
+        let now = self.recording.index_at(self.position);
+        let record = &self.recording.frames[now];
+        Some(SectionInfo {
+            kind: record.section?,
+            index: record.section_index,
+            elapsed: self.position - self.starts[now],
+        })
+    }
+
+    /// Moves `dt` seconds on and returns the frame now showing, with any
+    /// onset from frames skipped on the way.
+    pub fn advance(&mut self, dt: f32) -> Record {
+        if !self.paused {
+            self.position += dt;
+        }
+        let duration = self.duration();
+        if self.position > duration {
+            self.position = if duration > 0.0 {
+                self.position % duration
+            } else {
+                0.0
+            };
+            self.shown = None;
+        }
+        let now = self.recording.index_at(self.position);
+        let mut record = self.recording.frames[now].clone();
+        match self.shown {
+            Some(shown) if now > shown => {
+                for skipped in &self.recording.frames[shown + 1..now] {
+                    record.onset |= skipped.onset;
+                    record.onset_strength = record.onset_strength.max(skipped.onset_strength);
+                }
+            }
+            Some(shown) if now == shown => {
+                record.onset = false;
+                record.onset_strength = 0.0;
+            }
+            _ => {}
+        }
+        self.shown = Some(now);
+        record
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::record::Recorder;
+    use crate::testutil::TempDir;
+
+    #[test]
+    fn every_format_reads_back() {
+        let dir = TempDir::new("replay");
+        let frames: Vec<Record> = (0..5)
+            .map(|i| Record {
+                t: i as f32 * 0.25,
+                onset: i == 2,
+                bpm: (i > 2).then_some(128.0),
+                section: (i > 1).then_some(SectionKind::Build),
+                section_index: (i > 1) as usize,
+                bands: vec![i as f32 * 0.1, 0.5],
+                ..Default::default()
+            })
+            .collect();
+        for name in ["a.csv", "a.jsonl", "a.bin"] {
+            let path = dir.join(name);
+            let mut rec = Recorder::create(&path).unwrap();
+            for f in &frames {
+                rec.write(f).unwrap();
+            }
+            rec.finish().unwrap();
+            assert_eq!(Recording::load(&path).unwrap().frames, frames, "{name}");
+        }
+        // recordings from before sections were kept still load
+        std::fs::write(
+            dir.join("v1.csv"),
+            "t,bass_fast,bass_smooth,rms_db,peak_db,onset,onset_strength,centroid,flux,bpm,band_0\n\
+             0,0,0,0,0,0,0,0,0,120,0.5\n",
+        )
+        .unwrap();
+        let v1 = Recording::load(&dir.join("v1.csv")).unwrap();
+        assert_eq!(
+            (v1.frames[0].bpm, v1.frames[0].section),
+            (Some(120.0), None)
+        );
+        std::fs::write(dir.join("bad.csv"), "t,bands\n0,1\n").unwrap();
+        assert!(Recording::load(&dir.join("bad.csv")).is_err());
+    }
+
+    #[test]
+    fn replay_knows_when_sections_began() {
+        let frames = (0..10)
+            .map(|i| Record {
+                t: i as f32 * 0.5,
+                section: Some(if i < 4 {
+                    SectionKind::Intro
+                } else {
+                    SectionKind::Drop
+                }),
+                section_index: (i >= 4) as usize,
+                ..Default::default()
+            })
+            .collect();
+        let mut replay = Replay::new(Recording { frames });
+        replay.seek(3.2);
+        let section = replay.section().unwrap();
+        assert_eq!((section.kind, section.index), (SectionKind::Drop, 1));
+        assert!((section.elapsed - 1.2).abs() < 1e-5);
+    }
+
+    #[test]
+    fn replay_keeps_time_and_onsets() {
+        let frames = (0..10)
+            .map(|i| Record {
+                t: i as f32 * 0.1,
+                onset: i == 3,
+                ..Default::default()
+            })
+            .collect();
+        let mut replay = Replay::new(Recording { frames });
+        assert_eq!(replay.advance(0.0).t, 0.0);
+        // frame 3 is skipped over but its onset still comes through
+        let r = replay.advance(0.45);
+        assert!((r.t - 0.4).abs() < 1e-6);
+        assert!(r.onset);
+        assert!(!replay.advance(0.1).onset);
+
+        replay.seek(0.3);
+        replay.set_paused(true);
+        assert!(replay.advance(0.5).onset, "landing on an onset shows it");
+        assert!(
+            !replay.advance(0.5).onset,
+            "but holding it doesn't repeat it"
+        );
+
+        // past the end wraps around
+        replay.set_paused(false);
+        replay.seek(0.85);
+        assert!((replay.advance(0.2).t - 0.1).abs() < 1e-6);
+    }
+}
@@ -0,0 +1,158 @@
+use crate::analysis::{AnalysisFrame, Analyzer, AnalyzerParams, TrackAnalysis};
+use crate::audio::AudioData;
+use crate::canvas::Pixmap;
+use crate::visual::{Registry, Snapshot};
+use crate::{BARS, FFT_SIZE};
+use anyhow::Context;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process::{Child, Command, Stdio};
+
+pub struct RenderOptions {
+    pub file: String,
+    pub out: PathBuf,
+    pub fps: u32,
+    pub width: u32,
+    pub height: u32,
+    pub seconds: Option<f32>,
+    pub analyzer: AnalyzerParams,
+    /// Scale the file so its peak reaches full scale
+    pub normalize: bool,
+}
+
+/// Where finished frames go.
+enum Output {
+    /// Numbered PNGs in a directory
+    Png(PathBuf),
+    /// Raw RGBA piped into ffmpeg, which muxes in the source audio
+    Video(Child),
+}
+
+impl Output {
+    fn open(opts: &RenderOptions) -> anyhow::Result<Self> {
+        // anything that looks like a file name is a video; otherwise a frame directory
+        if opts.out.extension().is_some() && !opts.out.is_dir() {
+            return Ok(Self::Video(spawn_ffmpeg(opts)?));
+        }
+        std::fs::create_dir_all(&opts.out)
+            .with_context(|| format!("Failed to create {}", opts.out.display()))?;
+        Ok(Self::Png(opts.out.clone()))
+    }
+
+    fn write(&mut self, index: usize, pix: &Pixmap) -> anyhow::Result<()> {
+        let rgba = pix.to_rgba8();
+        match self {
+            Self::Png(dir) => {
+                let path = dir.join(format!("frame_{index:06}.png"));
+                nannou::image::save_buffer(
+                    &path,
+                    &rgba,
+                    pix.width,
+                    pix.height,
+                    nannou::image::ColorType::Rgba8,
+                )
+                .with_context(|| format!("Failed to write {}", path.display()))?;
+            }
+            Self::Video(ffmpeg) => {
+                let stdin = ffmpeg.stdin.as_mut().context("ffmpeg stdin not piped")?;
+                stdin
+                    .write_all(&rgba)
+                    .context("ffmpeg stopped accepting frames")?;
+            }
+        }
+        Ok(())
+    }
+
+    fn finish(self) -> anyhow::Result<()> {
+        if let Self::Video(mut ffmpeg) = self {
+            drop(ffmpeg.stdin.take()); // EOF tells ffmpeg to finalize the file
+            let status = ffmpeg.wait()?;
+            anyhow::ensure!(status.success(), "ffmpeg exited with an error");
+        }
+        Ok(())
+    }
+}
+
+fn spawn_ffmpeg(opts: &RenderOptions) -> anyhow::Result<Child> {
+    let size = format!("{}x{}", opts.width, opts.height);
+    let fps = opts.fps.to_string();
+    let mut cmd = Command::new("ffmpeg");
+    cmd.args(["-y", "-loglevel", "error"])
+        .args([
+            "-f", "rawvideo", "-pix_fmt", "rgba", "-s", &size, "-r", &fps,
+        ])
+        .args(["-i", "-", "-i", &opts.file]);
+    if let Some(secs) = opts.seconds {
+        cmd.args(["-t", &secs.to_string()]);
+    }
+    cmd.args(["-map", "0:v", "-map", "1:a"])
+        .args([
+            "-c:v",
+            "libx264",
+            "-pix_fmt",
+            "yuv420p",
+            "-c:a",
+            "aac",
+            "-shortest",
+        ])
+        .arg(&opts.out)
+        .stdin(Stdio::piped())
+        .stderr(Stdio::inherit())
+        .spawn()
+        .context("Failed to spawn ffmpeg — install with: brew install ffmpeg")
+}
+
+/// Renders a WAV through the selected visualizer without opening a window. Frame `k` shows the
+/// analysis of the window centred on `k / fps` seconds, and the analyzer runs
+/// exactly once per frame, so the same input always gives the same output.
+pub fn run(opts: &RenderOptions, mut visuals: Registry) -> anyhow::Result<()> {
+    anyhow::ensure!(opts.fps > 0, "--fps must be at least 1");
+    let audio = AudioData::load_wav(Path::new(&opts.file))?;
+    let ahead = TrackAnalysis::load(Path::new(&opts.file), &audio)?;
+    let gain = if opts.normalize { ahead.gain() } else { 1.0 };
+    let mut analyzer = Analyzer::new(FFT_SIZE, BARS);
+    analyzer.set_params(&opts.analyzer);
+    let mut pix = Pixmap::new(opts.width, opts.height);
+    let mut output = Output::open(opts)?;
+
+    let seconds = opts
+        .seconds
+        .map_or(audio.duration_sec, |s| s.min(audio.duration_sec));
+    let frames = (seconds * opts.fps as f32).ceil() as usize;
+    let dt = 1.0 / opts.fps as f32;
+
+    let mut window = Vec::with_capacity(FFT_SIZE);
+    let mut stereo = Vec::with_capacity(FFT_SIZE);
+    eprintln!("[render] {frames} frames at {} fps…", opts.fps);
+    for k in 0..frames {
+        let t = k as f32 * dt;
+        audio.window_at_time(t, FFT_SIZE, &mut window);
+        audio.stereo_window_at_time(t, FFT_SIZE, &mut stereo);
+        window.iter_mut().for_each(|s| *s *= gain);
+        stereo.iter_mut().flatten().for_each(|s| *s *= gain);
+        let frame = AnalysisFrame {
+            lookahead: Some(ahead.at(t)),
+            section: ahead.section_at(t),
+            ..analyzer.analyze(&window, audio.sample_rate)
+        };
+
+        let snap = Snapshot {
+            frame: &frame,
+            waveform: &window,
+            stereo: &stereo,
+            history: analyzer.history(),
+            sample_rate: audio.sample_rate,
+        };
+        visuals.update(&snap, dt);
+        visuals.draw(&mut pix, &snap);
+        output.write(k, &pix)?;
+
+        if (k + 1) % (opts.fps as usize * 10) == 0 {
+            eprintln!("[render] {:.0}s / {seconds:.0}s", (k + 1) as f32 * dt);
+        }
+    }
+
+    output.finish()?;
+    eprintln!("[render] wrote {}", opts.out.display());
+    Ok(())
+}
@@ -0,0 +1,30 @@
+use std::path::{Path, PathBuf};
+
+/// A fresh directory under the system temp directory for one test's files.
+/// It's removed on drop, so a failing test doesn't leave them behind.
+pub struct TempDir(PathBuf);
+
+impl TempDir {
+    /// `name` keeps tests apart, the process id keeps test runs apart.
+    pub fn new(name: &str) -> Self {
+        let dir = std::env::temp_dir().join(format!("{name}-{}", std::process::id()));
+        // left over from a run that was killed
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        Self(dir)
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.0
+    }
+
+    pub fn join(&self, path: impl AsRef<Path>) -> PathBuf {
+        self.0.join(path)
+    }
+}
+
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.0);
+    }
+}
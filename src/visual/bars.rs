@@ -0,0 +1,100 @@
+use super::{Snapshot, Visualizer};
+use crate::canvas::{Canvas, hsva, rgba};
+use crate::preset::Preset;
+use nannou::prelude::*;
+use serde::{Deserialize, Serialize};
+
+/// Classic equalizer: vertical bars across the bottom with falling peak caps.
+pub struct BarsVisualizer {
+    pub bars: usize,
+    pub params: BarsParams,
+
+    peaks: Vec<f32>, // cap heights as a fraction of the window height
+    hue_shift: f32,
+}
+
+/// The `[bars]` section of a preset.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct BarsParams {
+    pub bar_gain: f32,
+    pub peak_fall: f32, // fraction of the window height per second
+}
+
+impl Default for BarsParams {
+    fn default() -> Self {
+        Self {
+            bar_gain: 1.6,
+            peak_fall: 0.35,
+        }
+    }
+}
+
+impl BarsVisualizer {
+    pub fn new(bars: usize) -> Self {
+        Self {
+            bars,
+            params: BarsParams::default(),
+            peaks: vec![0.0; bars],
+            hue_shift: 0.0,
+        }
+    }
+
+    fn level(&self, v: f32) -> f32 {
+        (v * self.params.bar_gain).clamp(0.0, 1.0)
+    }
+}
+
+impl Visualizer for BarsVisualizer {
+    fn name(&self) -> &'static str {
+        "bars"
+    }
+
+    fn shift_hue(&mut self, delta: f32) {
+        self.hue_shift += delta;
+    }
+
+    fn reset_view(&mut self) {
+        self.hue_shift = 0.0;
+    }
+
+    fn apply_preset(&mut self, preset: &Preset) {
+        self.params = preset.bars.clone();
+    }
+
+    fn update(&mut self, snap: &Snapshot, dt: f32) {
+        let fall = self.params.peak_fall * dt;
+        for i in 0..self.peaks.len() {
+            let level = self.level(snap.frame.bands.get(i).copied().unwrap_or(0.0));
+            self.peaks[i] = (self.peaks[i] - fall).max(level);
+        }
+    }
+
+    fn draw(&self, canvas: &mut dyn Canvas, snap: &Snapshot) {
+        let win = canvas.bounds();
+        canvas.fill(rgba(0.0, 0.0, 0.0, 1.0));
+
+        let bars_f = self.bars as f32;
+        let slot = win.w() / bars_f;
+        let bar_w = (slot - 2.0).max(1.0);
+        let max_h = win.h() * 0.9;
+        let hue_shift = snap.frame.bass_smooth * 0.2 + self.hue_shift;
+
+        for (i, &v) in snap.frame.bands.iter().take(self.bars).enumerate() {
+            let x = win.left() + (i as f32 + 0.5) * slot;
+            let h = (0.66 - (i as f32 / bars_f) * 0.66 + hue_shift).fract();
+
+            let bar_h = self.level(v) * max_h;
+            if bar_h >= 1.0 {
+                canvas.rect(
+                    pt2(x, win.bottom() + bar_h * 0.5),
+                    vec2(bar_w, bar_h),
+                    hsva(h, 0.85, 0.95, 0.9),
+                );
+            }
+
+            let cap_y = win.bottom() + self.peaks[i] * max_h + 3.0;
+            canvas.rect(pt2(x, cap_y), vec2(bar_w, 3.0), hsva(h, 0.3, 1.0, 1.0));
+        }
+    }
+}
@@ -0,0 +1,266 @@
+//! Golden-image regression tests: fixed synthetic analysis frames go through
+//! `RadialVisualizer` on the software canvas and the result is compared with a
+//! stored reference under `tests/golden/`.
+//!
+//! After an intentional visual change, re-bless the references with
+//! `BLESS=1 cargo test golden` and review the new PNGs before committing.
+//! Failing cases write `<case>.actual.png` and `<case>.diff.png` to
+//! `target/golden/` for inspection.
+
+use super::palette::Mapping;
+use super::{RadialVisualizer, Snapshot, Visualizer};
+use crate::analysis::{AnalysisFrame, SpectrumHistory};
+use crate::canvas::Pixmap;
+use crate::preset::Preset;
+use std::f32::consts::TAU;
+use std::path::PathBuf;
+
+const BARS: usize = 120;
+const WINDOW: usize = 2048;
+const SAMPLE_RATE: u32 = 44_100;
+const SQUARE: (u32, u32) = (400, 400);
+const PORTRAIT: (u32, u32) = (270, 480);
+const LANDSCAPE: (u32, u32) = (480, 270);
+const FRAMES: usize = 12; // enough for the fade trail to build up
+
+/// A pixel counts as different once its CIE76 ΔE exceeds this (≈ just noticeable).
+const JND: f32 = 2.3;
+/// Share of pixels allowed to differ noticeably before the test fails.
+const MAX_DIFF_RATIO: f32 = 0.002;
+/// Ceiling on the mean ΔE, catching subtle global shifts (brightness, hue).
+const MAX_MEAN_DE: f32 = 0.5;
+
+struct Input {
+    frame: AnalysisFrame,
+    waveform: Vec<f32>,
+}
+
+fn sine(freq: f32, amp: f32) -> Vec<f32> {
+    (0..WINDOW)
+        .map(|i| amp * (TAU * freq * i as f32 / SAMPLE_RATE as f32).sin())
+        .collect()
+}
+
+/// Deterministic pseudo-random values in -1..1.
+fn noise(seed: u32, n: usize) -> Vec<f32> {
+    let mut x = seed.max(1);
+    (0..n)
+        .map(|_| {
+            x ^= x << 13;
+            x ^= x >> 17;
+            x ^= x << 5;
+            (x >> 8) as f32 / (1u32 << 23) as f32 - 1.0
+        })
+        .collect()
+}
+
+fn frame(bands: Vec<f32>, bass: f32) -> AnalysisFrame {
+    AnalysisFrame {
+        bands,
+        bass_fast: bass,
+        bass_smooth: bass * 0.8,
+        ..Default::default()
+    }
+}
+
+fn silence(_k: usize) -> Input {
+    Input {
+        frame: frame(vec![0.0; BARS], 0.0),
+        waveform: vec![0.0; WINDOW],
+    }
+}
+
+/// A single spectral peak walking up the bands, one step per frame.
+fn sine_sweep(k: usize) -> Input {
+    let center = (k as f32 + 1.0) / FRAMES as f32 * (BARS - 1) as f32;
+    let bands = (0..BARS)
+        .map(|b| 0.5 * (-((b as f32 - center) / 3.0).powi(2)).exp())
+        .collect();
+    let freq = 40.0 * 400f32.powf(k as f32 / FRAMES as f32);
+    Input {
+        frame: frame(bands, 0.0),
+        waveform: sine(freq, 0.6),
+    }
+}
+
+fn full_band_noise(k: usize) -> Input {
+    let bands = noise(k as u32 + 1, BARS)
+        .into_iter()
+        .map(|v| 0.25 + 0.1 * v)
+        .collect();
+    Input {
+        frame: frame(bands, 0.3),
+        waveform: noise(k as u32 + 100, WINDOW)
+            .into_iter()
+            .map(|v| v * 0.5)
+            .collect(),
+    }
+}
+
+fn heavy_bass(k: usize) -> Input {
+    let bands = (0..BARS)
+        .map(|b| 0.9 * (-(b as f32) / 12.0).exp())
+        .collect();
+    // kick envelope: hits on frame 0 and decays
+    let kick = (-(k as f32) * 0.25).exp();
+    Input {
+        frame: frame(bands, 0.4 + 0.6 * kick),
+        waveform: sine(55.0, 0.9),
+    }
+}
+
+fn render(case: fn(usize) -> Input, (width, height): (u32, u32), preset: &Preset) -> Pixmap {
+    let mut visual = RadialVisualizer::new(BARS);
+    visual.apply_preset(preset);
+    let mut pix = Pixmap::new(width, height);
+    let history = SpectrumHistory::new(1, BARS);
+    for k in 0..FRAMES {
+        let input = case(k);
+        let stereo: Vec<[f32; 2]> = input.waveform.iter().map(|&s| [s, s]).collect();
+        let snap = Snapshot {
+            frame: &input.frame,
+            waveform: &input.waveform,
+            stereo: &stereo,
+            history: &history,
+            sample_rate: SAMPLE_RATE,
+        };
+        visual.update(&snap, 1.0 / 60.0);
+        visual.draw(&mut pix, &snap);
+    }
+    pix
+}
+
+fn srgb_to_lab(c: &[u8]) -> [f32; 3] {
+    let lin = |v: u8| {
+        let v = v as f32 / 255.0;
+        if v <= 0.04045 {
+            v / 12.92
+        } else {
+            ((v + 0.055) / 1.055).powf(2.4)
+        }
+    };
+    let (r, g, b) = (lin(c[0]), lin(c[1]), lin(c[2]));
+    // linear sRGB → XYZ (D65), normalized by the white point
+    let x = (0.4124 * r + 0.3576 * g + 0.1805 * b) / 0.95047;
+    let y = 0.2126 * r + 0.7152 * g + 0.0722 * b;
+    let z = (0.0193 * r + 0.1192 * g + 0.9505 * b) / 1.08883;
+    let f = |t: f32| {
+        if t > 0.008856 {
+            t.cbrt()
+        } else {
+            7.787 * t + 16.0 / 116.0
+        }
+    };
+    let (fx, fy, fz) = (f(x), f(y), f(z));
+    [116.0 * fy - 16.0, 500.0 * (fx - fy), 200.0 * (fy - fz)]
+}
+
+fn delta_e(a: &[u8], b: &[u8]) -> f32 {
+    let (la, lb) = (srgb_to_lab(a), srgb_to_lab(b));
+    la.iter()
+        .zip(&lb)
+        .map(|(x, y)| (x - y) * (x - y))
+        .sum::<f32>()
+        .sqrt()
+}
+
+fn save(path: &PathBuf, rgba: &[u8], (width, height): (u32, u32)) {
+    nannou::image::save_buffer(path, rgba, width, height, nannou::image::ColorType::Rgba8)
+        .unwrap_or_else(|e| panic!("failed to write {}: {e}", path.display()));
+}
+
+fn check(name: &str, case: fn(usize) -> Input, size: (u32, u32)) {
+    check_with(name, case, size, &Preset::default());
+}
+
+fn check_with(name: &str, case: fn(usize) -> Input, size: (u32, u32), preset: &Preset) {
+    let root = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+    let reference = root.join("tests/golden").join(format!("{name}.png"));
+    let actual = render(case, size, preset).to_rgba8();
+
+    if std::env::var_os("BLESS").is_some() {
+        std::fs::create_dir_all(reference.parent().unwrap()).unwrap();
+        save(&reference, &actual, size);
+        return;
+    }
+
+    let expected = nannou::image::open(&reference)
+        .unwrap_or_else(|e| {
+            panic!(
+                "missing reference {} ({e}); run `BLESS=1 cargo test golden`",
+                reference.display()
+            )
+        })
+        .to_rgba8();
+    assert_eq!(
+        expected.dimensions(),
+        size,
+        "{name}: reference has the wrong size"
+    );
+
+    let mut diff = Vec::with_capacity(actual.len());
+    let (mut noticeable, mut total) = (0usize, 0.0f32);
+    for (a, e) in actual.chunks(4).zip(expected.as_raw().chunks(4)) {
+        let de = delta_e(a, e);
+        total += de;
+        if de > JND {
+            noticeable += 1;
+        }
+        let v = (de * 10.0).min(255.0) as u8;
+        diff.extend_from_slice(&[v, v, v, 255]);
+    }
+    let pixels = (size.0 * size.1) as f32;
+    let ratio = noticeable as f32 / pixels;
+    let mean = total / pixels;
+
+    if ratio > MAX_DIFF_RATIO || mean > MAX_MEAN_DE {
+        let out = root.join("target/golden");
+        std::fs::create_dir_all(&out).unwrap();
+        save(&out.join(format!("{name}.actual.png")), &actual, size);
+        save(&out.join(format!("{name}.diff.png")), &diff, size);
+        panic!(
+            "{name}: {:.2}% of pixels differ noticeably (max {:.2}%), mean ΔE {mean:.3} \
+             (max {MAX_MEAN_DE}); see target/golden/, or re-bless with `BLESS=1 cargo test golden`",
+            ratio * 100.0,
+            MAX_DIFF_RATIO * 100.0,
+        );
+    }
+}
+
+#[test]
+fn golden_radial_silence() {
+    check("radial_silence", silence, SQUARE);
+}
+
+#[test]
+fn golden_radial_sine_sweep() {
+    check("radial_sine_sweep", sine_sweep, SQUARE);
+}
+
+#[test]
+fn golden_radial_full_band_noise() {
+    check("radial_full_band_noise", full_band_noise, SQUARE);
+}
+
+#[test]
+fn golden_radial_heavy_bass() {
+    check("radial_heavy_bass", heavy_bass, SQUARE);
+}
+
+#[test]
+fn golden_radial_portrait() {
+    check("radial_portrait", heavy_bass, PORTRAIT);
+}
+
+#[test]
+fn golden_radial_landscape() {
+    check("radial_landscape", sine_sweep, LANDSCAPE);
+}
+
+#[test]
+fn golden_radial_gradient_palette() {
+    let mut preset = Preset::default();
+    preset.radial.color.palette = "sunset".into();
+    preset.radial.color.mapping = Mapping::Energy;
+    check_with("radial_gradient_palette", heavy_bass, SQUARE, &preset);
+}
@@ -1,3 +1,63 @@
+pub mod bars;
+#[cfg(test)]
+mod golden;
+pub mod oscilloscope;
+pub mod palette;
+pub mod particles;
 pub mod radial;
+pub mod registry;
+pub mod spectrogram;
+pub mod vectorscope;
 
-pub use radial::RadialVisualizer;
\ No newline at end of file
+pub use bars::{BarsParams, BarsVisualizer};
+pub use oscilloscope::{OscilloscopeParams, OscilloscopeVisualizer};
+pub use particles::{ParticleParams, ParticleVisualizer};
+pub use radial::{RadialParams, RadialVisualizer};
+pub use registry::Registry;
+pub use spectrogram::{Colormap, Scroll, SpectrogramParams, SpectrogramVisualizer};
+pub use vectorscope::{VectorscopeParams, VectorscopeVisualizer};
+
+use crate::analysis::{AnalysisFrame, SpectrumHistory};
+use crate::canvas::Canvas;
+use crate::preset::Preset;
+use nannou::prelude::*;
+
+/// Everything a visualizer may look at for one frame, shared by all of them.
+pub struct Snapshot<'a> {
+    pub frame: &'a AnalysisFrame,
+    pub waveform: &'a [f32],
+    pub stereo: &'a [[f32; 2]], // same window as `waveform`, left/right kept apart
+    pub history: &'a SpectrumHistory,
+    pub sample_rate: u32,
+}
+
+pub trait Visualizer {
+    /// Short lowercase name used on the command line.
+    fn name(&self) -> &'static str;
+
+    /// Takes this visualizer's parameters from `preset`. Runtime state (trails,
+    /// particles, peak caps) carries over so switching presets doesn't jump.
+    fn apply_preset(&mut self, _preset: &Preset) {}
+
+    /// Advances internal state (peak caps, particles, …) once per update.
+    fn update(&mut self, _snap: &Snapshot, _dt: f32) {}
+
+    /// Keys not claimed by the app are forwarded to the active visualizer.
+    fn key_pressed(&mut self, _key: Key) {}
+
+    /// Mouse movement with a button held, in pixels; active visualizer only.
+    fn mouse_dragged(&mut self, _delta: Vec2) {}
+
+    /// Scroll wheel movement in lines, positive away from the user.
+    fn mouse_scrolled(&mut self, _lines: f32) {}
+
+    /// Moves colours along the hue circle or palette by `delta` turns.
+    fn shift_hue(&mut self, _delta: f32) {}
+
+    /// Drops live tweaks (hue shift, rotation, zoom) that presets don't cover.
+    fn reset_view(&mut self) {}
+
+    /// Draws one frame. Anything that can rasterize the `Canvas` primitives —
+    /// the window or an in-memory image — works as a target.
+    fn draw(&self, canvas: &mut dyn Canvas, snap: &Snapshot);
+}
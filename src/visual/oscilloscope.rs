@@ -0,0 +1,149 @@
+use super::{Snapshot, Visualizer};
+use crate::analysis::{Trigger, TriggerMode};
+use crate::canvas::{Align, Canvas, rgba};
+use crate::preset::Preset;
+use nannou::prelude::*;
+use serde::{Deserialize, Serialize};
+
+/// Triggered time-domain trace of the current analysis window.
+///
+/// Keys while active: `T` cycles the trigger mode, Left/Right change the
+/// timebase, Up/Down change the gain.
+pub struct OscilloscopeVisualizer {
+    pub params: OscilloscopeParams,
+
+    trigger: Trigger,
+    trace: Vec<f32>,
+    max_ms: f32, // longest timebase the analysis window leaves room for
+}
+
+/// The `[oscilloscope]` section of a preset.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct OscilloscopeParams {
+    pub gain: f32,
+    pub timebase_ms: f32, // width of the visible trace
+    pub trigger: TriggerMode,
+    pub trigger_level: f32,
+    pub hysteresis: f32,
+}
+
+impl Default for OscilloscopeParams {
+    fn default() -> Self {
+        Self {
+            gain: 1.0,
+            timebase_ms: 20.0,
+            trigger: TriggerMode::Rising,
+            trigger_level: 0.1,
+            hysteresis: 0.02,
+        }
+    }
+}
+
+impl OscilloscopeVisualizer {
+    pub fn new() -> Self {
+        let params = OscilloscopeParams::default();
+        Self {
+            trigger: Trigger::new(params.trigger),
+            params,
+            trace: Vec::new(),
+            max_ms: 500.0,
+        }
+    }
+}
+
+impl Visualizer for OscilloscopeVisualizer {
+    fn name(&self) -> &'static str {
+        "oscilloscope"
+    }
+
+    fn apply_preset(&mut self, preset: &Preset) {
+        self.params = preset.oscilloscope.clone();
+    }
+
+    fn update(&mut self, snap: &Snapshot, _dt: f32) {
+        self.trigger.mode = self.params.trigger;
+        self.trigger.level = self.params.trigger_level;
+        self.trigger.hysteresis = self.params.hysteresis;
+
+        let wave = snap.waveform;
+        // keep at least half the window free so the trigger has room to search
+        let rate = snap.sample_rate as f32;
+        let longest = (wave.len() / 2).max(2);
+        if wave.len() >= 4 && rate > 0.0 {
+            self.max_ms = longest as f32 / rate * 1000.0;
+        }
+        let span = (self.params.timebase_ms.min(self.max_ms) * 0.001 * rate).round() as usize;
+        let span = span.clamp(2, longest);
+        let start = self.trigger.find(wave, span);
+
+        self.trace.clear();
+        self.trace
+            .extend_from_slice(&wave[start.min(wave.len())..(start + span).min(wave.len())]);
+    }
+
+    fn key_pressed(&mut self, key: Key) {
+        let p = &mut self.params;
+        match key {
+            Key::T => p.trigger = p.trigger.next(),
+            Key::Left => p.timebase_ms = (p.timebase_ms.min(self.max_ms) / 1.25).max(1.0),
+            Key::Right => p.timebase_ms = (p.timebase_ms * 1.25).min(self.max_ms),
+            Key::Up => p.gain = (p.gain * 1.25).min(64.0),
+            Key::Down => p.gain = (p.gain / 1.25).max(0.05),
+            _ => {}
+        }
+    }
+
+    fn draw(&self, canvas: &mut dyn Canvas, snap: &Snapshot) {
+        let win = canvas.bounds();
+        let p = &self.params;
+        canvas.fill(rgba(0.0, 0.0, 0.0, 1.0));
+
+        // centre line, plus the trigger level when it matters
+        canvas.line(
+            pt2(win.left(), 0.0),
+            pt2(win.right(), 0.0),
+            1.0,
+            rgba(0.3, 0.3, 0.3, 1.0),
+        );
+
+        let half_h = win.h() * 0.45;
+        if p.trigger == TriggerMode::Level {
+            let y = (p.trigger_level * p.gain).clamp(-1.0, 1.0) * half_h;
+            canvas.line(
+                pt2(win.left(), y),
+                pt2(win.left() + 20.0, y),
+                2.0,
+                rgba(1.0, 0.6, 0.2, 1.0),
+            );
+        }
+
+        if self.trace.len() < 2 {
+            return;
+        }
+        let step = win.w() / (self.trace.len() - 1) as f32;
+        let pts: Vec<Point2> = self
+            .trace
+            .iter()
+            .enumerate()
+            .map(|(i, &s)| {
+                let y = (s * p.gain).clamp(-1.0, 1.0) * half_h;
+                pt2(win.left() + i as f32 * step, y)
+            })
+            .collect();
+
+        let glow = (0.2 + snap.frame.bass_smooth * 0.4).clamp(0.2, 0.6);
+        canvas.polyline(&pts, 5.0, rgba(0.2, 1.0, 0.5, glow));
+        canvas.polyline(&pts, 1.5, rgba(0.7, 1.0, 0.8, 1.0));
+
+        // timebase / gain / mode readout, as drawn rather than as asked
+        let ms = p.timebase_ms.min(self.max_ms);
+        canvas.text(
+            &format!("{:?}  {:.1} ms  x{:.2}", p.trigger, ms, p.gain),
+            pt2(win.right() - 10.0, win.top() - 16.0),
+            12,
+            Align::Right,
+            rgba(0.6, 0.6, 0.6, 1.0),
+        );
+    }
+}
@@ -0,0 +1,336 @@
+use crate::canvas::{Rgba, hsva};
+use anyhow::{Context, bail, ensure};
+use serde::{Deserialize, Serialize};
+use std::f32::consts::TAU;
+use std::path::{Path, PathBuf};
+
+/// How a palette is defined in a preset's `[palettes.<name>]` table.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "lowercase", deny_unknown_fields)]
+pub enum PaletteDef {
+    /// The full HSV hue circle
+    Rainbow,
+    /// Straight lines between colour stops, `[[position, colour], …]`
+    Gradient {
+        stops: Vec<(f32, Color)>,
+        #[serde(default)]
+        wrap: bool,
+    },
+    /// `a + b·cos(2π(c·t + d))` per channel, after Inigo Quilez
+    Cosine {
+        a: [f32; 3],
+        b: [f32; 3],
+        c: [f32; 3],
+        d: [f32; 3],
+    },
+    /// Colours read along an image strip, left to right (or top to bottom
+    /// if it's taller than wide). Relative paths start at the preset file.
+    Image {
+        path: PathBuf,
+        #[serde(default)]
+        wrap: bool,
+    },
+}
+
+/// `"#rrggbb"` or `[r, g, b]` in 0..1.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum Color {
+    Hex(String),
+    Rgb([f32; 3]),
+}
+
+impl Color {
+    pub fn to_rgb(&self) -> anyhow::Result<[f32; 3]> {
+        match self {
+            Self::Rgb(rgb) => Ok(*rgb),
+            Self::Hex(hex) => {
+                let digits = hex.trim_start_matches('#');
+                ensure!(
+                    digits.len() == 6 && digits.is_ascii(),
+                    "'{hex}' is not a #rrggbb colour"
+                );
+                let channel = |i: usize| -> anyhow::Result<f32> {
+                    let v = u8::from_str_radix(&digits[i..i + 2], 16)
+                        .with_context(|| format!("'{hex}' is not a #rrggbb colour"))?;
+                    Ok(v as f32 / 255.0)
+                };
+                Ok([channel(0)?, channel(2)?, channel(4)?])
+            }
+        }
+    }
+}
+
+/// Which value picks each element's position in the palette.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Mapping {
+    /// Spread across the bands, so the palette runs once around the ring
+    Band,
+    /// Each band's level: quiet bands at the start, loud ones at the end
+    Energy,
+    /// Everything shares one colour that drifts through the palette over time
+    Time,
+}
+
+/// Colour settings of a visualizer, e.g. `[radial.color]`.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ColorParams {
+    /// A name from the preset's `[palettes]`, or a built-in one
+    pub palette: String,
+    pub mapping: Mapping,
+    pub offset: f32,      // shifts every position along the palette
+    pub bass_shift: f32,  // extra offset per unit of smoothed bass
+    pub speed: f32,       // palette lengths per second, for `time`
+    pub energy_gain: f32, // band level that reaches the palette end is 1 / energy_gain
+}
+
+impl Default for ColorParams {
+    fn default() -> Self {
+        Self {
+            palette: "rainbow".into(),
+            mapping: Mapping::Band,
+            offset: 0.0,
+            bass_shift: 0.30,
+            speed: 0.05,
+            energy_gain: 2.5,
+        }
+    }
+}
+
+impl ColorParams {
+    /// Palette position of element `i` of `n` with level `level`, at `time` seconds.
+    pub fn position(&self, i: usize, n: usize, level: f32, bass: f32, time: f32) -> f32 {
+        let base = self.offset + bass * self.bass_shift;
+        match self.mapping {
+            Mapping::Band => base + i as f32 / n.max(1) as f32,
+            Mapping::Energy => base + (level * self.energy_gain).clamp(0.0, 1.0),
+            Mapping::Time => base + time * self.speed,
+        }
+    }
+}
+
+/// Palettes available to every preset without defining them.
+pub const BUILTIN: &[&str] = &["rainbow", "sunset", "ocean", "fire", "mono", "iq"];
+
+fn builtin(name: &str) -> Option<PaletteDef> {
+    let stops = |s: &[(f32, &str)]| PaletteDef::Gradient {
+        stops: s.iter().map(|&(t, c)| (t, Color::Hex(c.into()))).collect(),
+        wrap: false,
+    };
+    Some(match name {
+        "rainbow" => PaletteDef::Rainbow,
+        "sunset" => stops(&[
+            (0.0, "#2d0b59"),
+            (0.35, "#b8236b"),
+            (0.7, "#f96e46"),
+            (1.0, "#ffd97d"),
+        ]),
+        "ocean" => stops(&[(0.0, "#03045e"), (0.5, "#0096c7"), (1.0, "#caf0f8")]),
+        "fire" => stops(&[
+            (0.0, "#3a0000"),
+            (0.4, "#c1121f"),
+            (0.75, "#f77f00"),
+            (1.0, "#fcbf49"),
+        ]),
+        "mono" => stops(&[(0.0, "#303030"), (1.0, "#ffffff")]),
+        "iq" => PaletteDef::Cosine {
+            a: [0.5, 0.5, 0.5],
+            b: [0.5, 0.5, 0.5],
+            c: [1.0, 1.0, 1.0],
+            d: [0.0, 0.33, 0.67],
+        },
+        _ => return None,
+    })
+}
+
+/// A palette ready to sample: positions map to colours, and positions outside
+/// 0..1 either wrap around or bounce back so the colour never jumps.
+#[derive(Clone, Debug, Default)]
+pub enum Palette {
+    #[default]
+    Rainbow,
+    Cosine([[f32; 3]; 4]),
+    Stops {
+        stops: Vec<(f32, [f32; 3])>,
+        wrap: bool,
+    },
+}
+
+impl Palette {
+    /// Looks `name` up among `custom` first, then the built-ins.
+    pub fn resolve(
+        name: &str,
+        custom: &std::collections::BTreeMap<String, PaletteDef>,
+    ) -> anyhow::Result<Self> {
+        match custom.get(name).cloned().or_else(|| builtin(name)) {
+            Some(def) => Self::build(&def).with_context(|| format!("palette '{name}'")),
+            None => {
+                let mut names: Vec<&str> = custom.keys().map(String::as_str).collect();
+                names.extend(BUILTIN);
+                bail!(
+                    "Unknown palette '{name}', expected one of: {}",
+                    names.join(", ")
+                )
+            }
+        }
+    }
+
+    pub fn build(def: &PaletteDef) -> anyhow::Result<Self> {
+        Ok(match def {
+            PaletteDef::Rainbow => Self::Rainbow,
+            PaletteDef::Cosine { a, b, c, d } => Self::Cosine([*a, *b, *c, *d]),
+            PaletteDef::Gradient { stops, wrap } => {
+                ensure!(!stops.is_empty(), "a gradient needs at least one stop");
+                let mut out = stops
+                    .iter()
+                    .map(|(t, c)| Ok((*t, c.to_rgb()?)))
+                    .collect::<anyhow::Result<Vec<_>>>()?;
+                out.sort_by(|a, b| a.0.total_cmp(&b.0));
+                Self::Stops {
+                    stops: out,
+                    wrap: *wrap,
+                }
+            }
+            PaletteDef::Image { path, wrap } => Self::Stops {
+                stops: strip(path)?,
+                wrap: *wrap,
+            },
+        })
+    }
+
+    /// Colour at palette position `t`.
+    pub fn sample(&self, t: f32) -> [f32; 3] {
+        match self {
+            Self::Rainbow => {
+                let [r, g, b, _] = hsva(t, 1.0, 1.0, 1.0);
+                [r, g, b]
+            }
+            Self::Cosine([a, b, c, d]) => std::array::from_fn(|i| {
+                (a[i] + b[i] * (TAU * (c[i] * t + d[i])).cos()).clamp(0.0, 1.0)
+            }),
+            Self::Stops { stops, wrap } => {
+                let t = if *wrap {
+                    t.rem_euclid(1.0)
+                } else {
+                    // ping-pong: 0 → 1 → 0, seamless around a ring
+                    1.0 - ((t.rem_euclid(2.0)) - 1.0).abs()
+                };
+                lerp_stops(stops, t)
+            }
+        }
+    }
+
+    pub fn rgba(&self, t: f32, alpha: f32) -> Rgba {
+        let [r, g, b] = self.sample(t);
+        [r, g, b, alpha]
+    }
+}
+
+fn lerp_stops(stops: &[(f32, [f32; 3])], t: f32) -> [f32; 3] {
+    let next = stops.partition_point(|s| s.0 < t);
+    if next == 0 {
+        return stops[0].1;
+    }
+    if next == stops.len() {
+        return stops[next - 1].1;
+    }
+    let (t0, c0) = stops[next - 1];
+    let (t1, c1) = stops[next];
+    let f = if t1 > t0 { (t - t0) / (t1 - t0) } else { 1.0 };
+    std::array::from_fn(|i| c0[i] + (c1[i] - c0[i]) * f)
+}
+
+/// Evenly spaced stops read along the middle of an image's long axis.
+fn strip(path: &Path) -> anyhow::Result<Vec<(f32, [f32; 3])>> {
+    let img = nannou::image::open(path)
+        .with_context(|| format!("Failed to load palette image {}", path.display()))?
+        .to_rgb8();
+    let (w, h) = img.dimensions();
+    let len = w.max(h);
+    ensure!(len > 0, "palette image {} is empty", path.display());
+    // a few hundred stops is plenty for a smooth gradient
+    let n = len.min(256);
+    Ok((0..n)
+        .map(|i| {
+            let pos = ((i as f32 + 0.5) / n as f32 * len as f32) as u32;
+            let px = if w >= h {
+                img.get_pixel(pos.min(w - 1), h / 2)
+            } else {
+                img.get_pixel(w / 2, pos.min(h - 1))
+            };
+            let t = if n > 1 {
+                i as f32 / (n - 1) as f32
+            } else {
+                0.0
+            };
+            (t, px.0.map(|c| c as f32 / 255.0))
+        })
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testutil::TempDir;
+
+    fn close(a: [f32; 3], b: [f32; 3]) -> bool {
+        a.iter().zip(&b).all(|(x, y)| (x - y).abs() < 1e-3)
+    }
+
+    #[test]
+    fn rainbow_matches_hsva() {
+        for i in 0..20 {
+            let t = i as f32 * 0.137 - 0.5;
+            let [r, g, b, _] = hsva(t, 1.0, 1.0, 1.0);
+            assert!(close(Palette::Rainbow.sample(t), [r, g, b]));
+        }
+    }
+
+    #[test]
+    fn gradient_interpolates_and_mirrors() {
+        let def = PaletteDef::Gradient {
+            stops: vec![
+                (0.0, Color::Hex("#000000".into())),
+                (1.0, Color::Rgb([1.0, 0.5, 0.0])),
+            ],
+            wrap: false,
+        };
+        let p = Palette::build(&def).unwrap();
+        assert!(close(p.sample(0.5), [0.5, 0.25, 0.0]));
+        // past the end it comes back down instead of jumping to the start
+        assert!(close(p.sample(1.25), p.sample(0.75)));
+        assert!(close(p.sample(-0.25), p.sample(0.25)));
+    }
+
+    #[test]
+    fn bad_colours_and_names_are_errors() {
+        let def = PaletteDef::Gradient {
+            stops: vec![(0.0, Color::Hex("#12345".into()))],
+            wrap: false,
+        };
+        assert!(Palette::build(&def).is_err());
+        assert!(Palette::resolve("nope", &Default::default()).is_err());
+        for name in BUILTIN {
+            Palette::resolve(name, &Default::default()).unwrap();
+        }
+    }
+
+    #[test]
+    fn image_strip_is_sampled_along_its_long_axis() {
+        let dir = TempDir::new("palette");
+        let path = dir.join("strip.png");
+        // 4×1: red, green, blue, white
+        let px = [255, 0, 0, 0, 255, 0, 0, 0, 255, 255, 255, 255];
+        nannou::image::save_buffer(&path, &px, 4, 1, nannou::image::ColorType::Rgb8).unwrap();
+        let p = Palette::build(&PaletteDef::Image {
+            path: path.clone(),
+            wrap: false,
+        })
+        .unwrap();
+        assert!(close(p.sample(0.0), [1.0, 0.0, 0.0]));
+        assert!(close(p.sample(1.0), [1.0, 1.0, 1.0]));
+        assert!(close(p.sample(1.0 / 3.0), [0.0, 1.0, 0.0]));
+    }
+}
@@ -0,0 +1,334 @@
+use super::{Snapshot, Visualizer};
+use crate::analysis::AnalysisFrame;
+use crate::canvas::{Canvas, hsva, rgba};
+use crate::preset::Preset;
+use nannou::prelude::*;
+use serde::{Deserialize, Serialize};
+
+/// Simulation step; the sim always advances in these increments regardless of frame rate.
+pub const STEP: f32 = 1.0 / 120.0;
+
+/// What the simulation reacts to, sampled once per analysis frame.
+#[derive(Clone, Copy, Default)]
+pub struct Emission {
+    pub onset: bool,
+    pub strength: f32,
+    pub energy: f32,   // mean band level, scaled by `energy_gain`
+    pub centroid: f32, // 0 = dark, 1 = bright
+}
+
+impl Emission {
+    pub fn from_frame(frame: &AnalysisFrame, energy_gain: f32) -> Self {
+        let mean = frame.bands.iter().sum::<f32>() / frame.bands.len().max(1) as f32;
+        Self {
+            onset: frame.onset,
+            strength: frame.onset_strength,
+            energy: mean * energy_gain,
+            centroid: frame.centroid,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct Particle {
+    pub pos: Vec2,
+    pub vel: Vec2,
+    pub life: f32, // seconds left
+    pub max_life: f32,
+    pub hue: f32,
+    pub size: f32,
+}
+
+/// The `[particles]` section of a preset.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ParticleParams {
+    pub max_particles: usize,
+    pub burst: usize,      // particles per onset at strength 0
+    pub trickle: f32,      // particles per second at energy 1
+    pub speed: f32,        // px/s at energy 1
+    pub drag: f32,         // fraction of velocity lost per second
+    pub life: f32,         // seconds
+    pub spawn_radius: f32, // particles start on this ring
+    pub hue_base: f32,
+    pub hue_range: f32,
+
+    pub energy_gain: f32,
+    pub fade_alpha: f32,
+}
+
+impl Default for ParticleParams {
+    fn default() -> Self {
+        Self {
+            max_particles: 4000,
+            burst: 60,
+            trickle: 90.0,
+            speed: 260.0,
+            drag: 0.9,
+            life: 2.2,
+            spawn_radius: 40.0,
+            hue_base: 0.55,
+            hue_range: 0.45,
+
+            energy_gain: 8.0,
+            fade_alpha: 0.12,
+        }
+    }
+}
+
+/// Window-free particle simulation: bursts on onsets, a trickle proportional to
+/// band energy, speed from energy and hue from the spectral centroid.
+pub struct ParticleSystem {
+    pub particles: Vec<Particle>,
+    pub params: ParticleParams,
+
+    accumulator: f32,
+    pending_onset: Option<f32>, // strength of an onset not yet consumed by a step
+    trickle_debt: f32,
+    rng: XorShift,
+}
+
+impl ParticleSystem {
+    pub fn new(seed: u32) -> Self {
+        Self {
+            particles: Vec::new(),
+            params: ParticleParams::default(),
+
+            accumulator: 0.0,
+            pending_onset: None,
+            trickle_debt: 0.0,
+            rng: XorShift::new(seed),
+        }
+    }
+
+    /// Advances by `dt` seconds in whole `STEP`s and returns how many were taken.
+    /// Leftover time carries over to the next call; an onset is kept until a
+    /// step consumes it so short frames never drop a beat.
+    pub fn advance(&mut self, dt: f32, input: Emission) -> usize {
+        if input.onset {
+            self.pending_onset = Some(input.strength);
+        }
+        // cap catch-up after a stall so one long frame can't freeze the app
+        self.accumulator += dt.clamp(0.0, 0.25);
+
+        let mut steps = 0;
+        while self.accumulator >= STEP {
+            self.accumulator -= STEP;
+            self.step(input);
+            steps += 1;
+        }
+        steps
+    }
+
+    fn step(&mut self, input: Emission) {
+        if let Some(strength) = self.pending_onset.take() {
+            let n = (self.params.burst as f32 * (1.0 + strength.min(3.0))) as usize;
+            for _ in 0..n {
+                self.spawn(input, 1.0);
+            }
+        }
+
+        self.trickle_debt += self.params.trickle * input.energy * STEP;
+        while self.trickle_debt >= 1.0 {
+            self.trickle_debt -= 1.0;
+            self.spawn(input, 0.5);
+        }
+
+        let keep = (1.0 - self.params.drag * STEP).max(0.0);
+        for p in &mut self.particles {
+            p.pos += p.vel * STEP;
+            p.vel *= keep;
+            p.life -= STEP;
+        }
+        self.particles.retain(|p| p.life > 0.0);
+    }
+
+    fn spawn(&mut self, input: Emission, kick: f32) {
+        let p = &self.params;
+        if self.particles.len() >= p.max_particles {
+            return;
+        }
+        let theta = self.rng.next_f32() * TAU;
+        let dir = vec2(theta.cos(), theta.sin());
+        let speed = p.speed * (0.3 + input.energy) * (0.5 + 0.5 * self.rng.next_f32()) * kick;
+        let jitter = (self.rng.next_f32() - 0.5) * 0.08;
+        self.particles.push(Particle {
+            pos: dir * p.spawn_radius,
+            vel: dir * speed,
+            life: p.life * (0.6 + 0.4 * self.rng.next_f32()),
+            max_life: p.life,
+            hue: (p.hue_base + input.centroid * p.hue_range + jitter).rem_euclid(1.0),
+            size: 2.0 + 3.0 * self.rng.next_f32(),
+        });
+    }
+}
+
+/// Tiny deterministic PRNG so simulations are reproducible in tests.
+struct XorShift(u32);
+
+impl XorShift {
+    fn new(seed: u32) -> Self {
+        Self(seed.max(1))
+    }
+
+    fn next_f32(&mut self) -> f32 {
+        let mut x = self.0;
+        x ^= x << 13;
+        x ^= x >> 17;
+        x ^= x << 5;
+        self.0 = x;
+        (x >> 8) as f32 / (1u32 << 24) as f32
+    }
+}
+
+pub struct ParticleVisualizer {
+    pub system: ParticleSystem,
+    hue_shift: f32,
+}
+
+impl ParticleVisualizer {
+    pub fn new() -> Self {
+        Self {
+            system: ParticleSystem::new(0x5eed),
+            hue_shift: 0.0,
+        }
+    }
+}
+
+impl Visualizer for ParticleVisualizer {
+    fn name(&self) -> &'static str {
+        "particles"
+    }
+
+    fn shift_hue(&mut self, delta: f32) {
+        self.hue_shift += delta;
+    }
+
+    fn reset_view(&mut self) {
+        self.hue_shift = 0.0;
+    }
+
+    fn apply_preset(&mut self, preset: &Preset) {
+        self.system.params = preset.particles.clone();
+    }
+
+    fn update(&mut self, snap: &Snapshot, dt: f32) {
+        let input = Emission::from_frame(snap.frame, self.system.params.energy_gain);
+        self.system.advance(dt, input);
+    }
+
+    fn draw(&self, canvas: &mut dyn Canvas, _snap: &Snapshot) {
+        // same fade-trail trick as the radial view
+        canvas.fill(rgba(0.0, 0.0, 0.0, self.system.params.fade_alpha));
+
+        for p in &self.system.particles {
+            let t = (p.life / p.max_life).clamp(0.0, 1.0);
+            canvas.circle(
+                p.pos,
+                p.size * (0.4 + 0.6 * t),
+                hsva(p.hue + self.hue_shift, 0.85, 1.0, t),
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn beat(strength: f32) -> Emission {
+        Emission {
+            onset: true,
+            strength,
+            energy: 0.5,
+            centroid: 0.5,
+        }
+    }
+
+    #[test]
+    fn silence_spawns_nothing() {
+        let mut sys = ParticleSystem::new(1);
+        for _ in 0..120 {
+            sys.advance(1.0 / 60.0, Emission::default());
+        }
+        assert!(sys.particles.is_empty());
+    }
+
+    #[test]
+    fn onset_spawns_a_burst_that_dies_out() {
+        let mut sys = ParticleSystem::new(1);
+        sys.params.trickle = 0.0;
+        sys.advance(STEP, beat(0.0));
+        assert_eq!(sys.particles.len(), sys.params.burst);
+
+        let lifetime_steps = (sys.params.life / STEP) as usize + 2;
+        for _ in 0..lifetime_steps {
+            sys.advance(STEP, Emission::default());
+        }
+        assert!(sys.particles.is_empty());
+    }
+
+    #[test]
+    fn short_frames_keep_the_onset_until_a_step_runs() {
+        let mut sys = ParticleSystem::new(1);
+        sys.params.trickle = 0.0;
+        assert_eq!(sys.advance(STEP * 0.4, beat(0.0)), 0);
+        assert!(sys.particles.is_empty());
+        assert_eq!(sys.advance(STEP * 0.7, Emission::default()), 1);
+        assert_eq!(sys.particles.len(), sys.params.burst);
+    }
+
+    #[test]
+    fn result_does_not_depend_on_frame_rate() {
+        let mut a = ParticleSystem::new(7);
+        let mut b = ParticleSystem::new(7);
+        a.advance(STEP, beat(1.0));
+        b.advance(STEP, beat(1.0));
+        for _ in 0..30 {
+            a.advance(STEP * 4.0, Emission::default());
+        }
+        for _ in 0..60 {
+            b.advance(STEP * 2.0, Emission::default());
+        }
+        assert_eq!(a.particles.len(), b.particles.len());
+        for (pa, pb) in a.particles.iter().zip(&b.particles) {
+            assert!(pa.pos.distance(pb.pos) < 1e-3);
+        }
+    }
+
+    #[test]
+    fn particle_count_is_capped() {
+        let mut sys = ParticleSystem::new(3);
+        sys.params.max_particles = 100;
+        for _ in 0..10 {
+            sys.advance(STEP, beat(3.0));
+        }
+        assert!(sys.particles.len() <= 100);
+    }
+
+    #[test]
+    fn brighter_audio_shifts_hue() {
+        let mut dark = ParticleSystem::new(5);
+        let mut bright = ParticleSystem::new(5);
+        dark.params.hue_base = 0.1;
+        bright.params.hue_base = 0.1;
+        dark.advance(
+            STEP,
+            Emission {
+                centroid: 0.0,
+                ..beat(0.0)
+            },
+        );
+        bright.advance(
+            STEP,
+            Emission {
+                centroid: 1.0,
+                ..beat(0.0)
+            },
+        );
+        let mean = |s: &ParticleSystem| {
+            s.particles.iter().map(|p| p.hue).sum::<f32>() / s.particles.len() as f32
+        };
+        assert!((mean(&bright) - mean(&dark) - dark.params.hue_range).abs() < 0.05);
+    }
+}
@@ -1,57 +1,232 @@
+use super::palette::{ColorParams, Palette};
+use super::{Snapshot, Visualizer};
+use crate::analysis::{Trigger, TriggerMode};
+use crate::canvas::{Canvas, rgba};
+use crate::preset::Preset;
 use nannou::prelude::*;
+use serde::{Deserialize, Serialize};
 
+/// Short side of the window the sizes below were tuned for. Lengths, gains and
+/// line weights are in pixels at that size and scale with the actual window.
+const REFERENCE: f32 = 800.0;
+/// Share of the window height the bottom spectrum strip may grow into.
+const STRIP_SHARE: f32 = 0.325;
+
+/// Radial bars around a bass-pulsing ring, a waveform ring inside and a
+/// spectrum strip along the bottom.
+///
+/// Sizes are in reference pixels (an 800 px short side), so the look holds at
+/// any resolution. In portrait windows the ring moves up to leave the strip room.
 pub struct RadialVisualizer {
     pub bars: usize,
+    pub params: RadialParams,
+
+    palette: Palette,
+    time: f32, // seconds since start, for time-mapped colour
+    trigger: Trigger,
+    ring_wave: Vec<f32>, // phase-stabilized slice of the window for the waveform ring
+
+    // live tweaks from the mouse and keymap, kept across presets
+    rotation: f32, // radians, counter-clockwise
+    zoom: f32,
+    hue_shift: f32,
+}
+
+/// The `[radial]` section of a preset.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(from = "RadialFields")]
+pub struct RadialParams {
     pub base_radius: f32,
     pub bar_gain: f32,
-
-    pub hue_base: f32,
-    pub hue_range: f32,
     pub pulse_gain: f32,
+    /// how far the ring draws in over the build-up before a drop, so it
+    /// springs out when the drop lands; file playback only
+    pub tension_gain: f32,
 
     pub fade_alpha: f32,
+
+    pub wave_radius: f32, // waveform ring, as a fraction of `base_radius`
+    pub wave_gain: f32,
+
+    pub color: ColorParams,
 }
 
-impl RadialVisualizer {
-    pub fn new(bars: usize) -> Self {
+impl Default for RadialParams {
+    fn default() -> Self {
         Self {
-            bars,
             base_radius: 150.0,
             bar_gain: 400.0,
-
-            hue_base: 0.0,
-            hue_range: 0.30,
             pulse_gain: 60.0,
+            tension_gain: 40.0,
 
             fade_alpha: 0.12,
+
+            wave_radius: 0.65,
+            wave_gain: 35.0,
+
+            color: ColorParams::default(),
         }
     }
+}
+
+/// `[radial]` as written, including `hue_base` and `hue_range` from before
+/// palettes. They were the rainbow's offset and bass shift, and become those.
+#[derive(Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct RadialFields {
+    base_radius: f32,
+    bar_gain: f32,
+    pulse_gain: f32,
+    tension_gain: f32,
+    fade_alpha: f32,
+    wave_radius: f32,
+    wave_gain: f32,
+    color: ColorParams,
+    hue_base: Option<f32>,
+    hue_range: Option<f32>,
+}
+
+impl Default for RadialFields {
+    fn default() -> Self {
+        let p = RadialParams::default();
+        Self {
+            base_radius: p.base_radius,
+            bar_gain: p.bar_gain,
+            pulse_gain: p.pulse_gain,
+            tension_gain: p.tension_gain,
+            fade_alpha: p.fade_alpha,
+            wave_radius: p.wave_radius,
+            wave_gain: p.wave_gain,
+            color: p.color,
+            hue_base: None,
+            hue_range: None,
+        }
+    }
+}
 
-    pub fn draw(
-        &self,
-        app: &App,
-        frame: Frame,
-        bands: &[f32],
-        bass_fast: f32,
-        bass_smooth: f32,
-        waveform: &[f32],
-    ) {
-        let draw = app.draw();
-        let win = app.window_rect();
-
-        let hue = (self.hue_base + bass_smooth * self.hue_range).fract();
-        let radius = (self.base_radius + bass_fast * self.pulse_gain).clamp(0.0, 350.0);
-        let glow = (0.15 + bass_smooth * 0.35).clamp(0.12, 0.55);
+impl From<RadialFields> for RadialParams {
+    fn from(f: RadialFields) -> Self {
+        let mut color = f.color;
+        if let Some(v) = f.hue_base {
+            color.offset = v;
+        }
+        if let Some(v) = f.hue_range {
+            color.bass_shift = v;
+        }
+        Self {
+            base_radius: f.base_radius,
+            bar_gain: f.bar_gain,
+            pulse_gain: f.pulse_gain,
+            tension_gain: f.tension_gain,
+            fade_alpha: f.fade_alpha,
+            wave_radius: f.wave_radius,
+            wave_gain: f.wave_gain,
+            color,
+        }
+    }
+}
+
+impl RadialVisualizer {
+    pub fn new(bars: usize) -> Self {
+        Self {
+            bars,
+            params: RadialParams::default(),
+            palette: Palette::default(),
+            time: 0.0,
+            trigger: Trigger::new(TriggerMode::Autocorrelation),
+            ring_wave: Vec::new(),
+            rotation: 0.0,
+            zoom: 1.0,
+            hue_shift: 0.0,
+        }
+    }
+}
+
+impl Visualizer for RadialVisualizer {
+    fn name(&self) -> &'static str {
+        "radial"
+    }
+
+    fn mouse_dragged(&mut self, delta: Vec2) {
+        // dragging right turns the ring clockwise, like grabbing its edge
+        self.rotation -= delta.x * 0.005;
+    }
+
+    fn mouse_scrolled(&mut self, lines: f32) {
+        self.zoom = (self.zoom * 1.1f32.powf(lines)).clamp(0.25, 4.0);
+    }
+
+    fn shift_hue(&mut self, delta: f32) {
+        self.hue_shift += delta;
+    }
+
+    fn reset_view(&mut self) {
+        self.rotation = 0.0;
+        self.zoom = 1.0;
+        self.hue_shift = 0.0;
+    }
+
+    fn apply_preset(&mut self, preset: &Preset) {
+        self.params = preset.radial.clone();
+        // presets are validated on load, so this only falls back on a bug
+        self.palette = preset
+            .palette(&self.params.color.palette)
+            .unwrap_or_else(|e| {
+                eprintln!("[palette] {e:#}");
+                Palette::default()
+            });
+    }
+
+    fn update(&mut self, snap: &Snapshot, dt: f32) {
+        self.time += dt;
+        // half the window leaves the trigger a full span to search for a stable phase
+        let span = snap.waveform.len() / 2;
+        let start = self.trigger.find(snap.waveform, span);
+        self.ring_wave.clear();
+        self.ring_wave
+            .extend_from_slice(&snap.waveform[start..(start + span).min(snap.waveform.len())]);
+    }
+
+    fn draw(&self, canvas: &mut dyn Canvas, snap: &Snapshot) {
+        let win = canvas.bounds();
+        let p = &self.params;
+        let unit = win.w().min(win.h()) / REFERENCE;
+        // the ring zooms; the strip along the bottom stays put
+        let ring = unit * self.zoom;
+        // portrait: sit the ring in the top square so the strip gets the rest
+        let center = vec2(0.0, (win.h() - win.w()).max(0.0) * 0.5);
+        let bands = &snap.frame.bands;
+        let bass_fast = snap.frame.bass_fast;
+        let bass_smooth = snap.frame.bass_smooth;
+        let waveform = if self.ring_wave.is_empty() {
+            snap.waveform
+        } else {
+            &self.ring_wave
+        };
+
+        let color = &p.color;
+        let mean = bands.iter().sum::<f32>() / bands.len().max(1) as f32;
+        let tint = |i: usize, level: f32, alpha: f32| {
+            let t = color.position(i, self.bars, level, bass_smooth, self.time) + self.hue_shift;
+            self.palette.rgba(t, alpha)
+        };
+        // eased in, so the pull is felt most in the last bars before the drop
+        let tension = snap
+            .frame
+            .lookahead
+            .map_or(0.0, |ahead| ahead.tension.powi(2));
+        let radius = (p.base_radius + bass_fast * p.pulse_gain - tension * p.tension_gain)
+            .clamp(0.0, 350.0)
+            * ring;
+        let glow = (0.15 + bass_smooth * 0.35 + tension * 0.15).clamp(0.12, 0.55);
 
         // 1. background fade
-        draw.rect()
-            .wh(win.wh())
-            .color(srgba(0.0, 0.0, 0.0, self.fade_alpha));
+        canvas.fill(rgba(0.0, 0.0, 0.0, p.fade_alpha));
 
         // 2. waveform ring — average blocks of samples so the shape is smooth, not noisy
         if !waveform.is_empty() {
-            let wf_radius = self.base_radius * 0.65;
-            let wf_gain = 35.0;
+            let wf_radius = p.base_radius * p.wave_radius * ring;
+            let wf_gain = p.wave_gain * ring;
             let n = 128;
             let chunk = (waveform.len() / n).max(1);
 
@@ -61,8 +236,8 @@ impl RadialVisualizer {
                     let end = (start + chunk).min(waveform.len());
                     let avg = waveform[start..end].iter().sum::<f32>() / (end - start) as f32;
                     let r = wf_radius + avg * wf_gain;
-                    let theta = (i as f32 / n as f32) * TAU;
-                    pt2(theta.cos() * r, theta.sin() * r)
+                    let theta = (i as f32 / n as f32) * TAU + self.rotation;
+                    center + vec2(theta.cos(), theta.sin()) * r
                 })
                 .collect();
 
@@ -71,63 +246,48 @@ impl RadialVisualizer {
                 ring_pts.push(first);
             }
 
-            draw.polyline()
-                .weight(1.5)
-                .points(ring_pts)
-                .color(hsva(hue, 0.7, 1.0, 0.55));
+            // the palette's first colour, washed 30% towards white
+            let [r, g, b, a] = tint(0, mean, 0.55);
+            let wash = |c: f32| c * 0.7 + 0.3;
+            canvas.polyline(&ring_pts, 1.5 * ring, rgba(wash(r), wash(g), wash(b), a));
         }
 
         // 3. radial bars
         let bars_f = self.bars as f32;
         for (i, &v) in bands.iter().take(self.bars).enumerate() {
-            let theta = (i as f32 / bars_f) * TAU;
+            let theta = (i as f32 / bars_f) * TAU + self.rotation;
             let dir = vec2(theta.cos(), theta.sin());
 
-            let len = (v * self.bar_gain).clamp(0.0, 480.0 - radius);
-            let p0 = dir * radius;
-            let p1 = dir * (radius + len);
-
-            let h = (hue + (i as f32 / bars_f) * 1.0).fract();
+            let len = (v * p.bar_gain * ring).clamp(0.0, (480.0 * ring - radius).max(0.0));
+            let p0 = center + dir * radius;
+            let p1 = center + dir * (radius + len);
 
             // outer glow
-            draw.line()
-                .start(p0)
-                .end(p1)
-                .weight(6.0)
-                .color(hsva(h, 1.0, 1.0, glow));
+            canvas.line(p0, p1, 6.0 * ring, tint(i, v, glow));
 
             // bright core
-            draw.line()
-                .start(p0)
-                .end(p1)
-                .weight(2.0)
-                .color(hsva(h, 1.0, 1.0, 0.9));
+            canvas.line(p0, p1, 2.0 * ring, tint(i, v, 0.9));
         }
 
         // 4. bottom spectrum bar
         let bar_w = win.w() / bars_f;
         let base_y = win.bottom() + 2.0;
+        // the strip spans the full height, so it scales with that rather than `unit`
+        let strip_unit = win.h() / REFERENCE;
         for (i, &v) in bands.iter().take(self.bars).enumerate() {
-            let bar_h = (v * self.bar_gain * 0.65).clamp(0.0, 260.0);
+            let bar_h = (v * p.bar_gain * 0.65 * strip_unit).clamp(0.0, win.h() * STRIP_SHARE);
             if bar_h < 1.0 {
                 continue;
             }
             let x = win.left() + (i as f32 + 0.5) * bar_w;
-            let h = (hue + (i as f32 / bars_f) * 1.0).fract();
+
+            let center = pt2(x, base_y + bar_h * 0.5);
 
             // glow layer
-            draw.rect()
-                .x_y(x, base_y + bar_h * 0.5)
-                .w_h(bar_w - 1.0, bar_h)
-                .color(hsva(h, 1.0, 1.0, glow));
+            canvas.rect(center, vec2(bar_w - 1.0, bar_h), tint(i, v, glow));
 
             // bright core
-            draw.rect()
-                .x_y(x, base_y + bar_h * 0.5)
-                .w_h((bar_w - 1.0) * 0.4, bar_h)
-                .color(hsva(h, 1.0, 1.0, 0.9));
+            canvas.rect(center, vec2((bar_w - 1.0) * 0.4, bar_h), tint(i, v, 0.9));
         }
-
-        draw.to_frame(app, &frame).unwrap();
     }
 }
@@ -0,0 +1,118 @@
+use super::{Snapshot, Visualizer};
+use crate::canvas::Canvas;
+use crate::preset::Preset;
+use nannou::prelude::*;
+
+/// Owns every built-in visualizer and tracks which one is on screen.
+pub struct Registry {
+    visuals: Vec<Box<dyn Visualizer>>,
+    active: usize,
+    fading_from: Option<usize>,
+    fade_t: f32, // 0 → 1 over `crossfade_secs`
+
+    pub crossfade_secs: f32, // 0 = hard cut
+}
+
+impl Registry {
+    pub fn new(visuals: Vec<Box<dyn Visualizer>>) -> Self {
+        assert!(
+            !visuals.is_empty(),
+            "Registry needs at least one visualizer"
+        );
+        Self {
+            visuals,
+            active: 0,
+            fading_from: None,
+            fade_t: 1.0,
+            crossfade_secs: 0.0,
+        }
+    }
+
+    pub fn names(&self) -> impl Iterator<Item = &'static str> + '_ {
+        self.visuals.iter().map(|v| v.name())
+    }
+
+    pub fn index_of(&self, name: &str) -> Option<usize> {
+        self.visuals.iter().position(|v| v.name() == name)
+    }
+
+    pub fn active_name(&self) -> &'static str {
+        self.visuals[self.active].name()
+    }
+
+    /// Switches to visualizer `idx`, fading from the current one if enabled.
+    /// Out-of-range indices are ignored.
+    pub fn select(&mut self, idx: usize) {
+        if idx >= self.visuals.len() || idx == self.active {
+            return;
+        }
+        if self.crossfade_secs > 0.0 {
+            self.fading_from = Some(self.active);
+            self.fade_t = 0.0;
+        }
+        self.active = idx;
+    }
+
+    /// Steps through the visualizers in registration order, wrapping around.
+    pub fn cycle(&mut self, step: isize) {
+        let n = self.visuals.len() as isize;
+        self.select((self.active as isize + step).rem_euclid(n) as usize);
+    }
+
+    pub fn update(&mut self, snap: &Snapshot, dt: f32) {
+        if let Some(from) = self.fading_from {
+            self.fade_t += dt / self.crossfade_secs.max(1e-3);
+            if self.fade_t >= 1.0 {
+                self.fade_t = 1.0;
+                self.fading_from = None;
+            } else {
+                self.visuals[from].update(snap, dt);
+            }
+        }
+        self.visuals[self.active].update(snap, dt);
+    }
+
+    /// Hands `preset` to every visualizer, not only the active one, so
+    /// switching views keeps the look.
+    pub fn apply_preset(&mut self, preset: &Preset) {
+        for v in &mut self.visuals {
+            v.apply_preset(preset);
+        }
+        self.crossfade_secs = preset.crossfade;
+    }
+
+    pub fn key_pressed(&mut self, key: Key) {
+        self.visuals[self.active].key_pressed(key);
+    }
+
+    pub fn mouse_dragged(&mut self, delta: Vec2) {
+        self.visuals[self.active].mouse_dragged(delta);
+    }
+
+    pub fn mouse_scrolled(&mut self, lines: f32) {
+        self.visuals[self.active].mouse_scrolled(lines);
+    }
+
+    /// Hue and reset reach every visualizer, like presets do.
+    pub fn shift_hue(&mut self, delta: f32) {
+        for v in &mut self.visuals {
+            v.shift_hue(delta);
+        }
+    }
+
+    pub fn reset_view(&mut self) {
+        for v in &mut self.visuals {
+            v.reset_view();
+        }
+    }
+
+    pub fn draw(&self, canvas: &mut dyn Canvas, snap: &Snapshot) {
+        if let Some(from) = self.fading_from {
+            canvas.set_opacity(1.0 - self.fade_t);
+            self.visuals[from].draw(canvas, snap);
+            canvas.set_opacity(self.fade_t);
+        }
+        self.visuals[self.active].draw(canvas, snap);
+        canvas.set_opacity(1.0);
+    }
+}
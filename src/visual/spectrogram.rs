@@ -0,0 +1,158 @@
+use super::{Snapshot, Visualizer};
+use crate::canvas::{Canvas, rgba};
+use crate::preset::Preset;
+use nannou::prelude::*;
+use serde::{Deserialize, Serialize};
+
+// 9 evenly spaced stops sampled from matplotlib's colormaps
+const VIRIDIS: [(f32, f32, f32); 9] = [
+    (0.267, 0.004, 0.329),
+    (0.278, 0.176, 0.482),
+    (0.231, 0.322, 0.545),
+    (0.173, 0.447, 0.557),
+    (0.129, 0.569, 0.549),
+    (0.157, 0.682, 0.502),
+    (0.369, 0.788, 0.384),
+    (0.678, 0.863, 0.188),
+    (0.992, 0.906, 0.145),
+];
+
+const MAGMA: [(f32, f32, f32); 9] = [
+    (0.001, 0.000, 0.016),
+    (0.110, 0.063, 0.267),
+    (0.310, 0.071, 0.482),
+    (0.506, 0.145, 0.506),
+    (0.710, 0.212, 0.478),
+    (0.898, 0.314, 0.392),
+    (0.984, 0.529, 0.380),
+    (0.996, 0.761, 0.529),
+    (0.988, 0.992, 0.749),
+];
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, clap::ValueEnum, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Colormap {
+    Viridis,
+    Magma,
+    Grayscale,
+}
+
+impl Colormap {
+    /// Maps `t` in [0, 1] to an RGB triple.
+    pub fn sample(self, t: f32) -> (f32, f32, f32) {
+        let t = t.clamp(0.0, 1.0);
+        let stops = match self {
+            Self::Viridis => &VIRIDIS,
+            Self::Magma => &MAGMA,
+            Self::Grayscale => return (t, t, t),
+        };
+        let pos = t * (stops.len() - 1) as f32;
+        let i = (pos as usize).min(stops.len() - 2);
+        let f = pos - i as f32;
+        let (a, b) = (stops[i], stops[i + 1]);
+        (
+            a.0 + (b.0 - a.0) * f,
+            a.1 + (b.1 - a.1) * f,
+            a.2 + (b.2 - a.2) * f,
+        )
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, clap::ValueEnum, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Scroll {
+    /// Time runs right-to-left, low frequencies at the bottom
+    Horizontal,
+    /// Classic waterfall: newest row on top, low frequencies on the left
+    Vertical,
+}
+
+/// The `[spectrogram]` section of a preset.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct SpectrogramParams {
+    pub colormap: Colormap,
+    pub scroll: Scroll,
+    pub min_db: f32,
+    pub max_db: f32,
+}
+
+impl Default for SpectrogramParams {
+    fn default() -> Self {
+        Self {
+            colormap: Colormap::Viridis,
+            scroll: Scroll::Horizontal,
+            min_db: -80.0,
+            max_db: -10.0,
+        }
+    }
+}
+
+pub struct SpectrogramVisualizer {
+    pub params: SpectrogramParams,
+}
+
+impl SpectrogramVisualizer {
+    pub fn new() -> Self {
+        Self {
+            params: SpectrogramParams::default(),
+        }
+    }
+}
+
+impl Visualizer for SpectrogramVisualizer {
+    fn name(&self) -> &'static str {
+        "spectrogram"
+    }
+
+    fn apply_preset(&mut self, preset: &Preset) {
+        self.params = preset.spectrogram.clone();
+    }
+
+    fn draw(&self, canvas: &mut dyn Canvas, snap: &Snapshot) {
+        let win = canvas.bounds();
+        let history = snap.history;
+        let p = &self.params;
+
+        canvas.fill(rgba(0.0, 0.0, 0.0, 1.0));
+
+        let cols = history.capacity();
+        let bins = history.bins();
+        if history.is_empty() || bins == 0 {
+            return;
+        }
+
+        // time axis and frequency axis lengths depend on scroll direction
+        let (time_len, freq_len) = match p.scroll {
+            Scroll::Horizontal => (win.w(), win.h()),
+            Scroll::Vertical => (win.h(), win.w()),
+        };
+        let cell_t = time_len / cols as f32;
+        let cell_f = freq_len / bins as f32;
+        let range = (p.max_db - p.min_db).max(1.0);
+
+        // one flat-coloured cell per band per frame, batched so the window backend
+        // can submit them as a single mesh
+        let mut cells = Vec::with_capacity(history.len() * bins);
+        for age in 0..history.len() {
+            let Some(row) = history.row(age) else { break };
+            // newest row sits at the far end of the time axis
+            let t = time_len - (age as f32 + 0.5) * cell_t;
+            for (bin, &db) in row.iter().enumerate() {
+                let f = (bin as f32 + 0.5) * cell_f;
+                let (r, g, b) = p.colormap.sample((db - p.min_db) / range);
+                let (center, size) = match p.scroll {
+                    Scroll::Horizontal => {
+                        (pt2(win.left() + t, win.bottom() + f), vec2(cell_t, cell_f))
+                    }
+                    Scroll::Vertical => {
+                        (pt2(win.left() + f, win.bottom() + t), vec2(cell_f, cell_t))
+                    }
+                };
+                // a little overlap hides anti-aliasing seams between neighbours
+                cells.push((center, size + 1.0, rgba(r, g, b, 1.0)));
+            }
+        }
+        canvas.rects(&cells);
+    }
+}
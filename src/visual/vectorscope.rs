@@ -0,0 +1,133 @@
+use super::{Snapshot, Visualizer};
+use crate::analysis::StereoMeter;
+use crate::canvas::{Align, Canvas, rgba};
+use crate::preset::Preset;
+use nannou::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::f32::consts::FRAC_1_SQRT_2;
+
+/// Goniometer: left/right plotted rotated 45° so mono sits on the vertical
+/// (mid) axis and out-of-phase content spreads along the horizontal (side)
+/// axis. Old traces fade out slowly for persistence. A phase correlation
+/// meter and balance readout sit underneath.
+pub struct VectorscopeVisualizer {
+    pub params: VectorscopeParams,
+    meter: StereoMeter,
+}
+
+/// The `[vectorscope]` section of a preset.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct VectorscopeParams {
+    pub gain: f32,
+    pub persistence: f32, // 0 = no trails, closer to 1 = longer trails
+}
+
+impl Default for VectorscopeParams {
+    fn default() -> Self {
+        Self {
+            gain: 1.0,
+            persistence: 0.85,
+        }
+    }
+}
+
+impl VectorscopeVisualizer {
+    pub fn new() -> Self {
+        Self {
+            params: VectorscopeParams::default(),
+            meter: StereoMeter::new(),
+        }
+    }
+}
+
+impl Visualizer for VectorscopeVisualizer {
+    fn name(&self) -> &'static str {
+        "vectorscope"
+    }
+
+    fn apply_preset(&mut self, preset: &Preset) {
+        self.params = preset.vectorscope.clone();
+    }
+
+    fn update(&mut self, snap: &Snapshot, _dt: f32) {
+        self.meter.update(snap.stereo);
+    }
+
+    fn key_pressed(&mut self, key: Key) {
+        match key {
+            Key::Up => self.params.gain = (self.params.gain * 1.25).min(32.0),
+            Key::Down => self.params.gain = (self.params.gain / 1.25).max(0.1),
+            _ => {}
+        }
+    }
+
+    fn draw(&self, canvas: &mut dyn Canvas, snap: &Snapshot) {
+        let win = canvas.bounds();
+        let fade = (1.0 - self.params.persistence).clamp(0.02, 1.0);
+        canvas.fill(rgba(0.0, 0.0, 0.0, fade));
+
+        let meter_h = 70.0;
+        let scope = Rect::from_w_h(win.w(), win.h() - meter_h).align_top_of(win);
+        let size = scope.w().min(scope.h()) * 0.45;
+        let c = scope.xy();
+        let grid = rgba(0.25, 0.25, 0.25, 1.0);
+
+        // axes: L/R diagonals, plus the mid (vertical) and side (horizontal) lines
+        for (dx, dy) in [
+            (0.0, 1.0),
+            (1.0, 0.0),
+            (FRAC_1_SQRT_2, FRAC_1_SQRT_2),
+            (-FRAC_1_SQRT_2, FRAC_1_SQRT_2),
+        ] {
+            let d = vec2(dx, dy) * size;
+            canvas.line(c - d, c + d, 1.0, grid);
+        }
+        canvas.text("L", c + vec2(-0.75, 0.75) * size, 14, Align::Center, grid);
+        canvas.text("R", c + vec2(0.75, 0.75) * size, 14, Align::Center, grid);
+
+        // mid = (L + R) / √2 on y, side = (R - L) / √2 on x
+        let pts: Vec<Point2> = snap
+            .stereo
+            .iter()
+            .map(|&[l, r]| {
+                let side = (r - l) * FRAC_1_SQRT_2 * self.params.gain;
+                let mid = (l + r) * FRAC_1_SQRT_2 * self.params.gain;
+                c + vec2(side.clamp(-1.0, 1.0), mid.clamp(-1.0, 1.0)) * size
+            })
+            .collect();
+        canvas.polyline(&pts, 1.0, rgba(0.3, 1.0, 0.6, 0.35));
+
+        // correlation meter (-1 … +1) and balance (L … R)
+        let bar_w = win.w() * 0.7;
+        let rows = [
+            ("corr", self.meter.correlation, "-1", "+1"),
+            ("bal", self.meter.balance, "L", "R"),
+        ];
+        let text = rgba(0.7, 0.7, 0.7, 1.0);
+        for (row, (label, value, lo, hi)) in rows.into_iter().enumerate() {
+            let y = win.bottom() + meter_h - 22.0 - row as f32 * 26.0;
+            canvas.rect(pt2(0.0, y), vec2(bar_w, 8.0), rgba(0.15, 0.15, 0.15, 1.0));
+            canvas.line(pt2(0.0, y - 8.0), pt2(0.0, y + 8.0), 1.0, grid);
+
+            // negative correlation is a phase problem, so it shows red
+            let color = if label == "corr" && value < 0.0 {
+                rgba(1.0, 0.25, 0.2, 1.0)
+            } else {
+                rgba(0.3, 1.0, 0.6, 1.0)
+            };
+            let x = value.clamp(-1.0, 1.0) * bar_w * 0.5;
+            canvas.rect(pt2(x * 0.5, y), vec2(x.abs().max(2.0), 8.0), color);
+
+            canvas.text(lo, pt2(-bar_w * 0.5 - 14.0, y), 12, Align::Center, text);
+            canvas.text(hi, pt2(bar_w * 0.5 + 14.0, y), 12, Align::Center, text);
+            canvas.text(
+                &format!("{label} {value:+.2}"),
+                pt2(win.left() + 12.0, y),
+                12,
+                Align::Left,
+                text,
+            );
+        }
+    }
+}
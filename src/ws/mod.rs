@@ -0,0 +1,332 @@
+//! Analysis frames over WebSocket for browser overlays, plus a demo page
+//! served from the same port.
+
+use crate::analysis::{AnalysisFrame, LevelMeter};
+use crate::pace::Pacer;
+use anyhow::Context;
+use serde::Serialize;
+use std::io::{Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::sync::mpsc::{Receiver, RecvTimeoutError, SyncSender, TrySendError};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tungstenite::{Message, WebSocket};
+
+const DEMO_PAGE: &str = include_str!("demo.html");
+
+/// Frames a slow client may fall behind by before it starts missing some.
+const QUEUE: usize = 8;
+
+type Clients = Arc<Mutex<Vec<SyncSender<Message>>>>;
+
+/// One frame as sent to clients. Binary mode sends the same fields as
+/// little-endian f32s in this order, bands last (`onset` is 0 or 1, a
+/// missing `bpm` is 0).
+#[derive(Serialize)]
+struct Frame {
+    t: f32,
+    bass_fast: f32,
+    bass_smooth: f32,
+    rms_db: f32,
+    peak_db: f32,
+    /// an onset happened since the previous frame sent
+    onset: bool,
+    onset_strength: f32,
+    centroid: f32,
+    bpm: Option<f32>,
+    /// kind of song section, once known; JSON only
+    section: Option<&'static str>,
+    bands: Vec<f32>,
+}
+
+impl Frame {
+    fn json(&self) -> Message {
+        Message::text(serde_json::to_string(self).expect("frames serialize"))
+    }
+
+    fn binary(&self) -> Message {
+        let head = [
+            self.t,
+            self.bass_fast,
+            self.bass_smooth,
+            self.rms_db,
+            self.peak_db,
+            self.onset as u8 as f32,
+            self.onset_strength,
+            self.centroid,
+            self.bpm.unwrap_or(0.0),
+        ];
+        let bytes = head
+            .iter()
+            .chain(&self.bands)
+            .flat_map(|v| v.to_le_bytes())
+            .collect::<Vec<u8>>();
+        Message::binary(bytes)
+    }
+}
+
+/// Three decimals is plenty for drawing and keeps the JSON small.
+fn round(v: f32) -> f32 {
+    (v * 1000.0).round() / 1000.0
+}
+
+/// Pushes analysis frames to every connected WebSocket client.
+pub struct WsServer {
+    clients: Clients,
+    started: Instant,
+    /// most frames per second; 0 sends every analysis frame
+    pacer: Pacer,
+    binary: bool,
+    onset: bool,
+    onset_strength: f32,
+}
+
+impl WsServer {
+    /// Listens on `addr` (`host:port`, or just a port for every interface).
+    pub fn bind(addr: &str, rate: f32, binary: bool) -> anyhow::Result<Self> {
+        let addr = match addr.parse::<u16>() {
+            Ok(port) => format!("0.0.0.0:{port}"),
+            Err(_) => addr.to_string(),
+        };
+        let listener = TcpListener::bind(&addr)
+            .with_context(|| format!("Can't listen for WebSocket clients on {addr}"))?;
+        let local = listener.local_addr()?;
+        eprintln!("[ws] serving on {local}, demo page at http://{local}/");
+        let clients = Clients::default();
+        let shared = clients.clone();
+        std::thread::spawn(move || {
+            let mut backoff = Duration::ZERO;
+            for stream in listener.incoming() {
+                let stream = match stream {
+                    Ok(stream) => stream,
+                    // e.g. out of file descriptors: wait for some to close
+                    // rather than spin on the same error
+                    Err(e) => {
+                        backoff =
+                            (backoff * 2).clamp(Duration::from_millis(50), Duration::from_secs(2));
+                        eprintln!("[ws] accept failed, retrying in {backoff:?}: {e}");
+                        std::thread::sleep(backoff);
+                        continue;
+                    }
+                };
+                backoff = Duration::ZERO;
+                let clients = shared.clone();
+                std::thread::spawn(move || {
+                    let peer = stream.peer_addr().ok();
+                    if let Err(e) = handle(stream, clients) {
+                        eprintln!("[ws] {}: {e:#}", peer.map_or("?".into(), |p| p.to_string()));
+                    }
+                });
+            }
+        });
+        Ok(Self {
+            clients,
+            started: Instant::now(),
+            pacer: Pacer::new(rate),
+            binary,
+            onset: false,
+            onset_strength: 0.0,
+        })
+    }
+
+    /// Called once per analysis frame, `dt` seconds after the previous one.
+    pub fn frame(&mut self, frame: &AnalysisFrame, levels: &LevelMeter, bpm: Option<f32>, dt: f32) {
+        self.onset |= frame.onset;
+        self.onset_strength = self.onset_strength.max(frame.onset_strength);
+        if !self.pacer.tick(dt) {
+            return;
+        }
+
+        let mut clients = self.clients.lock().unwrap();
+        if clients.is_empty() {
+            return;
+        }
+        let out = Frame {
+            t: round(self.started.elapsed().as_secs_f32()),
+            bass_fast: round(frame.bass_fast),
+            bass_smooth: round(frame.bass_smooth),
+            rms_db: round(levels.rms_db),
+            peak_db: round(levels.peak_db),
+            onset: std::mem::take(&mut self.onset),
+            onset_strength: round(std::mem::take(&mut self.onset_strength)),
+            centroid: round(frame.centroid),
+            bpm: bpm.map(round),
+            section: frame.section.map(|s| s.kind.name()),
+            bands: frame.bands.iter().copied().map(round).collect(),
+        };
+        let msg = if self.binary {
+            out.binary()
+        } else {
+            out.json()
+        };
+        // a full queue means the client is slow: it misses this frame
+        clients
+            .retain(|tx| !matches!(tx.try_send(msg.clone()), Err(TrySendError::Disconnected(_))));
+    }
+}
+
+/// Serves the demo page to plain HTTP requests and upgrades WebSocket ones.
+fn handle(stream: TcpStream, clients: Clients) -> anyhow::Result<()> {
+    stream.set_read_timeout(Some(Duration::from_secs(5)))?;
+    let head = peek_head(&stream)?;
+    if head.to_ascii_lowercase().contains("upgrade: websocket") {
+        let ws = tungstenite::accept(stream).context("handshake failed")?;
+        let (tx, rx) = std::sync::mpsc::sync_channel(QUEUE);
+        clients.lock().unwrap().push(tx);
+        return serve(ws, rx);
+    }
+
+    let mut stream = stream;
+    let mut request = vec![0u8; head.len()];
+    stream.read_exact(&mut request)?;
+    let path = head.split_whitespace().nth(1).unwrap_or("/");
+    let (status, kind, body) = match path {
+        "/" | "/index.html" => ("200 OK", "text/html; charset=utf-8", DEMO_PAGE),
+        _ => ("404 Not Found", "text/plain", "not found\n"),
+    };
+    write!(
+        stream,
+        "HTTP/1.1 {status}\r\nContent-Type: {kind}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{body}",
+        body.len()
+    )?;
+    Ok(())
+}
+
+/// The request line and headers, left in the socket for whoever reads next.
+fn peek_head(stream: &TcpStream) -> anyhow::Result<String> {
+    let mut buf = [0u8; 8192];
+    for _ in 0..500 {
+        let n = stream.peek(&mut buf)?;
+        anyhow::ensure!(n > 0, "connection closed");
+        if let Some(end) = buf[..n].windows(4).position(|w| w == b"\r\n\r\n") {
+            return Ok(String::from_utf8_lossy(&buf[..end + 4]).into_owned());
+        }
+        anyhow::ensure!(n < buf.len(), "request headers too long");
+        std::thread::sleep(Duration::from_millis(10));
+    }
+    anyhow::bail!("request headers never finished")
+}
+
+/// Forwards queued frames to one client until it goes away. Reads in
+/// between so pings get answered and closes noticed.
+fn serve(mut ws: WebSocket<TcpStream>, rx: Receiver<Message>) -> anyhow::Result<()> {
+    ws.get_ref()
+        .set_read_timeout(Some(Duration::from_millis(1)))?;
+    loop {
+        match rx.recv_timeout(Duration::from_millis(50)) {
+            Ok(msg) => ws.send(msg)?,
+            Err(RecvTimeoutError::Timeout) => {}
+            Err(RecvTimeoutError::Disconnected) => return Ok(()),
+        }
+        match ws.read() {
+            Ok(Message::Close(_)) => return Ok(()),
+            Ok(_) => {}
+            Err(tungstenite::Error::Io(e))
+                if matches!(
+                    e.kind(),
+                    std::io::ErrorKind::WouldBlock | std::io::ErrorKind::TimedOut
+                ) => {}
+            Err(tungstenite::Error::ConnectionClosed) => return Ok(()),
+            Err(e) => return Err(e.into()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn frame() -> AnalysisFrame {
+        AnalysisFrame {
+            bands: vec![0.25, 0.5],
+            bass_fast: 0.123456,
+            onset: true,
+            ..Default::default()
+        }
+    }
+
+    /// Sends frames until the client sees one, since it may connect a
+    /// moment after the server registers it. Gives up after about 5 s.
+    fn receive(server: &mut WsServer, client: &mut WebSocket<impl Read + Write>) -> Message {
+        let levels = LevelMeter::new();
+        let started = std::time::Instant::now();
+        loop {
+            assert!(
+                started.elapsed() < Duration::from_secs(5),
+                "no frame reached the client"
+            );
+            server.frame(&frame(), &levels, Some(120.0), 0.02);
+            match client.read() {
+                Ok(msg) => return msg,
+                Err(tungstenite::Error::Io(e))
+                    if matches!(
+                        e.kind(),
+                        std::io::ErrorKind::WouldBlock | std::io::ErrorKind::TimedOut
+                    ) => {}
+                Err(e) => panic!("{e}"),
+            }
+        }
+    }
+
+    fn connect(port: u16) -> WebSocket<TcpStream> {
+        let stream = TcpStream::connect(("127.0.0.1", port)).unwrap();
+        stream
+            .set_read_timeout(Some(Duration::from_millis(50)))
+            .unwrap();
+        let (ws, _) = tungstenite::client(format!("ws://127.0.0.1:{port}/"), stream).unwrap();
+        ws
+    }
+
+    fn bind(binary: bool) -> (WsServer, u16) {
+        // find a free port first: the server doesn't hand its listener back
+        let port = TcpListener::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap()
+            .port();
+        (
+            WsServer::bind(&format!("127.0.0.1:{port}"), 0.0, binary).unwrap(),
+            port,
+        )
+    }
+
+    #[test]
+    fn streams_json_to_every_client() {
+        let (mut server, port) = bind(false);
+        let mut a = connect(port);
+        let mut b = connect(port);
+        for client in [&mut a, &mut b] {
+            let Message::Text(text) = receive(&mut server, client) else {
+                panic!("expected text");
+            };
+            let v: serde_json::Value = serde_json::from_str(&text).unwrap();
+            assert_eq!(v["bands"], serde_json::json!([0.25, 0.5]));
+            assert_eq!(v["bass_fast"].as_f64().unwrap() as f32, 0.123);
+            assert_eq!(v["bpm"], 120.0);
+        }
+    }
+
+    #[test]
+    fn binary_frames_and_demo_page() {
+        let (mut server, port) = bind(true);
+        let mut ws = connect(port);
+        let Message::Binary(bytes) = receive(&mut server, &mut ws) else {
+            panic!("expected binary");
+        };
+        let floats: Vec<f32> = bytes
+            .chunks(4)
+            .map(|c| f32::from_le_bytes(c.try_into().unwrap()))
+            .collect();
+        assert_eq!(floats.len(), 9 + 2);
+        assert_eq!(floats[8], 120.0);
+        assert_eq!(&floats[9..], [0.25, 0.5]);
+
+        let mut http = TcpStream::connect(("127.0.0.1", port)).unwrap();
+        http.write_all(b"GET / HTTP/1.1\r\nHost: x\r\n\r\n")
+            .unwrap();
+        let mut page = String::new();
+        http.read_to_string(&mut page).unwrap();
+        assert!(page.starts_with("HTTP/1.1 200 OK"));
+        assert!(page.contains("<canvas"));
+    }
+}